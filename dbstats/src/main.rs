@@ -1,6 +1,17 @@
-use clap::Parser;
-use morello::db::FilesDatabase;
-use std::path;
+use anyhow::{anyhow, bail, Result};
+use clap::{Parser, ValueEnum};
+use itertools::Itertools;
+use morello::cost::MainCost;
+use morello::db::{ActionCostVec, Database, FilesDatabase, PackedDatabase};
+use morello::memorylimits::{MemVec, MemoryLimits};
+use morello::pprint::{pprint, ImplPrintStyle};
+use morello::scheduling::Action;
+use morello::spec::{LogicalSpec, Spec};
+use morello::target::{ArmTarget, CpuTarget, Target, TargetId, X86Target};
+use morello::utils::bit_length_inverse;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::{io, path};
 
 #[cfg(not(target_env = "msvc"))]
 use tikv_jemallocator::Jemalloc;
@@ -9,11 +20,53 @@ use tikv_jemallocator::Jemalloc;
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
+const BINARY_SCALE_SHAPES: bool = true;
 const K: u8 = 1;
 
 #[derive(clap::Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    subcmd: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Write statistics about a database to stdout
+    Analyze(AnalyzeCmd),
+
+    /// Write a database into a single read-only file for memory-mapped lookups
+    Pack(PackCmd),
+
+    /// Print the stored decision for a Spec
+    Query(QueryCmd),
+
+    /// Compare the entries of two databases built with the same settings
+    Diff(DiffCmd),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AnalyzeReport {
+    /// RLE run counts and lengths of each block's arrays (CSV only)
+    Runs,
+    /// The fraction of each stored block which is filled, per SpecKey
+    Fill,
+    /// Histograms of stored main costs, in power-of-two buckets, per SpecKey
+    MainCosts,
+    /// The most common kinds of stored Actions, per Spec type
+    Actions,
+    /// Average Impl depth, per SpecKey
+    Depth,
+}
+
+#[derive(clap::Args)]
+struct AnalyzeCmd {
     #[arg(
         short,
         long,
@@ -24,12 +77,574 @@ struct Args {
     db: path::PathBuf,
     #[arg(long, default_value = "128", help = "Cache size in database pages.")]
     cache_size: usize,
+    /// The report to write
+    #[arg(long, value_enum, default_value_t = AnalyzeReport::Runs)]
+    report: AnalyzeReport,
+    /// Output format
+    #[arg(long, short, value_enum, default_value_t = ReportFormat::Csv)]
+    format: ReportFormat,
+    /// Target architecture, used to decode stored Actions
+    #[arg(long, value_enum, hide_default_value = true, default_value_t = TargetId::default())]
+    target: TargetId,
+}
+
+#[derive(clap::Args)]
+struct PackCmd {
+    db: path::PathBuf,
+    output: path::PathBuf,
 }
 
-fn main() {
+#[derive(clap::Args)]
+struct QueryCmd {
+    /// A database directory or a file written by `pack`
+    #[arg(long)]
+    db: path::PathBuf,
+    #[arg(long, default_value = "128", help = "Cache size in database pages.")]
+    cache_size: usize,
+    /// Target architecture
+    #[arg(long, value_enum, hide_default_value = true, default_value_t = TargetId::default())]
+    target: TargetId,
+    /// Impl style
+    #[arg(long, value_enum, default_value_t = ImplPrintStyle::Compact)]
+    impl_style: ImplPrintStyle,
+    /// The Spec to look up, e.g. "(Zero((4×4, u32)), [64, 1024, 32768, 1073741824])"
+    spec: String,
+}
+
+#[derive(clap::Args)]
+struct DiffCmd {
+    a: path::PathBuf,
+    b: path::PathBuf,
+    /// Output format
+    #[arg(long, short, value_enum, default_value_t = ReportFormat::Csv)]
+    format: ReportFormat,
+    /// Report per-SpecKey point counts and main cost deltas instead of individual entries
+    #[arg(long, default_value_t = false)]
+    summary: bool,
+}
+
+#[derive(Serialize)]
+struct FillRow {
+    spec_key: String,
+    blocks: u64,
+    points: u64,
+    filled_points: u64,
+    fill_ratio: f64,
+    mean_block_fill_ratio: f64,
+}
+
+#[derive(Serialize)]
+struct MainCostBucketRow {
+    spec_key: String,
+    main_cost_min: MainCost,
+    main_cost_max: MainCost,
+    points: u64,
+}
+
+#[derive(Serialize)]
+struct ActionKindRow {
+    spec_type: String,
+    action_kind: String,
+    points: u64,
+    fraction: f64,
+}
+
+#[derive(Serialize)]
+struct DepthRow {
+    spec_key: String,
+    points: u64,
+    mean_depth: f64,
+    max_depth: u8,
+}
+
+#[derive(Serialize)]
+struct DiffEntryRow {
+    spec_key: String,
+    superblock: String,
+    block_pt: String,
+    inner_pt: String,
+    points: usize,
+    kind: &'static str,
+    a_actions: Option<String>,
+    b_actions: Option<String>,
+    a_main: Option<MainCost>,
+    b_main: Option<MainCost>,
+}
+
+#[derive(Serialize, Default)]
+struct DiffSummaryRow {
+    spec_key: String,
+    only_in_a: u64,
+    only_in_b: u64,
+    changed_actions: u64,
+    unchanged_actions: u64,
+    main_delta_min: Option<i64>,
+    main_delta_p25: Option<i64>,
+    main_delta_median: Option<i64>,
+    main_delta_p75: Option<i64>,
+    main_delta_max: Option<i64>,
+    main_delta_mean: Option<f64>,
+}
+
+fn main() -> Result<()> {
     env_logger::init();
     let args = Args::parse();
-    // TODO: This should gather the tiling_depth from the existing database, not be `None`.
-    let db = FilesDatabase::new(Some(&args.db), true, K, args.cache_size, 1, None);
-    db.analyze(args.keep_going);
+    match args.subcmd {
+        Subcommand::Analyze(cmd) => analyze(&cmd)?,
+        Subcommand::Pack(cmd) => {
+            PackedDatabase::pack(&cmd.db, &cmd.output, BINARY_SCALE_SHAPES, K)?;
+            let packed = PackedDatabase::open(&cmd.output)?;
+            log::info!(
+                "Packed {} blocks into {}",
+                packed.block_count(),
+                cmd.output.display()
+            );
+        }
+        Subcommand::Query(cmd) => {
+            if cmd.db.is_file() {
+                let db = PackedDatabase::open(&cmd.db)?;
+                query_per_target(&cmd, &db)?;
+            } else {
+                let tiling_depth = FilesDatabase::stored_tiling_depth(&cmd.db)?;
+                let db = FilesDatabase::new(
+                    Some(&cmd.db),
+                    BINARY_SCALE_SHAPES,
+                    K,
+                    cmd.cache_size,
+                    1,
+                    tiling_depth,
+                );
+                query_per_target(&cmd, &db)?;
+            }
+        }
+        Subcommand::Diff(cmd) => diff(&cmd)?,
+    }
+    Ok(())
+}
+
+fn analyze(cmd: &AnalyzeCmd) -> Result<()> {
+    match cmd.report {
+        AnalyzeReport::Runs => {
+            if matches!(cmd.format, ReportFormat::Json) {
+                bail!("The runs report can only be written as CSV");
+            }
+            let tiling_depth = FilesDatabase::stored_tiling_depth(&cmd.db)?;
+            let db = FilesDatabase::new(
+                Some(&cmd.db),
+                BINARY_SCALE_SHAPES,
+                K,
+                cmd.cache_size,
+                1,
+                tiling_depth,
+            );
+            db.analyze(cmd.keep_going);
+            Ok(())
+        }
+        AnalyzeReport::Fill => fill_report(cmd),
+        AnalyzeReport::MainCosts => main_costs_report(cmd),
+        AnalyzeReport::Actions => match cmd.target {
+            TargetId::X86 => actions_report::<X86Target>(cmd),
+            TargetId::Arm => actions_report::<ArmTarget>(cmd),
+        },
+        AnalyzeReport::Depth => depth_report(cmd),
+    }
+}
+
+fn fill_report(cmd: &AnalyzeCmd) -> Result<()> {
+    // Filled and total points for each block, keyed by SpecKey first.
+    let mut blocks = BTreeMap::<(String, path::PathBuf, Vec<u32>), (u64, u64)>::new();
+    FilesDatabase::visit_entries(&cmd.db, |entry| {
+        let key = (
+            spec_key_label(entry.superblock_path),
+            entry.superblock_path.to_owned(),
+            entry.block_pt.to_vec(),
+        );
+        let (filled, points) = blocks.entry(key).or_default();
+        let count = u64::try_from(entry.count).unwrap();
+        if entry.entry.is_some() {
+            *filled += count;
+        }
+        *points += count;
+    })?;
+
+    let rows = blocks
+        .into_iter()
+        .chunk_by(|((spec_key, _, _), _)| spec_key.clone())
+        .into_iter()
+        .map(|(spec_key, group)| {
+            let counts = group.map(|(_, c)| c).collect::<Vec<_>>();
+            let filled_points = counts.iter().map(|(f, _)| f).sum::<u64>();
+            let points = counts.iter().map(|(_, p)| p).sum::<u64>();
+            let ratio_sum = counts
+                .iter()
+                .map(|&(f, p)| f as f64 / p as f64)
+                .sum::<f64>();
+            FillRow {
+                spec_key,
+                blocks: counts.len() as u64,
+                points,
+                filled_points,
+                fill_ratio: filled_points as f64 / points as f64,
+                mean_block_fill_ratio: ratio_sum / counts.len() as f64,
+            }
+        })
+        .collect::<Vec<_>>();
+    write_report(cmd.format, &rows)
+}
+
+fn main_costs_report(cmd: &AnalyzeCmd) -> Result<()> {
+    // Buckets are keyed by the bit length of the main costs they contain.
+    let mut buckets = BTreeMap::<(String, u32), u64>::new();
+    FilesDatabase::visit_entries(&cmd.db, |entry| {
+        let Some(stored) = &entry.entry else {
+            return;
+        };
+        let spec_key = spec_key_label(entry.superblock_path);
+        for (_, cost) in stored.iter() {
+            let bucket = MainCost::BITS - cost.main.leading_zeros();
+            *buckets.entry((spec_key.clone(), bucket)).or_default() +=
+                u64::try_from(entry.count).unwrap();
+        }
+    })?;
+
+    let rows = buckets
+        .into_iter()
+        .map(|((spec_key, bucket), points)| {
+            let (main_cost_min, main_cost_max) = match bucket {
+                0 => (0, 0),
+                _ => (
+                    1 << (bucket - 1),
+                    MainCost::MAX >> (MainCost::BITS - bucket),
+                ),
+            };
+            MainCostBucketRow {
+                spec_key,
+                main_cost_min,
+                main_cost_max,
+                points,
+            }
+        })
+        .collect::<Vec<_>>();
+    write_report(cmd.format, &rows)
+}
+
+fn actions_report<Tgt: CpuTarget>(cmd: &AnalyzeCmd) -> Result<()> {
+    let tiling_depth = FilesDatabase::stored_tiling_depth(&cmd.db)?;
+    let mut counts = BTreeMap::<(String, String), u64>::new();
+    // Points are visited in row-major order with memory limits innermost, so consecutive entries
+    // usually share a LogicalSpec and, therefore, its Actions.
+    let mut cached_kinds: Option<(LogicalSpec<Tgt>, Vec<String>)> = None;
+    let mut undecodable = None;
+    FilesDatabase::visit_entries(&cmd.db, |entry| {
+        let Some(stored) = &entry.entry else {
+            return;
+        };
+        if stored.is_empty() || undecodable.is_some() {
+            return;
+        }
+        let Some(spec) = FilesDatabase::stored_spec::<Tgt>(
+            BINARY_SCALE_SHAPES,
+            entry.superblock_path,
+            entry.block_pt,
+            entry.inner_pt,
+        ) else {
+            undecodable = Some(entry.superblock_path.to_owned());
+            return;
+        };
+        if cached_kinds.as_ref().map(|(s, _)| s) != Some(&spec.0) {
+            let kinds = spec
+                .0
+                .actions(tiling_depth)
+                .into_iter()
+                .map(|a| action_kind(&a));
+            cached_kinds = Some((spec.0.clone(), kinds.collect()));
+        }
+        let kinds = &cached_kinds.as_ref().unwrap().1;
+        let LogicalSpec::Primitive(basics, _, _) = &spec.0 else {
+            unreachable!("Compose Specs are not stored");
+        };
+        for (action_idx, _) in stored.iter() {
+            let key = (
+                basics.typ.to_string(),
+                kinds[usize::from(*action_idx)].clone(),
+            );
+            *counts.entry(key).or_default() += u64::try_from(entry.count).unwrap();
+        }
+    })?;
+    if let Some(superblock_path) = undecodable {
+        bail!(
+            "Could not decode the Specs stored in {}; is the target correct?",
+            superblock_path.display()
+        );
+    }
+
+    let mut rows = vec![];
+    for (spec_type, group) in &counts.into_iter().chunk_by(|((t, _), _)| t.clone()) {
+        let mut kinds = group.map(|((_, k), c)| (k, c)).collect::<Vec<_>>();
+        kinds.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let total = kinds.iter().map(|(_, c)| c).sum::<u64>();
+        rows.extend(
+            kinds
+                .into_iter()
+                .map(|(action_kind, points)| ActionKindRow {
+                    spec_type: spec_type.clone(),
+                    action_kind,
+                    points,
+                    fraction: points as f64 / total as f64,
+                }),
+        );
+    }
+    write_report(cmd.format, &rows)
+}
+
+fn depth_report(cmd: &AnalyzeCmd) -> Result<()> {
+    // Point counts, depth sums, and maximum depths per SpecKey.
+    let mut depths = BTreeMap::<String, (u64, u64, u8)>::new();
+    FilesDatabase::visit_entries(&cmd.db, |entry| {
+        let Some((_, cost)) = entry.entry.as_ref().and_then(|e| e.first()) else {
+            return;
+        };
+        let count = u64::try_from(entry.count).unwrap();
+        let (points, depth_sum, max_depth) = depths
+            .entry(spec_key_label(entry.superblock_path))
+            .or_default();
+        *points += count;
+        *depth_sum += count * u64::from(cost.depth);
+        *max_depth = (*max_depth).max(cost.depth);
+    })?;
+
+    let rows = depths
+        .into_iter()
+        .map(|(spec_key, (points, depth_sum, max_depth))| DepthRow {
+            spec_key,
+            points,
+            mean_depth: depth_sum as f64 / points as f64,
+            max_depth,
+        })
+        .collect::<Vec<_>>();
+    write_report(cmd.format, &rows)
+}
+
+/// Returns the name of an [Action]'s variant, including the kernel for [Action::Place].
+fn action_kind<Tgt: Target>(action: &Action<Tgt>) -> String {
+    match action {
+        Action::Place(kernel) => format!("Place({kernel:?})"),
+        _ => <&'static str>::from(action).to_string(),
+    }
+}
+
+fn query_per_target<D: Database>(cmd: &QueryCmd, db: &D) -> Result<()> {
+    match cmd.target {
+        TargetId::X86 => query::<X86Target, _>(cmd, db),
+        TargetId::Arm => query::<ArmTarget, _>(cmd, db),
+    }
+}
+
+fn query<Tgt: CpuTarget, D: Database>(cmd: &QueryCmd, db: &D) -> Result<()> {
+    let mut spec = cmd.spec.parse::<Spec<Tgt>>()?;
+    spec.canonicalize()
+        .map_err(|e| anyhow!("Couldn't canonicalize {}: {e}", cmd.spec))?;
+    let Some(entry) = db.get(&spec) else {
+        println!("No entry for {spec}");
+        return Ok(());
+    };
+
+    println!("Spec: {spec}");
+    if entry.is_empty() {
+        println!("Unsatisfiable");
+    }
+    let actions = spec
+        .0
+        .actions(db.tiling_depth())
+        .into_iter()
+        .collect::<Vec<_>>();
+    for (action_idx, cost) in entry.iter() {
+        let action = actions
+            .get(usize::from(*action_idx))
+            .ok_or_else(|| anyhow!("Stored action index {action_idx} is out of range"))?;
+        println!("Action {action_idx}: {action:?}");
+        println!(
+            "  Cost: main={}, peaks={}, depth={}",
+            cost.main, cost.peaks, cost.depth
+        );
+    }
+
+    println!("Memory limits covered by this entry (varying one level at a time):");
+    for (level, (low, high)) in Tgt::levels().iter().zip(covered_limits(db, &spec, &entry)) {
+        println!("  {level}: {low}..={high}");
+    }
+
+    if let Some(impls) = db.get_impl(&spec) {
+        for imp in &impls {
+            pprint(imp, cmd.impl_style);
+        }
+    }
+    Ok(())
+}
+
+/// Returns, per memory level, the range of limits for which `db` returns `entry` for `spec`, with
+/// the limits of all other levels held fixed.
+fn covered_limits<Tgt: CpuTarget, D: Database>(
+    db: &D,
+    spec: &Spec<Tgt>,
+    entry: &ActionCostVec,
+) -> Vec<(u64, u64)> {
+    let MemoryLimits::Standard(limits) = &spec.1;
+    let MemoryLimits::Standard(max_mem) = Tgt::max_mem();
+    let scaled = limits.iter_binary_scaled().collect::<Vec<_>>();
+    let matches_at = |level_idx: usize, value: u8| {
+        let mut probe = scaled.clone();
+        probe[level_idx] = value;
+        let probe_limits =
+            MemoryLimits::Standard(MemVec::new_from_binary_scaled(probe.try_into().unwrap()));
+        db.get(&Spec(spec.0.clone(), probe_limits)).as_ref() == Some(entry)
+    };
+    (0..scaled.len())
+        .map(|level_idx| {
+            let mut low = scaled[level_idx];
+            while low > 0 && matches_at(level_idx, low - 1) {
+                low -= 1;
+            }
+            let mut high = scaled[level_idx];
+            while high < max_mem.get_binary_scaled(level_idx) && matches_at(level_idx, high + 1) {
+                high += 1;
+            }
+            (
+                bit_length_inverse(low.into()),
+                bit_length_inverse(high.into()),
+            )
+        })
+        .collect()
+}
+
+fn diff(cmd: &DiffCmd) -> Result<()> {
+    let a_depth = FilesDatabase::stored_tiling_depth(&cmd.a)?;
+    let b_depth = FilesDatabase::stored_tiling_depth(&cmd.b)?;
+    if a_depth != b_depth {
+        bail!("Databases have different tiling depths: {a_depth:?} and {b_depth:?}");
+    }
+
+    let mut entry_rows = vec![];
+    // Main cost deltas are recorded along with the number of points at which they occur.
+    let mut summaries = BTreeMap::<String, (DiffSummaryRow, Vec<(i64, u64)>)>::new();
+    FilesDatabase::align_entries(&cmd.a, &cmd.b, |entry| {
+        let spec_key = spec_key_label(entry.superblock_path);
+        let (summary, deltas) = summaries.entry(spec_key.clone()).or_default();
+        let points = u64::try_from(entry.count).unwrap();
+        let kind = match (&entry.a, &entry.b) {
+            (Some(_), None) => {
+                summary.only_in_a += points;
+                "only_in_a"
+            }
+            (None, Some(_)) => {
+                summary.only_in_b += points;
+                "only_in_b"
+            }
+            (Some(a), Some(b)) => {
+                if let (Some((_, a_cost)), Some((_, b_cost))) = (a.first(), b.first()) {
+                    deltas.push((i64::from(b_cost.main) - i64::from(a_cost.main), points));
+                }
+                if actions_str(a) == actions_str(b) {
+                    summary.unchanged_actions += points;
+                    return;
+                }
+                summary.changed_actions += points;
+                "changed_actions"
+            }
+            (None, None) => unreachable!(),
+        };
+        if !cmd.summary {
+            entry_rows.push(DiffEntryRow {
+                spec_key,
+                superblock: entry.superblock_path.display().to_string(),
+                block_pt: format!("{:?}", entry.block_pt),
+                inner_pt: format!("{:?}", entry.inner_pt),
+                points: entry.count,
+                kind,
+                a_actions: entry.a.as_ref().map(actions_str),
+                b_actions: entry.b.as_ref().map(actions_str),
+                a_main: entry.a.as_ref().and_then(first_main_cost),
+                b_main: entry.b.as_ref().and_then(first_main_cost),
+            });
+        }
+    })?;
+
+    if !cmd.summary {
+        return write_report(cmd.format, &entry_rows);
+    }
+    let summary_rows = summaries
+        .into_iter()
+        .map(|(spec_key, (mut summary, mut deltas))| {
+            deltas.sort_unstable();
+            let total = deltas.iter().map(|(_, w)| w).sum::<u64>();
+            let quantile = |q: f64| {
+                let target = ((total - 1) as f64 * q).round() as u64;
+                let mut seen = 0;
+                for &(delta, w) in &deltas {
+                    seen += w;
+                    if seen > target {
+                        return delta;
+                    }
+                }
+                unreachable!()
+            };
+            summary.spec_key = spec_key;
+            if total > 0 {
+                summary.main_delta_min = Some(deltas[0].0);
+                summary.main_delta_p25 = Some(quantile(0.25));
+                summary.main_delta_median = Some(quantile(0.5));
+                summary.main_delta_p75 = Some(quantile(0.75));
+                summary.main_delta_max = Some(deltas.last().unwrap().0);
+                summary.main_delta_mean = Some(
+                    deltas
+                        .iter()
+                        .map(|&(d, w)| d as f64 * w as f64)
+                        .sum::<f64>()
+                        / total as f64,
+                );
+            }
+            summary
+        })
+        .collect::<Vec<_>>();
+    write_report(cmd.format, &summary_rows)
+}
+
+/// Returns a label for the [SpecKey](morello::datadeps::SpecKey) of a superblock, such as
+/// `Matmul/u32_u32_u32`.
+fn spec_key_label(superblock_path: &path::Path) -> String {
+    superblock_path
+        .iter()
+        .take(2)
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn actions_str(entry: &ActionCostVec) -> String {
+    entry
+        .iter()
+        .map(|(action_idx, _)| action_idx.to_string())
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+fn first_main_cost(entry: &ActionCostVec) -> Option<MainCost> {
+    entry.first().map(|(_, cost)| cost.main)
+}
+
+fn write_report<T: Serialize>(format: ReportFormat, rows: &[T]) -> Result<()> {
+    match format {
+        ReportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(io::stdout());
+            for row in rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(io::stdout(), rows)?;
+            println!();
+        }
+    }
+    Ok(())
 }
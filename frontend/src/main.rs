@@ -1,19 +1,33 @@
 #[cfg(not(target_env = "msvc"))]
 use tikv_jemallocator::Jemalloc;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use clap::{Parser, ValueEnum};
-use log::info;
+use log::{info, warn};
+use serde::Serialize;
 
+use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
 use std::num::{NonZeroU32, NonZeroUsize};
-use std::{io, path};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::{fs, io, path};
 
-use morello::codegen::CodeGen;
+use morello::codegen::{
+    emit_kernels, host_can_load, is_valid_library_symbol, BuildOptions, CodeGen, Compiler,
+    CpuCodeGenThreadStyle, LibraryKind, RustCodeGen,
+};
 use morello::color::{self, ColorMode};
 use morello::common::{DimSize, Dtype};
-use morello::db::FilesDatabase;
+use morello::cost::MainCost;
+use morello::db::{Database, FilesDatabase, InMemoryDatabase, PackedDatabase};
+use morello::imp::ImplNode;
 use morello::layout::{col_major, row_major};
-use morello::pprint::{pprint, ImplPrintStyle};
+use morello::pprint::{pprint, pprint_string, ImplPrintStyle};
+use morello::search::{
+    CancellationToken, SearchControl, SearchObserver, SearchProgress, SearchStrategy, TraceRecorder,
+};
 use morello::target::{
     ArmTarget,
     CpuMemoryLevel::{self, GL},
@@ -21,6 +35,8 @@ use morello::target::{
 };
 use morello::tensorspec::TensorSpecAux;
 use morello::utils::ToWriteFmt;
+#[cfg(feature = "verification")]
+use morello::verification::{CheckOptions, CorrectnessFailure, InputStrategy};
 use morello::{
     lspec,
     spec::{LogicalSpec, PrimitiveBasics, PrimitiveSpecType, Spec},
@@ -71,19 +87,107 @@ struct Args {
     #[arg(long, short)]
     jobs: Option<usize>,
 
+    /// Search strategy; only the exact search finds an optimal Impl
+    #[arg(long, value_enum, default_value_t = SearchStrategy::Exact)]
+    search: SearchStrategy,
+
+    /// Number of actions considered at each Spec by beam search
+    #[arg(long, default_value = "4")]
+    beam_width: NonZeroUsize,
+
+    /// Don't draw the search's progress on stderr
+    #[arg(long, default_value_t = false)]
+    no_progress: bool,
+
+    /// Write a JSON trace of the actions the search considered for each Spec to this file; the
+    /// search then runs with one job
+    #[arg(long, value_name = "PATH")]
+    trace: Option<path::PathBuf>,
+
+    /// Only trace this Spec
+    #[arg(long, value_name = "SPEC", requires = "trace")]
+    trace_spec: Option<String>,
+
+    /// Stop searching after this many seconds, emitting the best Impl found so far (exact search
+    /// only)
+    #[arg(long, value_name = "SECONDS")]
+    time_limit: Option<f64>,
+
+    /// Stop expanding Specs after this many, emitting the best Impl found by then (greedy and beam
+    /// search only)
+    #[arg(long, value_name = "SPECS")]
+    node_limit: Option<u64>,
+
     /// Disable verification
     #[cfg(feature = "verification")]
     #[arg(long, default_value_t = false)]
     skip_check: bool,
 
+    /// Directory to which to write a report, inputs, and source if verification fails
+    #[cfg(feature = "verification")]
+    #[arg(long, value_name = "DIR")]
+    dump_on_failure: Option<path::PathBuf>,
+
+    /// How to choose verification inputs
+    #[cfg(feature = "verification")]
+    #[arg(long, value_enum, default_value_t = InputStrategy::Sequential)]
+    check_inputs: InputStrategy,
+
+    /// Number of sets of random verification inputs to check
+    #[cfg(feature = "verification")]
+    #[arg(long, default_value_t = 4)]
+    check_trials: u32,
+
+    /// Seed for random verification inputs
+    #[cfg(feature = "verification")]
+    #[arg(long, default_value_t = 0)]
+    check_seed: u64,
+
+    /// Synthesize the Specs in a JSON or YAML file instead of running a subcommand
+    #[arg(long)]
+    spec_file: Option<path::PathBuf>,
+
+    /// Directory to which to write one output file per Spec in the `--spec-file`
+    #[arg(long, default_value = ".")]
+    output_dir: path::PathBuf,
+
+    /// C compiler with which to build (defaults to `$CLANG`, then `$GCC`)
+    #[arg(long, value_enum)]
+    compiler: Option<Compiler>,
+
+    /// How generated code runs parallel loops
+    #[arg(long, value_enum, default_value_t = CpuCodeGenThreadStyle::OpenMP)]
+    thread_style: CpuCodeGenThreadStyle,
+
+    /// Optimization level passed to the C compiler as `-O<LEVEL>`
+    #[arg(long, value_name = "LEVEL", default_value = "3")]
+    opt_level: String,
+
+    /// Target architecture passed to the C compiler as `-march`
+    #[arg(long)]
+    march: Option<String>,
+
+    /// Extra flag to pass to the C compiler (may be repeated)
+    #[arg(long = "cflag", value_name = "FLAG", allow_hyphen_values = true)]
+    cflags: Vec<String>,
+
+    /// Directory in which to build generated code instead of a temporary directory
+    #[arg(long)]
+    build_dir: Option<path::PathBuf>,
+
+    /// Keep generated sources after building
+    #[arg(long, default_value_t = false)]
+    keep_sources: bool,
+
     #[command(subcommand)]
-    subcmd: Subcommand,
+    subcmd: Option<Subcommand>,
 }
 
 #[derive(Clone, ValueEnum)]
 enum OutputFormat {
     C,
     Impl,
+    Rust,
 }
 
 #[derive(Parser)]
@@ -91,11 +195,20 @@ enum Subcommand {
     #[command(flatten)]
     Emit(QuerySpec),
 
-    /// Compile and run the synthesized implementation
+    /// Compile and run the synthesized implementation (if no C compiler is found, only check it
+    /// with the interpreter)
     Run(RunCmd),
 
     /// Compile and benchmark the synthesized implementation
     Bench(BenchCmd),
+
+    /// Write a C header and a source file without `main` to the output directory, for linking
+    /// into another program
+    Library(LibraryCmd),
+
+    /// Synthesize many Specs together, writing one file per Spec and a manifest to the output
+    /// directory
+    Batch(BatchCmd),
 }
 
 #[derive(clap::Subcommand)]
@@ -123,6 +236,12 @@ enum QuerySpec {
         filters_size: DimSize,
         size: DimSize,
     },
+    #[command(about = "Synthesize a Spec written in the syntax of its Display output")]
+    Spec {
+        /// A Spec such as "(Zero((4×4, u32)), [64, 1024, 0, 0])", or a LogicalSpec such as
+        /// "Zero((4×4, u32))" to synthesize with the target's maximum memory limits
+        spec: String,
+    },
 }
 
 #[derive(Parser)]
@@ -141,40 +260,137 @@ struct BenchCmd {
     query_spec: QuerySpec,
 }
 
+#[derive(Parser)]
+struct LibraryCmd {
+    /// Name of the kernel function, which is also used to name the output files
+    #[arg(long)]
+    symbol: String,
+
+    /// Also compile the source into a static or shared library
+    #[arg(long, value_enum)]
+    kind: Option<LibraryKind>,
+
+    #[command(subcommand)]
+    query_spec: QuerySpec,
+}
+
+#[derive(Parser)]
+struct BatchCmd {
+    /// A JSON or YAML file of serialized Specs, as accepted by `--spec-file`
+    #[arg(long = "file", short)]
+    files: Vec<path::PathBuf>,
+
+    /// Specs in the syntax accepted by the `spec` subcommand
+    specs: Vec<String>,
+
+    /// Write all kernels to `<NAME>.c`, along with a function `NAME` which dispatches to them by
+    /// Spec, instead of writing one file per Spec
+    #[arg(long, value_name = "NAME")]
+    single_file: Option<String>,
+}
+
+/// Written to `manifest.json` by the `batch` subcommand.
+#[derive(Serialize)]
+struct BatchManifest {
+    /// Wall-clock time spent searching for all Specs together
+    synthesis_secs: f64,
+    hits: u64,
+    misses: u64,
+    specs: Vec<BatchManifestEntry>,
+}
+
+#[derive(Serialize)]
+struct BatchManifestEntry {
+    spec: String,
+    /// The output file, relative to the output directory, or `None` if the Spec is unsatisfiable
+    output: Option<String>,
+    /// The kernel's name in the output file, if written with `--single-file`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    symbol: Option<String>,
+    main_cost: Option<MainCost>,
+    peaks: Option<String>,
+    depth: Option<u8>,
+    /// Time spent writing this Spec's output file or, with `--single-file`, loading its Impl
+    emit_secs: f64,
+}
+
 fn main() -> Result<()> {
     env_logger::init();
     let args = Args::parse();
+    match (&args.spec_file, &args.subcmd) {
+        (Some(_), Some(_)) => bail!("--spec-file cannot be combined with a subcommand"),
+        (None, None) => bail!("Either --spec-file or a subcommand is required"),
+        _ => {}
+    }
     color::set_color_mode(args.color);
-    let threads = rayon::current_num_threads();
-    let db = FilesDatabase::new(
-        args.db.as_deref(),
-        BINARY_SCALE_SHAPES,
-        K,
-        args.cache_size,
-        threads,
-        args.tiling_depth,
-    );
+    match args.db.as_deref() {
+        Some(db_path) => {
+            if db_path.is_file() && PackedDatabase::open(db_path).is_ok() {
+                bail!(
+                    "{} is a read-only packed database; --db must be a database directory",
+                    db_path.display()
+                );
+            }
+            let threads = rayon::current_num_threads();
+            let db = FilesDatabase::new(
+                Some(db_path),
+                BINARY_SCALE_SHAPES,
+                K,
+                args.cache_size,
+                threads,
+                args.tiling_depth,
+            );
+            main_per_target(&args, &db)
+        }
+        None => {
+            let db = InMemoryDatabase::new(BINARY_SCALE_SHAPES, K, args.tiling_depth);
+            main_per_target(&args, &db)
+        }
+    }
+}
+
+fn main_per_target<D>(args: &Args, db: &D) -> Result<()>
+where
+    D: Database + Sync,
+{
     match &args.target {
-        TargetId::X86 => main_per_db::<X86Target>(&args, &db),
-        TargetId::Arm => main_per_db::<ArmTarget>(&args, &db),
+        TargetId::X86 => main_per_db::<X86Target, _>(args, db),
+        TargetId::Arm => main_per_db::<ArmTarget, _>(args, db),
     }
 }
 
-fn main_per_db<Tgt>(args: &Args, db: &FilesDatabase) -> Result<()>
+fn main_per_db<Tgt, D>(args: &Args, db: &D) -> Result<()>
 where
     Tgt: CpuTarget,
+    D: Database + Sync,
 {
-    let subcmd = &args.subcmd;
+    if db.is_read_only() {
+        bail!("Cannot synthesize into a read-only database");
+    }
+    let Some(subcmd) = &args.subcmd else {
+        let spec_file = args.spec_file.as_deref().unwrap();
+        return synthesize_spec_file::<Tgt, _>(args, db, spec_file);
+    };
     let query_spec = match subcmd {
         Subcommand::Emit(query_spec) => query_spec,
         Subcommand::Run(run_cmd) => &run_cmd.query_spec,
         Subcommand::Bench(bench_cmd) => &bench_cmd.query_spec,
+        Subcommand::Library(library_cmd) => {
+            let symbol = &library_cmd.symbol;
+            if !is_valid_library_symbol(symbol) {
+                bail!("--symbol must be a valid C function name other than main, not {symbol:?}");
+            }
+            &library_cmd.query_spec
+        }
+        Subcommand::Batch(batch_cmd) => return synthesize_batch::<Tgt, _>(args, db, batch_cmd),
     };
-    let logical_spec = match query_spec {
+    let spec = match query_spec {
+        QuerySpec::Spec { spec } => parse_spec_arg(spec)?,
         QuerySpec::Transpose { size } => {
             let rm2 = row_major(2);
             let cm2 = col_major(2);
-            lspec!(Move([*size, *size], (u32, GL, rm2), (u32, GL, cm2), serial))
+            let logical_spec = lspec!(Move([*size, *size], (u32, GL, rm2), (u32, GL, cm2), serial));
+            Spec(logical_spec, Tgt::max_mem())
         }
         QuerySpec::Matmul { size } | QuerySpec::MatmulU8S8S16 { size } => {
             let rm2 = row_major(2);
@@ -183,13 +399,14 @@ where
                 QuerySpec::MatmulU8S8S16 { .. } => [Dtype::Uint8, Dtype::Sint8, Dtype::Sint16],
                 _ => unreachable!(),
             };
-            lspec!(Matmul(
+            let logical_spec = lspec!(Matmul(
                 [*size, *size, *size],
                 (dt_a, GL, rm2.clone()),
                 (dt_b, GL, rm2.clone()),
                 (dt_c, GL, rm2),
                 serial
-            ))
+            ));
+            Spec(logical_spec, Tgt::max_mem())
         }
         QuerySpec::Conv {
             batch,
@@ -199,7 +416,7 @@ where
             size,
         } => {
             let rm = row_major(4);
-            LogicalSpec::Primitive(
+            let logical_spec = LogicalSpec::Primitive(
                 PrimitiveBasics {
                     typ: PrimitiveSpecType::Conv { accum: false },
                     spec_shape: vec![
@@ -224,78 +441,773 @@ where
                     3
                 ],
                 true,
-            )
+            );
+            Spec(logical_spec, Tgt::max_mem())
+        }
+    };
+    let synthesized_impl = &synthesize(args, db, &spec)?;
+
+    if let Subcommand::Library(library_cmd) = subcmd {
+        return write_library(args, synthesized_impl, library_cmd);
+    }
+
+    let build_options = build_options(args);
+
+    match args.format {
+        OutputFormat::C => {
+            synthesized_impl.emit_ext(
+                true,
+                include_impl(args),
+                args.thread_style,
+                &mut ToWriteFmt(io::stdout()),
+            )?;
+        }
+        OutputFormat::Impl => pprint(synthesized_impl, args.impl_style),
+        OutputFormat::Rust => {
+            let mut source = String::new();
+            synthesized_impl.emit_rust("kernel", true, include_impl(args), &mut source)?;
+            print!("{source}");
+        }
+    }
+    if let Subcommand::Emit(_) = subcmd {
+        return Ok(());
+    }
+
+    // Without a C compiler, the Impl can still be checked by interpreting it.
+    #[cfg(feature = "verification")]
+    if matches!(subcmd, Subcommand::Run(_))
+        && !matches!(args.format, OutputFormat::Rust)
+        && build_options.compiler.path().is_none()
+    {
+        warn!("No C compiler found; interpreting the Impl to check it instead of running it");
+        if !args.skip_check {
+            if let Err(failure) = synthesized_impl
+                .check_correctness_interpreted_with_options(&spec, &check_options(args))
+            {
+                return report_check_failure(args, synthesized_impl, failure);
+            }
+        }
+        return Ok(());
+    }
+
+    #[cfg(feature = "verification")]
+    let checking = !args.skip_check;
+    #[cfg(not(feature = "verification"))]
+    let checking = false;
+    // Check in-process when possible, which avoids running the program for each input.
+    let check_loaded =
+        checking && !matches!(args.format, OutputFormat::Rust) && host_can_load(Tgt::target_id());
+    let parameters = spec.0.parameters();
+    if check_loaded && parameters.iter().any(|p| !p.layout().is_row_major()) {
+        bail!("Can't check a kernel with non-row-major parameters; pass --skip-check");
+    }
+    // The kernel is loaded at most once, and then both checked and benchmarked.
+    let loaded_kernel = if check_loaded
+        || (matches!(subcmd, Subcommand::Bench(_)) && host_can_load(Tgt::target_id()))
+    {
+        let kernel = synthesized_impl.build_loaded(&build_options)?;
+        report_compiler_warnings(kernel.compiler_warnings());
+        Some(kernel)
+    } else {
+        None
+    };
+
+    let built_artifact = if matches!(subcmd, Subcommand::Run(_)) || (checking && !check_loaded) {
+        let artifact = match args.format {
+            OutputFormat::Rust => synthesized_impl.build_rust()?,
+            OutputFormat::C | OutputFormat::Impl => {
+                synthesized_impl.build_with_options(false, &build_options)?
+            }
+        };
+        report_compiler_warnings(artifact.compiler_warnings());
+        if let Some(source_path) = artifact.source_path() {
+            info!("Kept source at {}", source_path.display());
         }
+        Some(artifact)
+    } else {
+        None
     };
+    if let (Subcommand::Run(_), Some(artifact)) = (subcmd, &built_artifact) {
+        let output = artifact.run()?;
+        println!("\nOutput:\n{}", String::from_utf8_lossy(&output.stdout));
+    }
+    #[cfg(feature = "verification")]
+    if checking {
+        let check_options = check_options(args);
+        let check_result = match &loaded_kernel {
+            Some(kernel) if check_loaded => {
+                kernel.check_correctness_with_options(&spec, &check_options)
+            }
+            _ => built_artifact
+                .as_ref()
+                .expect("the program is built when the kernel isn't checked in-process")
+                .check_correctness_with_options(&spec, &check_options),
+        };
+        if let Err(failure) = check_result {
+            return report_check_failure(args, synthesized_impl, failure);
+        }
+    }
 
-    let spec = Spec(logical_spec, Tgt::max_mem());
+    if let Subcommand::Bench(BenchCmd {
+        inner_loop_iters, ..
+    }) = subcmd
+    {
+        // We need an exact number of samples when benchmarking. If the user didn't specify one,
+        // we estimate a good number.
+        let result = match &loaded_kernel {
+            Some(kernel) => {
+                let iters = inner_loop_iters.unwrap_or_else(|| kernel.estimate_optimal_iters());
+                kernel.bench(iters, None)
+            }
+            None => {
+                let artifact = synthesized_impl.build_with_options(true, &build_options)?;
+                report_compiler_warnings(artifact.compiler_warnings());
+                let iters = match *inner_loop_iters {
+                    Some(s) => s,
+                    None => artifact.estimate_optimal_iters()?,
+                };
+                artifact.bench(iters, None)?
+            }
+        };
+        let inner_loop_runtime = result.best_inner_loop_runtime();
+        let kernel_runtime = inner_loop_runtime / result.inner_loop_iterations;
+        println!("\nkernel runtime: {:.8}s", kernel_runtime.as_secs_f32());
+        println!("loop runtime: {}ns", inner_loop_runtime.as_nanos());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "verification")]
+fn check_options(args: &Args) -> CheckOptions {
+    CheckOptions {
+        strategy: args.check_inputs,
+        trials: args.check_trials,
+        seed: args.check_seed,
+    }
+}
+
+/// Dumps `failure` if `--dump-on-failure` was given, then returns it as an error.
+#[cfg(feature = "verification")]
+fn report_check_failure<Tgt: CpuTarget>(
+    args: &Args,
+    imp: &ImplNode<Tgt>,
+    failure: CorrectnessFailure,
+) -> Result<()> {
+    if let Some(dump_dir) = &args.dump_on_failure {
+        failure
+            .dump(dump_dir)
+            .and_then(|()| dump_source(args, imp, dump_dir))
+            .with_context(|| format!("Failed to dump to {}", dump_dir.display()))?;
+        info!("Wrote correctness failure to {}", dump_dir.display());
+    }
+    bail!("Generated code returned incorrect output\n{failure}");
+}
+
+/// Writes the source for `imp` in the `--format` language to `dir`, for failure reports.
+#[cfg(feature = "verification")]
+fn dump_source<Tgt: CpuTarget>(
+    args: &Args,
+    imp: &ImplNode<Tgt>,
+    dir: &path::Path,
+) -> io::Result<()> {
+    let mut source = String::new();
+    let file_name = match args.format {
+        OutputFormat::C | OutputFormat::Impl => {
+            imp.emit_ext(true, include_impl(args), args.thread_style, &mut source)
+                .map_err(io::Error::other)?;
+            "main.c"
+        }
+        OutputFormat::Rust => {
+            imp.emit_rust("kernel", true, include_impl(args), &mut source)
+                .map_err(io::Error::other)?;
+            "main.rs"
+        }
+    };
+    fs::write(dir.join(file_name), source)
+}
+
+fn build_options(args: &Args) -> BuildOptions {
+    BuildOptions {
+        compiler: args.compiler.unwrap_or_else(Compiler::from_env),
+        thread_style: args.thread_style,
+        opt_level: args.opt_level.clone(),
+        march: args.march.clone(),
+        extra_flags: args.cflags.clone(),
+        output_dir: args.build_dir.clone(),
+        keep_sources: args.keep_sources,
+    }
+}
+
+fn report_compiler_warnings(warnings: &str) {
+    if !warnings.is_empty() {
+        eprint!("{warnings}");
+    }
+}
+
+/// Parses a [Spec], or a [LogicalSpec] to which the target's maximum memory limits are added, and
+/// canonicalizes it.
+fn parse_spec_arg<Tgt: CpuTarget>(text: &str) -> Result<Spec<Tgt>> {
+    let mut spec = if text.trim_start().starts_with('(') {
+        text.parse::<Spec<Tgt>>()?
+    } else {
+        Spec(text.parse::<LogicalSpec<Tgt>>()?, Tgt::max_mem())
+    };
+    spec.canonicalize()?;
+    Ok(spec)
+}
+
+fn synthesize<Tgt, D>(args: &Args, db: &D, spec: &Spec<Tgt>) -> Result<ImplNode<Tgt>>
+where
+    Tgt: CpuTarget,
+    D: Database + Sync,
+{
     info!("Synthesizing {}", spec);
 
+    // Greedy and beam searches take no deadline and aren't traced. They also put nothing into
+    // the database, so Ctrl-C's default handling, which exits immediately, loses nothing.
+    if !matches!(args.search, SearchStrategy::Exact) {
+        if args.time_limit.is_some() {
+            bail!("--time-limit is only supported by --search exact");
+        }
+        if args.trace.is_some() {
+            bail!("--trace is only supported by --search exact");
+        }
+    } else if args.node_limit.is_some() {
+        bail!("--node-limit is only supported by --search greedy and --search beam");
+    }
+
     let start_time = std::time::Instant::now();
-    let (_, hits, misses) =
-        morello::search::top_down(db, &spec, K.into(), args.jobs.and_then(NonZeroUsize::new));
+    let jobs = search_jobs(args)?;
+    let node_limit = args.node_limit.unwrap_or(u64::MAX);
+    let approximate = match args.search {
+        SearchStrategy::Exact => None,
+        SearchStrategy::Greedy => Some(morello::search::greedy_search_with_budget(
+            db, spec, node_limit,
+        )),
+        SearchStrategy::Beam => Some(morello::search::beam_search_with_budget(
+            db,
+            spec,
+            args.beam_width,
+            node_limit,
+        )),
+    };
+    if let Some(result) = approximate {
+        info!("{:?} search took {:?}", args.search, start_time.elapsed());
+        let Some((synthesized_impl, _)) = result else {
+            bail!("No Impl found");
+        };
+        return Ok(synthesized_impl);
+    }
+
+    warn_untraced_goals(args, db, std::slice::from_ref(spec));
+    let progress_bar = progress_bar(args);
+    let trace_recorder = trace_recorder::<Tgt>(args)?;
+    let searching = SearchGuard::start();
+    let control = SearchControl {
+        deadline: args
+            .time_limit
+            .map(|time_limit| start_time + Duration::from_secs_f64(time_limit)),
+        cancellation: Some(cancellation_token()),
+        observer: progress_bar.as_ref().map(|p| p as &dyn SearchObserver),
+        trace: trace_recorder.as_ref(),
+    };
+    let result = morello::search::top_down_with_control(db, spec, jobs, &control);
+    drop(searching);
+    if progress_bar.is_some() {
+        ProgressBar::clear();
+    }
+    if let Some(trace_recorder) = trace_recorder {
+        write_trace(args, trace_recorder)?;
+    }
     info!("top_down took {:?}", start_time.elapsed());
     info!(
         "top_down missed {} times ({:.2}% of {})",
-        misses,
-        misses as f32 / (hits + misses) as f32,
-        hits + misses
+        result.misses,
+        result.misses as f32 / (result.hits + result.misses) as f32,
+        result.hits + result.misses
     );
-
-    let Some(results) = db.get_impl(&spec) else {
-        panic!("No Impl found");
+    if cancellation_token().is_cancelled() {
+        bail!("Interrupted");
+    }
+    if !result.optimal {
+        warn!("Reached the time limit; the Impl may not be optimal");
+    }
+    let Some((synthesized_impl, _)) = result.best else {
+        bail!("No Impl found");
     };
-    let Some(synthesized_impl) = results.first() else {
-        panic!("No Impl found");
+    Ok(synthesized_impl)
+}
+
+/// Returns the number of search threads from `--jobs`, or `None` for the default.
+///
+/// Only the first search thread is traced, so `--trace` searches with one thread and can't be
+/// combined with more.
+fn search_jobs(args: &Args) -> Result<Option<NonZeroUsize>> {
+    let jobs = args.jobs.and_then(NonZeroUsize::new);
+    if args.trace.is_none() {
+        return Ok(jobs);
+    }
+    match jobs {
+        Some(jobs) if jobs.get() > 1 => bail!("--trace requires --jobs 1, but got --jobs {jobs}"),
+        _ => Ok(Some(NonZeroUsize::MIN)),
+    }
+}
+
+/// Returns a recorder for `--trace`, if given, which records only `--trace-spec` if given.
+fn trace_recorder<Tgt: CpuTarget>(args: &Args) -> Result<Option<TraceRecorder>> {
+    if args.trace.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(match &args.trace_spec {
+        Some(text) => TraceRecorder::only(&parse_spec_arg::<Tgt>(text)?),
+        None => TraceRecorder::new(),
+    }))
+}
+
+/// Warns, if tracing, about each goal already in the database, since it won't be searched and so
+/// won't be traced.
+fn warn_untraced_goals<Tgt, D>(args: &Args, db: &D, goals: &[Spec<Tgt>])
+where
+    Tgt: CpuTarget,
+    D: Database,
+{
+    if args.trace.is_none() {
+        return;
+    }
+    for goal in goals {
+        let mut canonical_goal = goal.clone();
+        if canonical_goal.canonicalize().is_ok() && db.get(&canonical_goal).is_some() {
+            warn!("{goal} is already in the database, so it won't be searched or traced");
+        }
+    }
+}
+
+/// Writes the traces recorded by a search to `--trace`.
+fn write_trace(args: &Args, trace_recorder: TraceRecorder) -> Result<()> {
+    let Some(path) = &args.trace else {
+        return Ok(());
     };
+    let traces = trace_recorder.into_traces();
+    if let Some(text) = args.trace_spec.as_ref().filter(|_| traces.is_empty()) {
+        warn!("No trace was recorded for {text}");
+    }
+    fs::write(path, serde_json::to_string_pretty(&traces)? + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    info!("Wrote search trace to {}", path.display());
+    Ok(())
+}
 
-    let bench_inner_loop_iters = if let Subcommand::Bench(BenchCmd {
-        inner_loop_iters, ..
-    }) = subcmd
-    {
-        // We need an exact number of samples when benchmarking.
-        match *inner_loop_iters {
-            // The user specified a number of samples.
-            Some(s) => Some(s),
-            // The user didn't specify a number of samples, so we estimate
-            // a good number of samples.
-            None => Some(synthesized_impl.estimate_optimal_iters()?),
+/// Whether a search is running, in which case Ctrl-C stops it rather than exiting.
+static SEARCHING: AtomicBool = AtomicBool::new(false);
+
+/// Marks a search as running until dropped. See [cancellation_token].
+struct SearchGuard;
+
+impl SearchGuard {
+    fn start() -> Self {
+        cancellation_token();
+        SEARCHING.store(true, Ordering::SeqCst);
+        SearchGuard
+    }
+}
+
+impl Drop for SearchGuard {
+    fn drop(&mut self) {
+        SEARCHING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Returns the token cancelled by Ctrl-C, installing the handler on the first call.
+///
+/// While a [SearchGuard] is alive, cancelling stops the search so that its finished decisions are
+/// still put into the database, which is then flushed when dropped. Otherwise, or on a second
+/// Ctrl-C, the process exits immediately.
+fn cancellation_token() -> &'static CancellationToken {
+    static TOKEN: OnceLock<CancellationToken> = OnceLock::new();
+    TOKEN.get_or_init(|| {
+        let token = CancellationToken::new();
+        let handler_token = token.clone();
+        let installed = ctrlc::set_handler(move || {
+            if !SEARCHING.load(Ordering::SeqCst) || handler_token.is_cancelled() {
+                std::process::exit(130);
+            }
+            eprintln!("\nInterrupted; stopping search");
+            handler_token.cancel();
+        });
+        if let Err(e) = installed {
+            warn!("Failed to install Ctrl-C handler: {e}");
         }
+        token
+    })
+}
+
+/// Draws a search's progress on the last line of stderr.
+///
+/// Searches of more than one stage get a bar and an ETA; others get only counts.
+struct ProgressBar;
+
+impl ProgressBar {
+    const WIDTH: usize = 24;
+
+    fn clear() {
+        eprint!("\r\x1b[K");
+    }
+}
+
+impl SearchObserver for ProgressBar {
+    fn report(&self, progress: &SearchProgress) {
+        let counts = format!(
+            "{} Specs done, {} in working set, {} hits, {} misses, {}s elapsed",
+            progress.specs_completed,
+            progress.working_set_size,
+            progress.hits,
+            progress.misses,
+            progress.elapsed.as_secs(),
+        );
+        // Progress is only measured in stages, so a single stage would show no progress until it
+        // finished. Show only the counts instead.
+        if progress.stage_count <= 1 {
+            eprint!("\r\x1b[K{counts}");
+            return;
+        }
+        let filled = Self::WIDTH * progress.stage / progress.stage_count;
+        let eta = match progress.eta {
+            Some(eta) => format!("{}s", eta.as_secs()),
+            None => String::from("?"),
+        };
+        eprint!(
+            "\r\x1b[K[{}{}] stage {}/{}, {counts}, ETA {eta}",
+            "#".repeat(filled),
+            "-".repeat(Self::WIDTH - filled),
+            (progress.stage + 1).min(progress.stage_count),
+            progress.stage_count,
+        );
+    }
+}
+
+/// Returns a [ProgressBar] if it should be drawn: unless disabled, when stderr is a terminal.
+fn progress_bar(args: &Args) -> Option<ProgressBar> {
+    (!args.no_progress && io::stderr().is_terminal()).then_some(ProgressBar)
+}
+
+fn include_impl(args: &Args) -> Option<ImplPrintStyle> {
+    if args.include_impl {
+        Some(args.impl_style)
     } else {
         None
+    }
+}
+
+/// Synthesizes each Spec in `spec_file`, writing each to its own file in the output directory.
+fn synthesize_spec_file<Tgt, D>(args: &Args, db: &D, spec_file: &path::Path) -> Result<()>
+where
+    Tgt: CpuTarget,
+    D: Database + Sync,
+{
+    let specs = read_spec_file::<Tgt>(spec_file)?;
+    let stem = file_stem(spec_file);
+    fs::create_dir_all(&args.output_dir)?;
+    for (i, spec) in specs.iter().enumerate() {
+        let synthesized_impl = synthesize(args, db, spec)?;
+        let output_path = args
+            .output_dir
+            .join(output_file_name(args, &format!("{stem}_{i}")));
+        write_output(args, &synthesized_impl, &output_path)?;
+        info!("Wrote {} to {}", spec, output_path.display());
+    }
+    Ok(())
+}
+
+/// Synthesizes all of a batch's Specs with a single search, sharing the database and thread pool,
+/// then writes each to its own file alongside a manifest.
+fn synthesize_batch<Tgt, D>(args: &Args, db: &D, batch_cmd: &BatchCmd) -> Result<()>
+where
+    Tgt: CpuTarget,
+    D: Database + Sync,
+{
+    // Name each output after its source: a Spec file's stem or `spec`, and an index.
+    let mut named_specs = vec![];
+    for (i, text) in batch_cmd.specs.iter().enumerate() {
+        named_specs.push((format!("spec_{i}"), parse_spec_arg::<Tgt>(text)?));
+    }
+    for spec_file in &batch_cmd.files {
+        let stem = file_stem(spec_file);
+        for (i, spec) in read_spec_file::<Tgt>(spec_file)?.into_iter().enumerate() {
+            named_specs.push((format!("{stem}_{i}"), spec));
+        }
+    }
+    if named_specs.is_empty() {
+        bail!("No Specs given");
+    }
+    // Spec files with the same stem, or named `spec`, would overwrite each other's outputs.
+    let mut names = HashSet::new();
+    for (name, _) in &named_specs {
+        if !names.insert(name) {
+            bail!(
+                "More than one Spec would be written as {name}; give the Spec files distinct names"
+            );
+        }
+    }
+    if let Some(name) = &batch_cmd.single_file {
+        if !matches!(args.format, OutputFormat::C) {
+            bail!("--single-file requires C output");
+        }
+        if !is_valid_library_symbol(name) {
+            bail!("--single-file must be a valid C function name other than main, not {name:?}");
+        }
+    }
+    // `top_down_many` requires distinct goals, so search for each Spec only once.
+    let mut specs = vec![];
+    let mut spec_idxs = HashMap::new();
+    let result_idxs = named_specs
+        .iter()
+        .map(|(_, spec)| {
+            *spec_idxs.entry(spec).or_insert_with(|| {
+                specs.push(spec.clone());
+                specs.len() - 1
+            })
+        })
+        .collect::<Vec<_>>();
+
+    info!("Synthesizing {} Specs", specs.len());
+    let start_time = std::time::Instant::now();
+    warn_untraced_goals(args, db, &specs);
+    let progress_bar = progress_bar(args);
+    let trace_recorder = trace_recorder::<Tgt>(args)?;
+    let searching = SearchGuard::start();
+    let control = SearchControl {
+        cancellation: Some(cancellation_token()),
+        observer: progress_bar.as_ref().map(|p| p as &dyn SearchObserver),
+        trace: trace_recorder.as_ref(),
+        ..SearchControl::default()
+    };
+    let (results, hits, misses) = morello::search::top_down_many_with_control(
+        db,
+        &specs,
+        K.into(),
+        search_jobs(args)?,
+        &control,
+    );
+    drop(searching);
+    if progress_bar.is_some() {
+        ProgressBar::clear();
+    }
+    if let Some(trace_recorder) = trace_recorder {
+        write_trace(args, trace_recorder)?;
+    }
+    let synthesis_time = start_time.elapsed();
+    info!("top_down_many took {:?}", synthesis_time);
+    let Some(results) = results.into_iter().collect::<Option<Vec<_>>>() else {
+        bail!("Interrupted");
     };
 
-    match args.format {
-        OutputFormat::C => {
-            let impl_style = if args.include_impl {
-                Some(args.impl_style)
+    fs::create_dir_all(&args.output_dir)?;
+    let mut entries = Vec::with_capacity(named_specs.len());
+    // With `--single-file`, the Impls to emit and, for each entry, the index of its Impl.
+    let mut kernel_idxs = HashMap::new();
+    let mut kernels = vec![];
+    let mut entry_kernel_idxs = Vec::with_capacity(named_specs.len());
+    for ((name, spec), result_idx) in named_specs.iter().zip(result_idxs) {
+        let emit_start = std::time::Instant::now();
+        let mut kernel_idx = None;
+        let mut entry = BatchManifestEntry {
+            spec: spec.to_string(),
+            output: None,
+            symbol: None,
+            main_cost: None,
+            peaks: None,
+            depth: None,
+            emit_secs: 0.0,
+        };
+        if let Some((_, cost)) = results[result_idx].first() {
+            let synthesized_impl = db
+                .get_impl(spec)
+                .and_then(|impls| impls.into_iter().next())
+                .expect("Database should have the synthesized Spec");
+            if batch_cmd.single_file.is_some() {
+                kernel_idx = Some(*kernel_idxs.entry(result_idx).or_insert_with(|| {
+                    kernels.push((spec.clone(), synthesized_impl));
+                    kernels.len() - 1
+                }));
             } else {
-                None
-            };
-            synthesized_impl.emit(true, impl_style, &mut ToWriteFmt(io::stdout()))?;
+                let file_name = output_file_name(args, name);
+                write_output(args, &synthesized_impl, &args.output_dir.join(&file_name))?;
+                entry.output = Some(file_name);
+            }
+            entry.main_cost = Some(cost.main);
+            entry.peaks = Some(cost.peaks.to_string());
+            entry.depth = Some(cost.depth);
+        } else {
+            warn!("No Impl found for {}", spec);
         }
-        OutputFormat::Impl => pprint(synthesized_impl, args.impl_style),
+        entry.emit_secs = emit_start.elapsed().as_secs_f64();
+        entries.push(entry);
+        entry_kernel_idxs.push(kernel_idx);
     }
-    if let Subcommand::Emit(_) = subcmd {
+
+    if let Some(dispatch_name) = &batch_cmd.single_file {
+        let mut source = String::new();
+        let kernel_names = emit_kernels(&kernels, dispatch_name, include_impl(args), &mut source)?;
+        let file_name = format!("{dispatch_name}.c");
+        let path = args.output_dir.join(&file_name);
+        fs::write(&path, source).with_context(|| format!("Failed to write {}", path.display()))?;
+        for (entry, kernel_idx) in entries.iter_mut().zip(entry_kernel_idxs) {
+            if let Some(kernel_idx) = kernel_idx {
+                entry.symbol = Some(kernel_names[kernel_idx].clone());
+                entry.output = Some(file_name.clone());
+            }
+        }
+    }
+
+    let manifest = BatchManifest {
+        synthesis_secs: synthesis_time.as_secs_f64(),
+        hits,
+        misses,
+        specs: entries,
+    };
+    let manifest_path = args.output_dir.join("manifest.json");
+    fs::write(
+        &manifest_path,
+        serde_json::to_string_pretty(&manifest)? + "\n",
+    )
+    .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    info!("Wrote manifest to {}", manifest_path.display());
+    Ok(())
+}
+
+fn write_library<Tgt: CpuTarget>(
+    args: &Args,
+    synthesized_impl: &ImplNode<Tgt>,
+    library_cmd: &LibraryCmd,
+) -> Result<()> {
+    let symbol = &library_cmd.symbol;
+    fs::create_dir_all(&args.output_dir)
+        .with_context(|| format!("Failed to create {}", args.output_dir.display()))?;
+    if let Some(kind) = library_cmd.kind {
+        let built =
+            synthesized_impl.build_library(symbol, kind, &args.output_dir, &build_options(args))?;
+        report_compiler_warnings(&built.compiler_warnings);
+        info!("Wrote {}", built.library_path.display());
         return Ok(());
     }
 
-    let built_artifact = synthesized_impl.build(false)?;
-    let output = built_artifact.run()?;
-    if let Subcommand::Run(_) = subcmd {
-        println!("\nOutput:\n{}", String::from_utf8_lossy(&output.stdout));
+    let mut header = String::new();
+    synthesized_impl.emit_library_header(symbol, &mut header)?;
+    let header_path = args.output_dir.join(format!("{symbol}.h"));
+    fs::write(&header_path, header)
+        .with_context(|| format!("Failed to write {}", header_path.display()))?;
+    let mut source = String::new();
+    synthesized_impl.emit_library(symbol, include_impl(args), &mut source)?;
+    let source_path = args.output_dir.join(format!("{symbol}.c"));
+    fs::write(&source_path, source)
+        .with_context(|| format!("Failed to write {}", source_path.display()))
+}
+
+fn file_stem(path: &path::Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("spec"))
+}
+
+fn output_file_name(args: &Args, name: &str) -> String {
+    match args.format {
+        OutputFormat::C => format!("{name}.c"),
+        OutputFormat::Impl => format!("{name}.txt"),
+        OutputFormat::Rust => format!("{name}.rs"),
     }
-    #[cfg(feature = "verification")]
-    if !args.skip_check && !built_artifact.check_correctness(&spec) {
-        panic!("Generated code returned incorrect output");
+}
+
+/// Writes an Impl to a file in the selected output format.
+fn write_output<Tgt: CpuTarget>(
+    args: &Args,
+    synthesized_impl: &ImplNode<Tgt>,
+    path: &path::Path,
+) -> Result<()> {
+    let mut output = String::new();
+    match args.format {
+        OutputFormat::C => {
+            synthesized_impl.emit_ext(true, include_impl(args), args.thread_style, &mut output)?
+        }
+        OutputFormat::Impl => output = pprint_string(synthesized_impl, args.impl_style),
+        OutputFormat::Rust => {
+            synthesized_impl.emit_rust("kernel", true, include_impl(args), &mut output)?
+        }
     }
+    fs::write(path, output).with_context(|| format!("Failed to write {}", path.display()))
+}
 
-    if let Subcommand::Bench(_) = subcmd {
-        let result = synthesized_impl.bench(bench_inner_loop_iters.unwrap(), None)?;
-        let inner_loop_runtime = result.best_inner_loop_runtime();
-        let kernel_runtime = inner_loop_runtime / result.inner_loop_iterations;
-        println!("\nkernel runtime: {:.8}s", kernel_runtime.as_secs_f32());
-        println!("loop runtime: {}ns", inner_loop_runtime.as_nanos());
+/// Reads serialized [Spec]s from a file. Files with a `.json` extension are read as a sequence of
+/// JSON values; all others are read as YAML, where each document is a [Spec].
+fn read_spec_file<Tgt: CpuTarget>(path: &path::Path) -> Result<Vec<Spec<Tgt>>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read Spec file {}", path.display()))?;
+    let mut specs = vec![];
+    if path.extension().is_some_and(|e| e == "json") {
+        let stream = serde_json::Deserializer::from_str(&text).into_iter::<serde_json::Value>();
+        for (i, document) in stream.enumerate() {
+            let document = document.with_context(|| format!("Spec {i} is not valid JSON"))?;
+            let spec = serde_path_to_error::deserialize(document)
+                .with_context(|| format!("Spec {i} in {} is malformed", path.display()))?;
+            specs.push(
+                validate_spec(spec)
+                    .with_context(|| format!("Spec {i} in {} is invalid", path.display()))?,
+            );
+        }
+    } else {
+        for (i, document) in serde_yaml::Deserializer::from_str(&text).enumerate() {
+            let spec = serde_path_to_error::deserialize(document)
+                .with_context(|| format!("Spec {i} in {} is malformed", path.display()))?;
+            specs.push(
+                validate_spec(spec)
+                    .with_context(|| format!("Spec {i} in {} is invalid", path.display()))?,
+            );
+        }
     }
-    Ok(())
+    Ok(specs)
+}
+
+/// Returns the given [Spec] if it is well-formed and canonical, or an error naming the offending
+/// fields.
+fn validate_spec<Tgt: CpuTarget>(spec: Spec<Tgt>) -> Result<Spec<Tgt>> {
+    let mut canonical = spec.clone();
+    canonical.canonicalize()?;
+    if canonical == spec {
+        return Ok(spec);
+    }
+
+    let mut problems = vec![];
+    if let (LogicalSpec::Primitive(_, auxes, _), LogicalSpec::Primitive(_, canonical_auxes, _)) =
+        (&spec.0, &canonical.0)
+    {
+        for (i, (aux, canonical_aux)) in auxes.iter().zip(canonical_auxes).enumerate() {
+            if aux.layout != canonical_aux.layout {
+                problems.push(format!(
+                    "operand {i}'s layout should be {}",
+                    canonical_aux.layout
+                ));
+            }
+            if aux.contig != canonical_aux.contig {
+                problems.push(format!(
+                    "operand {i}'s contig should be {}",
+                    canonical_aux.contig
+                ));
+            }
+            if aux.aligned != canonical_aux.aligned {
+                problems.push(format!(
+                    "operand {i}'s aligned should be {}",
+                    canonical_aux.aligned
+                ));
+            }
+        }
+    }
+    if canonical.1 != spec.1 {
+        problems.push(format!(
+            "memory limits should be {} (levels slower than every operand must be zero)",
+            canonical.1
+        ));
+    }
+    if problems.is_empty() {
+        problems.push(format!("it should be {canonical}"));
+    }
+    bail!("{spec} is not canonical: {}", problems.join("; "))
 }
@@ -0,0 +1,271 @@
+#[cfg(not(target_env = "msvc"))]
+use tikv_jemallocator::Jemalloc;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use log::{info, warn};
+use nonzero::nonzero as nz;
+use proptest::strategy::Strategy;
+use proptest::test_runner::{Config, RngAlgorithm, TestCaseError, TestError, TestRng, TestRunner};
+
+use std::io::{self, Write};
+use std::{fs, path};
+
+use morello::codegen::{host_can_load, BuildOptions, CodeGen, Compiler};
+use morello::common::DimSize;
+use morello::db::{Database, FilesDatabase};
+use morello::search::top_down;
+use morello::spec::{arb_canonical_spec, Spec};
+use morello::target::{ArmTarget, CpuMemoryLevel, CpuTarget, Target, TargetId, X86Target};
+use morello::verification::{CheckOptions, InputStrategy};
+
+#[cfg(not(target_env = "msvc"))]
+#[global_allocator]
+static GLOBAL: Jemalloc = Jemalloc;
+
+const BINARY_SCALE_SHAPES: bool = true;
+const K: u8 = 1;
+
+/// Synthesizes random canonical Specs, then builds each Impl and checks its output.
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Target to fuzz (may be repeated; defaults to all targets)
+    #[arg(long = "target", value_enum)]
+    targets: Vec<TargetId>,
+
+    /// Number of random Specs to check per target
+    #[arg(long, default_value_t = 256)]
+    cases: u32,
+
+    /// Seed from which Specs are generated (defaults to a random seed)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Maximum size of each Spec dimension
+    #[arg(long, default_value = "4")]
+    max_size: DimSize,
+
+    /// Maximum memory, in bytes, at each level
+    #[arg(long)]
+    max_memory: Option<u64>,
+
+    /// Directory of failing Specs, which are checked again before any new Specs
+    #[arg(long, default_value = "fuzz-regressions")]
+    regressions: path::PathBuf,
+
+    #[arg(long, default_value = "128", help = "Cache size in database pages.")]
+    cache_size: usize,
+
+    /// C compiler with which to build (defaults to `$CLANG`, then `$GCC`)
+    #[arg(long, value_enum)]
+    compiler: Option<Compiler>,
+
+    /// How to choose verification inputs
+    #[arg(long, value_enum, default_value_t = InputStrategy::Sequential)]
+    check_inputs: InputStrategy,
+
+    /// Number of sets of random verification inputs to check per Spec
+    #[arg(long, default_value_t = 4)]
+    check_trials: u32,
+}
+
+fn main() -> Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+    info!("Fuzzing with seed {seed}");
+
+    let targets = if args.targets.is_empty() {
+        vec![TargetId::X86, TargetId::Arm]
+    } else {
+        args.targets.clone()
+    };
+    // Check for the compiler up front so that a missing one isn't reported as failing Specs.
+    let compiler = args.compiler.unwrap_or_else(Compiler::from_env);
+    if compiler.path().is_none() && targets.iter().any(|&target| host_can_load(target)) {
+        bail!("{compiler:?} could not be found; set $CLANG or $GCC");
+    }
+    let mut failed = false;
+    for target in targets {
+        failed |= match target {
+            TargetId::X86 => fuzz::<X86Target>(&args, compiler, seed)?,
+            TargetId::Arm => fuzz::<ArmTarget>(&args, compiler, seed)?,
+        };
+    }
+    if failed {
+        bail!(
+            "Found failing Specs; they are saved in {}",
+            args.regressions.display()
+        );
+    }
+    Ok(())
+}
+
+/// Checks the saved failing Specs for `Tgt`, then random Specs, saving the first new failure
+/// after minimizing it. Returns `true` if any Spec failed.
+fn fuzz<Tgt: CpuTarget>(args: &Args, compiler: Compiler, seed: u64) -> Result<bool> {
+    let target_name = format!("{:?}", Tgt::target_id()).to_lowercase();
+    let regressions_path = args.regressions.join(format!("{target_name}.txt"));
+
+    let mut failed = false;
+    for spec in read_regressions::<Tgt>(&regressions_path)? {
+        if let Err(reason) = check_spec(args, compiler, &spec) {
+            warn!("Saved Spec still fails: {spec}\n{reason}");
+            failed = true;
+        }
+    }
+
+    let mut seed_bytes = [0; 32];
+    seed_bytes[..8].copy_from_slice(&seed.to_le_bytes());
+    let config = Config {
+        cases: args.cases,
+        failure_persistence: None,
+        ..Config::default()
+    };
+    let mut runner = TestRunner::new_with_rng(
+        config,
+        TestRng::from_seed(RngAlgorithm::ChaCha, &seed_bytes),
+    );
+    let strategy = arb_canonical_spec::<Tgt>(Some(args.max_size), args.max_memory)
+        .prop_filter("programs only load row-major inputs in GL", loads_inputs);
+    // The runner shrinks a failing Spec before returning it.
+    match runner.run(&strategy, |spec| {
+        check_spec(args, compiler, &spec).map_err(TestCaseError::fail)
+    }) {
+        Ok(()) => info!("{} random Specs passed for {target_name}", args.cases),
+        Err(TestError::Fail(reason, spec)) => {
+            warn!("Minimized failing Spec: {spec}\n{reason}");
+            save_regression(&regressions_path, &spec, seed)?;
+            failed = true;
+        }
+        Err(TestError::Abort(reason)) => bail!("Fuzzing {target_name} aborted: {reason}"),
+    }
+    Ok(failed)
+}
+
+/// Returns `true` if built programs can load `spec`'s inputs, which must be row-major and in
+/// [CpuMemoryLevel::GL].
+fn loads_inputs<Tgt: CpuTarget>(spec: &Spec<Tgt>) -> bool {
+    spec.0
+        .parameters()
+        .iter()
+        .all(|p| p.level() == CpuMemoryLevel::GL && p.layout().is_row_major())
+}
+
+/// Synthesizes `spec` with a fresh database, then builds with `compiler` and checks the result,
+/// returning why it failed if it did.
+///
+/// Programs are run in their own processes rather than loaded, so a crashing kernel fails the
+/// Spec rather than ending the fuzzer. Impls for targets other than the host's are only emitted.
+fn check_spec<Tgt: CpuTarget>(
+    args: &Args,
+    compiler: Compiler,
+    spec: &Spec<Tgt>,
+) -> Result<(), String> {
+    if !loads_inputs(spec) {
+        warn!("Skipping Spec with inputs programs can't load: {spec}");
+        return Ok(());
+    }
+    let db = FilesDatabase::new(None, BINARY_SCALE_SHAPES, K, args.cache_size, 1, None);
+    top_down(&db, spec, K.into(), Some(nz!(1usize)));
+    let Some(imp) = db.get_impl(spec).and_then(|impls| impls.into_iter().next()) else {
+        // No Impl fits within the Spec's memory limits.
+        return Ok(());
+    };
+
+    let build_options = BuildOptions {
+        compiler,
+        ..Default::default()
+    };
+    if !host_can_load(Tgt::target_id()) {
+        let mut source = String::new();
+        return imp
+            .emit_ext(false, None, build_options.thread_style, &mut source)
+            .map_err(|e| format!("Emission failed: {e}"));
+    }
+    let artifact = imp
+        .build_with_options(false, &build_options)
+        .map_err(|e| format!("Build failed: {e}"))?;
+    let check_options = CheckOptions {
+        strategy: args.check_inputs,
+        trials: args.check_trials,
+        seed: 0,
+    };
+    artifact
+        .check_correctness_with_options(spec, &check_options)
+        .map_err(|failure| failure.to_string())
+}
+
+/// Reads the Specs saved by [save_regression], one per line. Lines starting with `#` are
+/// comments.
+fn read_regressions<Tgt: CpuTarget>(path: &path::Path) -> Result<Vec<Spec<Tgt>>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.parse::<Spec<Tgt>>()
+                .with_context(|| format!("Failed to parse saved Spec: {line}"))
+        })
+        .collect()
+}
+
+fn save_regression<Tgt: Target>(path: &path::Path, spec: &Spec<Tgt>, seed: u64) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "# Found with --seed {seed}")?;
+    writeln!(file, "{spec}")?;
+    info!("Saved failing Spec to {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = "(MatmulAccum((2×3, u8), (3×3, i8, c0), (2×3, u16, L1, ua), serial), \
+                        [64, 1024, 32768, 0])";
+
+    #[test]
+    fn test_saved_regressions_are_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("regressions").join("x86.txt");
+        let spec = SPEC.parse::<Spec<X86Target>>().unwrap();
+        save_regression(&path, &spec, 1).unwrap();
+        save_regression(&path, &spec, 2).unwrap();
+        assert_eq!(
+            read_regressions::<X86Target>(&path).unwrap(),
+            vec![spec.clone(), spec]
+        );
+    }
+
+    #[test]
+    fn test_missing_regressions_file_has_no_specs() {
+        let dir = tempfile::tempdir().unwrap();
+        let specs = read_regressions::<X86Target>(&dir.path().join("x86.txt")).unwrap();
+        assert!(specs.is_empty());
+    }
+
+    #[test]
+    fn test_specs_with_non_row_major_or_non_gl_inputs_are_not_loaded() {
+        let spec = SPEC.parse::<Spec<X86Target>>().unwrap();
+        assert!(!loads_inputs(&spec));
+        let spec = "(Matmul((2×3, u8), (3×3, i8), (2×3, u16), serial), [64, 1024, 32768, 0])"
+            .parse::<Spec<X86Target>>()
+            .unwrap();
+        assert!(loads_inputs(&spec));
+    }
+}
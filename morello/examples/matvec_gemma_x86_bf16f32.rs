@@ -1,4 +1,4 @@
-use morello::codegen::{CodeGen, CpuCodeGenThreadStyle};
+use morello::codegen::{BuildOptions, CodeGen, CpuCodeGenThreadStyle};
 use morello::common::{DimSize, Dtype};
 use morello::cost::Cost;
 use morello::layout::{col_major, row_major, Layout, PhysDim};
@@ -92,7 +92,9 @@ fn main() {
 
     // Benchmark.
     const ITERS: u32 = 100;
-    let result = implementation.bench(ITERS, None).unwrap();
+    let result = implementation
+        .bench(ITERS, None, &BuildOptions::default())
+        .unwrap();
     let kernel_runtime =
         (result.best_inner_loop_runtime() / result.inner_loop_iterations).as_secs_f64();
     let throughput =
@@ -1,6 +1,6 @@
 //! This example shows how to manually schedule a simple matrix multiplication for X86.
 
-use morello::codegen::CodeGen;
+use morello::codegen::{BuildOptions, CodeGen};
 use morello::cost::Cost;
 use morello::layout::row_major;
 use morello::lspec;
@@ -153,14 +153,16 @@ fn main() {
     #[cfg(feature = "verification")]
     {
         let artifact = implementation.build(false).unwrap();
-        if !artifact.check_correctness(&spec) {
-            panic!("Generated code returned incorrect output");
+        if let Err(failure) = artifact.check_correctness(&spec) {
+            panic!("Generated code returned incorrect output:\n{failure}");
         }
     }
 
     // Benchmark.
     const ITERS: u32 = 100;
-    let result = implementation.bench(ITERS, None).unwrap();
+    let result = implementation
+        .bench(ITERS, None, &BuildOptions::default())
+        .unwrap();
     let kernel_runtime =
         (result.best_inner_loop_runtime() / result.inner_loop_iterations).as_secs_f64();
     let throughput =
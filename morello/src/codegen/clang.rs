@@ -4,3 +4,17 @@ pub fn clang_path() -> Option<String> {
         Err(_) => None,
     }
 }
+
+pub fn gcc_path() -> Option<String> {
+    std::env::var("GCC").ok()
+}
+
+/// The archiver used to build static libraries: `$AR` if set, otherwise `ar`.
+pub fn ar_path() -> String {
+    std::env::var("AR").unwrap_or_else(|_| String::from("ar"))
+}
+
+/// The compiler used for the Rust backend: `$RUSTC` if set, otherwise `rustc`.
+pub fn rustc_path() -> String {
+    std::env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"))
+}
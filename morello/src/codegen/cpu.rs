@@ -8,6 +8,8 @@ use std::rc::Rc;
 use super::namegen::NameGenerator;
 use crate::codegen::c_utils::{c_type, printf_fmt, CBuffer, CExprVar, InitType, VecType};
 use crate::codegen::header::HeaderEmitter;
+use crate::codegen::tensor_file;
+use crate::codegen::EmitError;
 use crate::common::{DimSize, Dtype};
 use crate::expr::{AffineForm, NonAffine, NonAffineExpr, Substitute, Term};
 use crate::imp::blocks::Block;
@@ -19,6 +21,7 @@ use crate::imp::ImplNode;
 use crate::layout::BufferVar;
 use crate::pprint::{pprint_write, ImplPrintStyle};
 use crate::shape;
+use crate::spec::Spec;
 use crate::target::cpu::{DOT_PRODUCT_BF16_ACCUM_COUNT, DOT_PRODUCT_BF16_STRIP_SIZE};
 use crate::target::{
     cpu::{DOT_PRODUCT_ACCUM_COUNT, DOT_PRODUCT_STRIP_SIZE},
@@ -37,13 +40,27 @@ pub struct CpuCodeGenerator<'a, Tgt: Target> {
     pub headers: HeaderEmitter,
     pub kernel_name: String,
     pub thread_style: CpuCodeGenThreadStyle,
+    /// Worker functions outlined from parallel loops, to be written before the kernel.
+    outlined_fns: String,
+    /// Names of the C loop iterators in scope at the current point of emission.
+    loop_iters_in_scope: Vec<String>,
+    /// Why emission stopped, if it stopped because the Impl can't be emitted. See
+    /// [CpuCodeGenerator::unsupported].
+    unsupported_reason: Option<String>,
 }
 
-#[derive(Default)]
+/// How parallel loops are run on multiple threads.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
 pub enum CpuCodeGenThreadStyle {
+    /// Annotate parallel loops with `#pragma omp parallel for`.
     #[default]
     OpenMP,
+    /// Run parallel loop bodies on a `hwy::ThreadPool` passed to the kernel. Emits C++.
+    #[cfg_attr(feature = "clap", value(skip))]
     Highway, // TODO: Generalize to plug-in codelets
+    /// Outline parallel loop bodies into worker functions run on POSIX threads.
+    Pthreads,
 }
 
 impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
@@ -69,15 +86,151 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
         top_arg_tensors: &'a [Rc<Tensor<Tgt>>],
         bench: bool,
         out: &mut W,
-    ) -> fmt::Result {
-        debug_assert_eq!(top_arg_tensors.len(), usize::from(imp.parameter_count()));
+    ) -> Result<(), EmitError> {
+        let main_body_str = self
+            .emit_kernel_definition(imp, top_arg_tensors, true)
+            .map_err(|e| self.take_error(e))?;
+        self.headers.emit(Tgt::target_id(), out)?;
+        out.write_char('\n')?;
+        if bench {
+            out.write_str(include_str!("../codegen/partials/benchmarking.c"))?;
+            out.write_str("\n\n")?;
+        }
+        out.write_str(&main_body_str)?;
+        Ok(())
+    }
+
+    /// Write many kernels, and a function dispatching to them by [Spec], into one C file.
+    ///
+    /// Headers and vector type definitions are written once for all kernels. Each kernel is named
+    /// `kernel_` followed by a fresh name from `self.namer` and, unlike [Self::emit_kernel], may
+    /// be inlined into the others. The dispatch function, named `dispatch_name`, has the signature
+    /// `int dispatch_name(const char *spec, void *const *args)`, where `spec` is the [Spec]'s
+    /// [Display](std::fmt::Display) form. It returns 0 if it called a kernel and 1 if no kernel
+    /// implements `spec`.
+    ///
+    /// Returns the kernel names in the order of `kernels`. Returns [EmitError::Unsupported],
+    /// without writing anything, if `self.thread_style` adds arguments to kernels (e.g., a Highway
+    /// thread pool), since the dispatch function has no way to supply them.
+    pub fn emit_kernels<W: Write>(
+        &mut self,
+        kernels: &'a [(Spec<Tgt>, ImplNode<Tgt>)],
+        top_arg_tensors: &'a [Vec<Rc<Tensor<Tgt>>>],
+        dispatch_name: &str,
+        out: &mut W,
+    ) -> Result<Vec<String>, EmitError> {
+        debug_assert_eq!(kernels.len(), top_arg_tensors.len());
+        if !self.thread_style_extra_args().is_empty() {
+            return Err(EmitError::Unsupported(format!(
+                "the {:?} thread style adds arguments to kernels, so it can't be used to emit \
+                 many kernels into one file",
+                self.thread_style
+            )));
+        }
+
+        let mut definitions = String::new();
+        let mut kernel_names = Vec::with_capacity(kernels.len());
+        for ((_, imp), tensors) in kernels.iter().zip(top_arg_tensors) {
+            self.name_env.clear();
+            self.loop_iter_bindings.clear();
+            self.param_bindings.clear();
+            self.kernel_name = format!("kernel_{}", self.namer.fresh_name());
+            let definition = self
+                .emit_kernel_definition(imp, tensors, false)
+                .map_err(|e| self.take_error(e))?;
+            definitions.push_str(&definition);
+            definitions.push('\n');
+            kernel_names.push(self.kernel_name.clone());
+        }
+
+        self.headers.emit(Tgt::target_id(), out)?;
+        out.write_char('\n')?;
+        out.write_str(&definitions)?;
+
+        // Adapt each kernel to a common signature so that they can share a table.
+        for ((_, imp), kernel_name) in kernels.iter().zip(&kernel_names) {
+            writeln!(
+                out,
+                "static void {kernel_name}_dispatch(void *const *args) {{"
+            )?;
+            let args = (0..imp.parameter_count())
+                .map(|i| format!("args[{i}]"))
+                .join(", ");
+            writeln!(out, "{}{kernel_name}({args});", indent(1))?;
+            writeln!(out, "}}\n")?;
+        }
+
+        writeln!(out, "static const struct {{")?;
+        writeln!(out, "{}const char *spec;", indent(1))?;
+        writeln!(out, "{}void (*fn)(void *const *args);", indent(1))?;
+        writeln!(out, "}} {dispatch_name}_table[] = {{")?;
+        for ((spec, _), kernel_name) in kernels.iter().zip(&kernel_names) {
+            writeln!(
+                out,
+                "{}{{{}, {kernel_name}_dispatch}},",
+                indent(1),
+                c_string_literal(&spec.to_string())
+            )?;
+        }
+        writeln!(out, "}};\n")?;
 
-        let mut main_body_str = String::new();
         writeln!(
-            main_body_str,
-            "__attribute__((noinline))\nvoid {}(",
-            self.kernel_name
+            out,
+            "int {dispatch_name}(const char *spec, void *const *args) {{"
+        )?;
+        writeln!(
+            out,
+            "{}for (size_t i = 0; i < {}; i++) {{",
+            indent(1),
+            kernels.len()
         )?;
+        writeln!(
+            out,
+            "{}if (strcmp({dispatch_name}_table[i].spec, spec) == 0) {{",
+            indent(2)
+        )?;
+        writeln!(out, "{}{dispatch_name}_table[i].fn(args);", indent(3))?;
+        writeln!(out, "{}return 0;", indent(3))?;
+        writeln!(out, "{}}}", indent(2))?;
+        writeln!(out, "{}}}", indent(1))?;
+        writeln!(out, "{}return 1;", indent(1))?;
+        writeln!(out, "}}")?;
+
+        Ok(kernel_names)
+    }
+
+    /// Records that the Impl can't be emitted because of `reason`, returning the [fmt::Error] with
+    /// which to stop emitting. The public emission methods return `reason` as an
+    /// [EmitError::Unsupported].
+    fn unsupported(&mut self, reason: String) -> fmt::Error {
+        self.unsupported_reason = Some(reason);
+        fmt::Error
+    }
+
+    /// Converts an error from emitting the kernel into an [EmitError].
+    fn take_error(&mut self, err: fmt::Error) -> EmitError {
+        match self.unsupported_reason.take() {
+            Some(reason) => EmitError::Unsupported(reason),
+            None => EmitError::Fmt(err),
+        }
+    }
+
+    /// Returns the C function definition of the kernel named `self.kernel_name`.
+    ///
+    /// Headers needed by the definition are recorded in `self.headers`.
+    fn emit_kernel_definition(
+        &mut self,
+        imp: &'a ImplNode<Tgt>,
+        top_arg_tensors: &'a [Rc<Tensor<Tgt>>],
+        noinline: bool,
+    ) -> Result<String, fmt::Error> {
+        debug_assert_eq!(top_arg_tensors.len(), usize::from(imp.parameter_count()));
+
+        let mut main_body_str = String::new();
+        if noinline {
+            writeln!(main_body_str, "__attribute__((noinline))")?;
+        }
+        writeln!(main_body_str, "void {}(", self.kernel_name)?;
 
         let thread_extra_args = self.thread_style_extra_args();
         let parameter_count = usize::from(imp.parameter_count());
@@ -139,15 +292,59 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
 
         writeln!(main_body_str, "}}")?;
 
-        self.headers.emit(Tgt::target_id(), out)?;
-        out.write_char('\n')?;
-        if bench {
-            out.write_str(include_str!("../codegen/partials/benchmarking.c"))?;
-            out.write_str("\n\n")?;
-        }
-        out.write_str(&main_body_str)
+        // Worker functions must precede the kernel which calls them.
+        let mut definition_str = std::mem::take(&mut self.outlined_fns);
+        definition_str.push_str(&main_body_str);
+        Ok(definition_str)
     }
 
+    /// Write a C header declaring the kernel emitted by [Self::emit_kernel].
+    ///
+    /// The header has an include guard derived from `self.kernel_name` and can be included from C
+    /// or C++.
+    pub fn emit_kernel_prototype<W: Write>(&self, imp: &ImplNode<Tgt>, out: &mut W) -> fmt::Result {
+        let guard = format!("MORELLO_{}_H", self.kernel_name.to_ascii_uppercase());
+        writeln!(out, "#ifndef {guard}\n#define {guard}\n")?;
+        writeln!(out, "#include <stdint.h>\n")?;
+        writeln!(out, "#ifdef __cplusplus\nextern \"C\" {{\n#endif\n")?;
+
+        let parameter_count = usize::from(imp.parameter_count());
+        let mut args = imp
+            .parameters()
+            .enumerate()
+            .map(|(idx, operand)| {
+                format!(
+                    "{}{} *__restrict__ p{idx}",
+                    if idx + 1 < parameter_count {
+                        "const "
+                    } else {
+                        ""
+                    },
+                    c_type(operand.dtype),
+                )
+            })
+            .collect::<Vec<_>>();
+        args.extend(
+            self.thread_style_extra_args()
+                .iter()
+                .map(|a| String::from(*a)),
+        );
+        writeln!(
+            out,
+            "void {}(\n  {}\n);\n",
+            self.kernel_name,
+            args.join(",\n  ")
+        )?;
+
+        writeln!(out, "#ifdef __cplusplus\n}}\n#endif\n")?;
+        writeln!(out, "#endif /* {guard} */")
+    }
+
+    /// Write a C function, `load_inputs`, which reads each parameter from a file.
+    ///
+    /// If its `binary` argument is zero, each file holds just the parameter's little-endian values.
+    /// Otherwise, each file is in the [tensor_file](crate::codegen::tensor_file) format and
+    /// `load_inputs` checks that its header matches the parameter.
     pub fn emit_load_inputs<W: Write>(
         &mut self,
         top_arg_tensors: &'a [Rc<Tensor<Tgt>>],
@@ -160,7 +357,7 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
             todo!("Support changing layout for non-row-major tensors");
         }
 
-        write!(out, "int load_inputs(char *paths[]")?;
+        write!(out, "int load_inputs(char *paths[], int binary")?;
         for i in 0..top_arg_tensors.len() {
             write!(out, ", void *__restrict__ dest{i}")?;
         }
@@ -168,11 +365,13 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
 
         writeln!(out, "{}int fd;", indent(1))?;
         writeln!(out, "{}void *mapped;", indent(1))?;
+        writeln!(out, "{}size_t offset;", indent(1))?;
 
         for (idx, input_tensor) in top_arg_tensors.iter().enumerate() {
             // Open and mmap the data.
+            let spec = input_tensor.spec();
             let value_cnt = input_tensor.0.volume().get();
-            let byte_cnt = value_cnt * u32::from(input_tensor.spec().dtype().size());
+            let byte_cnt = value_cnt * u32::from(spec.dtype().size());
             writeln!(out)?;
             writeln!(
                 out,
@@ -180,22 +379,54 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
                 indent(1)
             )?;
             writeln!(out, "{}return 1;", indent(2))?;
-            writeln!(out, "{}if ((mapped = mmap(NULL, {byte_cnt}, PROT_READ, MAP_SHARED, fd, 0)) == MAP_FAILED)", indent(1))?;
+            writeln!(
+                out,
+                "{}offset = binary ? {} : 0;",
+                indent(1),
+                tensor_file::header_len(spec.shape().len())
+            )?;
+            writeln!(out, "{}if ((mapped = mmap(NULL, offset + {byte_cnt}, PROT_READ, MAP_SHARED, fd, 0)) == MAP_FAILED)", indent(1))?;
             writeln!(out, "{}return 2;", indent(2))?;
             writeln!(out, "{}close(fd);", indent(1))?;
 
+            // Check the header, if any, describes this parameter.
+            let header_fields = header_fields(spec.dtype(), spec.shape());
+            let header_check = header_fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    format!(
+                        " || LE_TO_CPU32(((uint32_t *)mapped)[{}]) != {field}",
+                        i + 1
+                    )
+                })
+                .join("");
+            writeln!(
+                out,
+                "{}if (binary && (memcmp(mapped, {}, 4) != 0{header_check})) {{",
+                indent(1),
+                tensor_file_magic_literal()
+            )?;
+            writeln!(out, "{}munmap(mapped, offset + {byte_cnt});", indent(2))?;
+            writeln!(out, "{}return 4;", indent(2))?;
+            writeln!(out, "{}}}", indent(1))?;
+
             // Move into destination argument.
             writeln!(out, "{}for (int i = 0; i < {value_cnt}; i++)", indent(1))?;
             writeln!(
                 out,
-                "{}(({1} *)dest{idx})[i] = {2}((({1} *)mapped)[i]);",
+                "{}(({1} *)dest{idx})[i] = {2}((({1} *)((char *)mapped + offset))[i]);",
                 indent(2),
-                c_type(input_tensor.spec().dtype()),
-                endian_convert_fn(input_tensor.spec().dtype())
+                c_type(spec.dtype()),
+                endian_convert_fn(spec.dtype())
             )?;
 
             // Un-map.
-            writeln!(out, "{}if (munmap(mapped, {byte_cnt}) != 0)", indent(1))?;
+            writeln!(
+                out,
+                "{}if (munmap(mapped, offset + {byte_cnt}) != 0)",
+                indent(1)
+            )?;
             writeln!(out, "{}return 3;", indent(2))?;
         }
 
@@ -203,6 +434,68 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
         writeln!(out, "}}")
     }
 
+    /// Write a C function, `write_output`, which writes the output parameter to a file in the
+    /// [tensor_file](crate::codegen::tensor_file) format.
+    pub fn emit_write_output<W: Write>(
+        &mut self,
+        top_arg_tensors: &'a [Rc<Tensor<Tgt>>],
+        out: &mut W,
+    ) -> fmt::Result {
+        let spec = top_arg_tensors.last().unwrap().spec();
+        let dtype = spec.dtype();
+        let value_cnt = spec.volume().get();
+
+        writeln!(
+            out,
+            "int write_output(const char *path, const {} *src) {{",
+            c_type(dtype)
+        )?;
+        writeln!(out, "{}FILE *f = fopen(path, \"wb\");", indent(1))?;
+        writeln!(out, "{}if (f == NULL)", indent(1))?;
+        writeln!(out, "{}return 1;", indent(2))?;
+        writeln!(
+            out,
+            "{}const uint32_t header[] = {{{}}};",
+            indent(1),
+            header_fields(dtype, spec.shape())
+                .iter()
+                .map(|field| format!("LE_TO_CPU32({field})"))
+                .join(", ")
+        )?;
+        writeln!(
+            out,
+            "{}if (fwrite({}, 1, 4, f) != 4 || fwrite(header, sizeof(header), 1, f) != 1) {{",
+            indent(1),
+            tensor_file_magic_literal()
+        )?;
+        writeln!(out, "{}fclose(f);", indent(2))?;
+        writeln!(out, "{}return 2;", indent(2))?;
+        writeln!(out, "{}}}", indent(1))?;
+        writeln!(
+            out,
+            "{}for (size_t i = 0; i < {value_cnt}; i++) {{",
+            indent(1)
+        )?;
+        writeln!(
+            out,
+            "{}{} value = {}(src[i]);",
+            indent(2),
+            c_type(dtype),
+            endian_convert_fn(dtype)
+        )?;
+        writeln!(
+            out,
+            "{}if (fwrite(&value, sizeof(value), 1, f) != 1) {{",
+            indent(2)
+        )?;
+        writeln!(out, "{}fclose(f);", indent(3))?;
+        writeln!(out, "{}return 2;", indent(3))?;
+        writeln!(out, "{}}}", indent(2))?;
+        writeln!(out, "{}}}", indent(1))?;
+        writeln!(out, "{}return fclose(f) == 0 ? 0 : 2;", indent(1))?;
+        writeln!(out, "}}")
+    }
+
     pub fn emit_standard_main<W: Write>(
         &mut self,
         top_arg_tensors: &'a [Rc<Tensor<Tgt>>],
@@ -227,18 +520,25 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
             writeln!(main_body_str)?;
         }
 
-        // Load data, if provided.
+        // Load data, if provided. With `--binary OUTPUT`, inputs and the output are in the
+        // tensor file format.
         let full_argc = top_arg_tensors.len() + 1;
         writeln!(
             main_body_str,
-            "{}if (argc == {}) {{",
+            "{}const int binary = argc == {} && strcmp(argv[1], \"--binary\") == 0;",
+            indent(depth),
+            full_argc + 2
+        )?;
+        writeln!(
+            main_body_str,
+            "{}if (argc == {} || binary) {{",
             indent(depth),
             full_argc
         )?;
         depth += 1;
         writeln!(
             main_body_str,
-            "{}int load_result = load_inputs(&argv[1]{});",
+            "{}int load_result = load_inputs(&argv[binary ? 3 : 1], binary{});",
             indent(depth),
             parameter_buf_names
                 .iter()
@@ -269,7 +569,27 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
 
         let kernel_call_str = self.make_kernel_call(top_arg_tensors)?;
         writeln!(main_body_str, "{}{}\n", indent(depth), kernel_call_str)?;
-        self.emit_print_tensor(top_arg_tensors.last().unwrap(), depth, &mut main_body_str)?;
+        writeln!(main_body_str, "{}if (binary) {{", indent(depth))?;
+        writeln!(
+            main_body_str,
+            "{}if (write_output(argv[2], {}) != 0) {{",
+            indent(depth + 1),
+            parameter_buf_names.last().unwrap()
+        )?;
+        writeln!(
+            main_body_str,
+            "{}fprintf(stderr, \"Error writing output tensor.\\n\");",
+            indent(depth + 2)
+        )?;
+        writeln!(main_body_str, "{}return 3;", indent(depth + 2))?;
+        writeln!(main_body_str, "{}}}", indent(depth + 1))?;
+        writeln!(main_body_str, "{}}} else {{", indent(depth))?;
+        self.emit_print_tensor(
+            top_arg_tensors.last().unwrap(),
+            depth + 1,
+            &mut main_body_str,
+        )?;
+        writeln!(main_body_str, "{}}}", indent(depth))?;
         writeln!(main_body_str)?;
 
         // Free the buffers.
@@ -371,7 +691,12 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
             "{}clock_gettime(CLOCK_MONOTONIC, &start);",
             indent(depth)
         )?;
-        writeln!(out, "#pragma clang loop unroll(disable)")?; // preprocessor directives should not have indentation.
+        // Preprocessor directives should not have indentation.
+        writeln!(out, "#if defined(__clang__)")?;
+        writeln!(out, "#pragma clang loop unroll(disable)")?;
+        writeln!(out, "#elif defined(__GNUC__)")?;
+        writeln!(out, "#pragma GCC unroll 1")?;
+        writeln!(out, "#endif")?;
         writeln!(
             out,
             "{}for (long long bench_itr = 0; bench_itr < bench_samples; ++bench_itr) {{",
@@ -788,15 +1113,18 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
                             )?;
 
                             // TODO: Combine!
-                            // TODO: Indices below should be generic in vector size
+                            // Interleave element-wise rather than with `__builtin_shufflevector`,
+                            // which GCC lacks before version 12.
                             writeln!(
                                 w,
-                                "{}{} {} = __builtin_shufflevector({}, {}, 0, 4, 1, 5, 2, 6, 3, 7);",
+                                "{}{} {} = ({}){{{}}};",
                                 indent(depth),
                                 vfc.name,
                                 concat_name,
-                                odd_name,
-                                even_name,
+                                vfc.name,
+                                (0..vf8.value_cnt)
+                                    .map(|i| format!("{odd_name}[{i}], {even_name}[{i}]"))
+                                    .join(", "),
                             )?;
 
                             // TODO: Don't inline `short` below.
@@ -1513,8 +1841,12 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
                     }
                     return Ok(());
                 }
+                CpuCodeGenThreadStyle::Pthreads => {
+                    return self.emit_pthreads_loop(w, l, &axes_to_emit, &iter_var_names, depth);
+                }
             }
         }
+        let scope_len = self.loop_iters_in_scope.len();
         for (axis, steps) in &axes_to_emit {
             let var_name = iter_var_names.get(axis).unwrap();
             writeln!(
@@ -1522,14 +1854,201 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
                 "{0}for (int {var_name} = 0; {var_name} < {steps}; {var_name}++) {{",
                 indent(depth)
             )?;
+            self.loop_iters_in_scope.push(var_name.clone());
         }
         self.emit(w, &l.body, depth + 1)?;
+        self.loop_iters_in_scope.truncate(scope_len);
         for _ in 0..axes_to_emit.len() {
             writeln!(w, "{}}}", indent(depth))?;
         }
         Ok(())
     }
 
+    /// Emit a parallel loop which splits its iterations over up to [Target::processors] threads.
+    ///
+    /// The loop body is outlined into a worker function, added to `self.outlined_fns`, which runs
+    /// a contiguous range of the loop nest's collapsed iterations. The buffers and loop iterators
+    /// in scope are passed to each worker in a context struct.
+    fn emit_pthreads_loop<W: Write>(
+        &mut self,
+        w: &mut W,
+        l: &Loop<Tgt>,
+        axes_to_emit: &[(u8, u32)],
+        iter_var_names: &HashMap<u8, String>,
+        depth: usize,
+    ) -> fmt::Result {
+        self.headers.emit_pthread_header = true;
+
+        // Sort captures by name so that output doesn't depend on HashMap order.
+        let mut captured_buffers = self
+            .name_env
+            .iter()
+            .map(|(tensor, buffer)| match buffer {
+                CBuffer::HeapArray { name, dtype, .. }
+                | CBuffer::StackArray { name, dtype, .. }
+                | CBuffer::Ptr { name, dtype } => Ok((Rc::clone(tensor), name.clone(), *dtype)),
+                // Worker threads can't share the caller's registers, and copies wouldn't carry
+                // their writes back, so Impls like this can't be emitted with pthreads.
+                CBuffer::ValueVar { name, .. } => Err(format!("scalar register `{name}`")),
+                CBuffer::SingleVecVar { name, .. } => Err(format!("vector register `{name}`")),
+                CBuffer::VecVars { inner_vecs } => Err(format!(
+                    "vector registers `{}`",
+                    inner_vecs.iter().filter_map(CBuffer::name).join("`, `")
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|buffer| {
+                self.unsupported(format!(
+                    "the pthreads thread style can't pass the {buffer} to a parallel loop's \
+                     worker threads"
+                ))
+            })?;
+        captured_buffers.sort_by(|a, b| a.1.cmp(&b.1));
+        let captured_iters = self.loop_iters_in_scope.clone();
+
+        let worker_name = format!("{}_worker_{}", self.kernel_name, self.namer.fresh_name());
+        let total_steps = axes_to_emit
+            .iter()
+            .map(|&(_, steps)| u64::from(steps))
+            .product::<u64>();
+        let thread_count = total_steps.min(Tgt::processors().into());
+
+        let mut worker = String::new();
+        writeln!(worker, "struct {worker_name}_ctx {{")?;
+        for (_, name, dtype) in &captured_buffers {
+            writeln!(worker, "{}{} *{name};", indent(1), c_type(*dtype))?;
+        }
+        for name in &captured_iters {
+            writeln!(worker, "{}int {name};", indent(1))?;
+        }
+        writeln!(worker, "{}long long begin;", indent(1))?;
+        writeln!(worker, "{}long long end;", indent(1))?;
+        writeln!(worker, "}};\n")?;
+
+        writeln!(worker, "static void *{worker_name}(void *arg) {{")?;
+        writeln!(
+            worker,
+            "{}const struct {worker_name}_ctx *ctx = arg;",
+            indent(1)
+        )?;
+        for (_, name, dtype) in &captured_buffers {
+            writeln!(
+                worker,
+                "{}{} *__restrict__ {name} = ctx->{name};",
+                indent(1),
+                c_type(*dtype)
+            )?;
+        }
+        for name in &captured_iters {
+            writeln!(worker, "{}int {name} = ctx->{name};", indent(1))?;
+        }
+        writeln!(
+            worker,
+            "{}for (long long flat = ctx->begin; flat < ctx->end; flat++) {{",
+            indent(1)
+        )?;
+        let mut stride = total_steps;
+        for (axis, steps) in axes_to_emit {
+            stride /= u64::from(*steps);
+            let flat_expr = if stride == 1 {
+                String::from("flat")
+            } else {
+                format!("flat / {stride}")
+            };
+            writeln!(
+                worker,
+                "{}int {} = (int)({flat_expr} % {steps});",
+                indent(2),
+                iter_var_names.get(axis).unwrap()
+            )?;
+        }
+
+        // Within the worker, every captured buffer is a pointer.
+        let outer_name_env = self.name_env.clone();
+        for (tensor, name, dtype) in &captured_buffers {
+            self.name_env.insert(
+                Rc::clone(tensor),
+                CBuffer::Ptr {
+                    name: name.clone(),
+                    dtype: *dtype,
+                },
+            );
+        }
+        let outer_loop_iters = std::mem::replace(
+            &mut self.loop_iters_in_scope,
+            axes_to_emit
+                .iter()
+                .map(|(axis, _)| iter_var_names.get(axis).unwrap().clone())
+                .collect(),
+        );
+        self.emit(&mut worker, &l.body, 2)?;
+        self.loop_iters_in_scope = outer_loop_iters;
+        self.name_env = outer_name_env;
+
+        writeln!(worker, "{}}}", indent(1))?;
+        writeln!(worker, "{}return NULL;", indent(1))?;
+        writeln!(worker, "}}\n")?;
+        self.outlined_fns.push_str(&worker);
+
+        // Start the threads, giving each an equal share of the iterations, and wait for them. A
+        // share whose thread couldn't be created is run on the calling thread instead.
+        writeln!(w, "{}{{", indent(depth))?;
+        writeln!(w, "{}pthread_t threads[{thread_count}];", indent(depth + 1))?;
+        writeln!(w, "{}int started[{thread_count}];", indent(depth + 1))?;
+        writeln!(
+            w,
+            "{}struct {worker_name}_ctx thread_ctxs[{thread_count}];",
+            indent(depth + 1)
+        )?;
+        writeln!(
+            w,
+            "{}for (int thread_idx = 0; thread_idx < {thread_count}; thread_idx++) {{",
+            indent(depth + 1)
+        )?;
+        let ctx_fields = captured_buffers
+            .iter()
+            .map(|(_, name, dtype)| format!(".{name} = ({} *){name}", c_type(*dtype)))
+            .chain(
+                captured_iters
+                    .iter()
+                    .map(|name| format!(".{name} = {name}")),
+            )
+            .chain([
+                format!(".begin = thread_idx * {total_steps}LL / {thread_count}"),
+                format!(".end = (thread_idx + 1) * {total_steps}LL / {thread_count}"),
+            ])
+            .join(", ");
+        writeln!(
+            w,
+            "{}thread_ctxs[thread_idx] = (struct {worker_name}_ctx){{{ctx_fields}}};",
+            indent(depth + 2)
+        )?;
+        writeln!(
+            w,
+            "{}started[thread_idx] = pthread_create(&threads[thread_idx], NULL, {worker_name}, &thread_ctxs[thread_idx]) == 0;",
+            indent(depth + 2)
+        )?;
+        writeln!(w, "{}if (!started[thread_idx])", indent(depth + 2))?;
+        writeln!(
+            w,
+            "{}{worker_name}(&thread_ctxs[thread_idx]);",
+            indent(depth + 3)
+        )?;
+        writeln!(w, "{}}}", indent(depth + 1))?;
+        writeln!(
+            w,
+            "{}for (int thread_idx = 0; thread_idx < {thread_count}; thread_idx++)",
+            indent(depth + 1)
+        )?;
+        writeln!(w, "{}if (started[thread_idx])", indent(depth + 2))?;
+        writeln!(
+            w,
+            "{}pthread_join(threads[thread_idx], NULL);",
+            indent(depth + 3)
+        )?;
+        writeln!(w, "{}}}", indent(depth))
+    }
+
     fn emit_unrolled_loop<W: Write>(
         &mut self,
         w: &mut W,
@@ -1746,7 +2265,7 @@ impl<'a, Tgt: CpuTarget> CpuCodeGenerator<'a, Tgt> {
 
     fn thread_style_extra_args(&self) -> &[&'static str] {
         match self.thread_style {
-            CpuCodeGenThreadStyle::OpenMP => &[],
+            CpuCodeGenThreadStyle::OpenMP | CpuCodeGenThreadStyle::Pthreads => &[],
             CpuCodeGenThreadStyle::Highway => &["hwy::ThreadPool& pool"],
         }
     }
@@ -1762,8 +2281,29 @@ impl<'a, Tgt: Target> Default for CpuCodeGenerator<'a, Tgt> {
             headers: Default::default(),
             kernel_name: String::from("kernel"),
             thread_style: Default::default(),
+            outlined_fns: String::new(),
+            loop_iters_in_scope: vec![],
+            unsupported_reason: None,
+        }
+    }
+}
+
+/// Returns `s` as a C string literal.
+fn c_string_literal(s: &str) -> String {
+    let mut literal = String::with_capacity(s.len() + 2);
+    literal.push('"');
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            '\n' => literal.push_str("\\n"),
+            _ => literal.push(c),
         }
     }
+    literal.push('"');
+    literal
 }
 
 fn vec_func_names(
@@ -1787,7 +2327,9 @@ fn vec_func_names(
     }
 }
 
-fn axis_order_and_steps<Tgt: Target>(l: &Loop<Tgt>) -> impl Iterator<Item = (u8, u32)> + '_ {
+pub(super) fn axis_order_and_steps<Tgt: Target>(
+    l: &Loop<Tgt>,
+) -> impl Iterator<Item = (u8, u32)> + '_ {
     // TODO: Choose according to a skip-minimizing heuristic.
     let result = l
         .tiles
@@ -1821,7 +2363,7 @@ fn axis_order_and_steps<Tgt: Target>(l: &Loop<Tgt>) -> impl Iterator<Item = (u8,
     result
 }
 
-fn get_vector(
+pub(super) fn get_vector(
     vec_types: &'static [VecType; 16],
     dtype: Dtype,
     vector_size: DimSize,
@@ -1840,7 +2382,7 @@ fn get_vector(
         })
 }
 
-fn expr_to_c(e: &AffineForm<NonAffine<CExprVar>>) -> String {
+pub(super) fn expr_to_c(e: &AffineForm<NonAffine<CExprVar>>) -> String {
     let mut buf =
         e.0.iter()
             .map(|Term(coef, sym)| {
@@ -1880,7 +2422,7 @@ fn cexpr_subexpr_to_c(subexpr: &NonAffine<CExprVar>) -> String {
     }
 }
 
-fn zero_points(expr: NonAffineExpr<BufferVar>) -> NonAffineExpr<BufferVar> {
+pub(super) fn zero_points(expr: NonAffineExpr<BufferVar>) -> NonAffineExpr<BufferVar> {
     expr.map_vars(&mut |v| match v {
         BufferVar::TileIdx(_, _) => AffineForm::from(v),
         BufferVar::Pt(_, _) => AffineForm::zero(),
@@ -1898,6 +2440,23 @@ const fn endian_convert_fn(dtype: Dtype) -> &'static str {
     }
 }
 
+/// Returns the C literals of a tensor file header's fields after the magic.
+fn header_fields(dtype: Dtype, shape: &[DimSize]) -> Vec<String> {
+    [
+        tensor_file::dtype_code(dtype),
+        u32::try_from(shape.len()).unwrap(),
+    ]
+    .into_iter()
+    .chain(shape.iter().map(|d| d.get()))
+    .map(|field| format!("{field}u"))
+    .collect()
+}
+
+/// Returns [tensor_file::TENSOR_FILE_MAGIC] as a C string literal.
+fn tensor_file_magic_literal() -> String {
+    c_string_literal(std::str::from_utf8(&tensor_file::TENSOR_FILE_MAGIC).unwrap())
+}
+
 #[cfg(test)]
 mod tests {
     use super::expr_to_c;
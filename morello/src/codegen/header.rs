@@ -7,6 +7,7 @@ pub struct HeaderEmitter {
     pub emit_benchmarking: bool,
     pub vector_type_defs: HashSet<&'static VecType>,
     pub emit_stdbool_and_assert_headers: bool,
+    pub emit_pthread_header: bool,
 }
 
 impl HeaderEmitter {
@@ -15,6 +16,7 @@ impl HeaderEmitter {
             emit_benchmarking: false,
             vector_type_defs: HashSet::new(),
             emit_stdbool_and_assert_headers: false,
+            emit_pthread_header: false,
         }
     }
 
@@ -24,6 +26,9 @@ impl HeaderEmitter {
         if self.emit_stdbool_and_assert_headers {
             out.write_str("#include <assert.h>\n#include <stdbool.h>\n")?;
         }
+        if self.emit_pthread_header {
+            out.write_str("#include <pthread.h>\n")?;
+        }
         match target {
             TargetId::X86 => {
                 out.write_str(include_str!("../codegen/partials/x86.c"))?;
@@ -38,7 +43,10 @@ impl HeaderEmitter {
             out.write_char('\n')?;
         }
 
-        for vec_type in &self.vector_type_defs {
+        // Sort so that output doesn't depend on the order in which types were used.
+        let mut vector_type_defs = self.vector_type_defs.iter().collect::<Vec<_>>();
+        vector_type_defs.sort_unstable_by_key(|vec_type| vec_type.name);
+        for vec_type in vector_type_defs {
             // Declare a vector of {vec_bytes} bytes, divided into {dt.c_type}
             // values. (vec_bytes must be a multiple of the c_type size.)
             out.write_str(&format!(
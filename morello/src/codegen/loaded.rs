@@ -0,0 +1,187 @@
+//! Kernels built as shared libraries and loaded into the current process.
+
+use std::alloc::{self, Layout};
+use std::convert::Infallible;
+use std::ffi::c_void;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::time::{Duration, Instant};
+
+use libloading::Library;
+use tempfile::TempDir;
+
+use super::{optimal_iters, sample_runtimes, RobustTimingResult};
+use crate::common::Dtype;
+use crate::target::TargetId;
+
+/// The name of the function, in libraries built by [crate::codegen::CodeGen::build_loaded], which
+/// calls the kernel with its arguments given as an array of pointers.
+pub(super) const CALL_SYMBOL: &str = "morello_kernel_call";
+
+/// Alignment of the buffers passed to a kernel, matching the buffers allocated by generated
+/// `main` functions.
+const BUFFER_ALIGN: usize = 128;
+
+type CallFn = unsafe extern "C" fn(*const *mut c_void);
+
+/// A kernel loaded from a shared library built by [crate::codegen::CodeGen::build_loaded].
+///
+/// Calling the kernel directly avoids the process start-up, temporary files, and output parsing of
+/// running a [crate::codegen::BuiltArtifact].
+pub struct LoadedKernel {
+    call: CallFn,
+    library_path: PathBuf,
+    parameter_dtypes: Vec<Dtype>,
+    parameter_volumes: Vec<usize>,
+    /// Whether each parameter has a row-major layout.
+    row_major_parameters: Vec<bool>,
+    compiler_warnings: String,
+    // `call` points into `_library`, which must be unloaded before `_scratch_dir` is deleted.
+    _library: Library,
+    _scratch_dir: Option<TempDir>,
+}
+
+/// A zeroed, [BUFFER_ALIGN]-aligned buffer for a kernel argument.
+pub(crate) struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl LoadedKernel {
+    /// Loads the library at `library_path`.
+    ///
+    /// # Safety
+    /// The library must have been built by [crate::codegen::CodeGen::build_loaded] for a kernel
+    /// with the given parameters.
+    pub(super) unsafe fn load(
+        library_path: PathBuf,
+        scratch_dir: Option<TempDir>,
+        parameter_dtypes: Vec<Dtype>,
+        parameter_volumes: Vec<usize>,
+        row_major_parameters: Vec<bool>,
+        compiler_warnings: String,
+    ) -> Result<Self, libloading::Error> {
+        let library = Library::new(&library_path)?;
+        let call = *library.get::<CallFn>(CALL_SYMBOL.as_bytes())?;
+        Ok(LoadedKernel {
+            call,
+            library_path,
+            parameter_dtypes,
+            parameter_volumes,
+            row_major_parameters,
+            compiler_warnings,
+            _library: library,
+            _scratch_dir: scratch_dir,
+        })
+    }
+
+    pub fn library_path(&self) -> &Path {
+        &self.library_path
+    }
+
+    pub fn parameter_dtypes(&self) -> &[Dtype] {
+        &self.parameter_dtypes
+    }
+
+    /// Anything the compiler wrote to stderr, such as warnings.
+    pub fn compiler_warnings(&self) -> &str {
+        &self.compiler_warnings
+    }
+
+    /// Returns the index of the first parameter which doesn't have a row-major layout, if any.
+    #[cfg(feature = "verification")]
+    pub(crate) fn first_non_row_major_parameter(&self) -> Option<usize> {
+        self.row_major_parameters.iter().position(|&r| !r)
+    }
+
+    /// Returns zeroed buffers of the right sizes for each of the kernel's parameters.
+    pub(crate) fn make_buffers(&self) -> Vec<AlignedBuffer> {
+        self.parameter_dtypes
+            .iter()
+            .zip(&self.parameter_volumes)
+            .map(|(dtype, volume)| AlignedBuffer::zeroed(volume * usize::from(dtype.size())))
+            .collect()
+    }
+
+    /// Calls the kernel on `buffers`, which should be from [LoadedKernel::make_buffers].
+    pub(crate) fn call(&self, buffers: &mut [AlignedBuffer]) {
+        assert_eq!(buffers.len(), self.parameter_dtypes.len());
+        let args = buffers
+            .iter_mut()
+            .map(|b| b.ptr.as_ptr().cast::<c_void>())
+            .collect::<Vec<_>>();
+        // SAFETY: The buffers are aligned, as large as the kernel's parameters, and distinct.
+        unsafe { (self.call)(args.as_ptr()) };
+    }
+
+    /// Benchmarks the kernel in-process.
+    ///
+    /// Measured by calling the kernel `steps` times, after one warm-up call, on zeroed buffers
+    /// and returning the total runtime.
+    pub fn measure_time(&self, steps: u32) -> Duration {
+        let mut buffers = self.make_buffers();
+        self.call(&mut buffers);
+        let start = Instant::now();
+        for _ in 0..steps {
+            self.call(&mut buffers);
+        }
+        start.elapsed()
+    }
+
+    /// Estimate a good number of inner loop iterations for [LoadedKernel::bench].
+    pub fn estimate_optimal_iters(&self) -> u32 {
+        optimal_iters(self.measure_time(1))
+    }
+
+    /// Benchmark `repeat` times in-process.
+    pub fn bench(&self, inner_loop_iters: u32, repeat: Option<usize>) -> RobustTimingResult {
+        let inner_loop_runtimes = sample_runtimes(inner_loop_iters, repeat, |steps| {
+            Ok::<_, Infallible>(self.measure_time(steps))
+        })
+        .unwrap_or_else(|never| match never {});
+        RobustTimingResult {
+            inner_loop_runtimes,
+            inner_loop_iterations: inner_loop_iters,
+            artifact: None,
+        }
+    }
+}
+
+impl AlignedBuffer {
+    fn zeroed(size: usize) -> Self {
+        let layout = Layout::from_size_align(size.max(1), BUFFER_ALIGN).unwrap();
+        // SAFETY: `layout` has a non-zero size.
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let Some(ptr) = NonNull::new(ptr) else {
+            alloc::handle_alloc_error(layout);
+        };
+        AlignedBuffer { ptr, layout }
+    }
+
+    #[cfg(feature = "verification")]
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        // SAFETY: The buffer is initialized and `layout.size()` bytes long.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+
+    #[cfg(feature = "verification")]
+    pub(crate) fn as_bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: The buffer is initialized and `layout.size()` bytes long.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` was allocated with `layout`.
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+/// Returns `true` if kernels for `target` can be loaded into the current process.
+pub fn host_can_load(target: TargetId) -> bool {
+    match target {
+        TargetId::X86 => cfg!(target_arch = "x86_64"),
+        TargetId::Arm => cfg!(target_arch = "aarch64"),
+    }
+}
@@ -2,37 +2,48 @@ pub mod c_utils;
 mod clang;
 mod cpu;
 mod header;
+mod loaded;
 mod namegen;
+mod rust;
+pub mod tensor_file;
 
-use crate::codegen::clang::clang_path;
+use crate::codegen::clang::{ar_path, clang_path, gcc_path};
 use crate::codegen::cpu::CpuCodeGenerator;
 use crate::color::do_color;
 use crate::common::Dtype;
 use crate::imp::Impl;
 use crate::imp::ImplNode;
 use crate::pprint::ImplPrintStyle;
+use crate::spec::Spec;
 use crate::target::CpuTarget;
 use crate::target::{Target, TargetId};
-use crate::utils::ToWriteFmt;
+use crate::utils::{indent, ToWriteFmt};
 use crate::views::Tensor;
 
+use itertools::Itertools;
 use log::{debug, info};
 use std::cmp::max;
+use std::collections::HashSet;
 use std::fmt;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{self, Command, Output};
 use std::rc::Rc;
 use std::time::Duration;
-use tempfile::tempdir;
+use tempfile::{tempdir, TempDir};
 
 pub use self::cpu::CpuCodeGenThreadStyle;
+pub use self::loaded::{host_can_load, LoadedKernel};
+pub use self::rust::{RustCodeGen, RustCodeGenerator};
 
-const CLI_FLAGS: [&str; 4] = ["-std=gnu99", "-O3", "-rtlib=compiler-rt", "-o"];
+const CLANG_CLI_FLAGS: [&str; 2] = ["-std=gnu99", "-rtlib=compiler-rt"];
+const GCC_CLI_FLAGS: [&str; 1] = ["-std=gnu99"];
+const LIBRARY_CLI_FLAGS: [&str; 2] = ["-std=gnu99", "-fPIC"];
 
-// TODO: Avoid -fopenmp if we're not using an OpenMP pool.
-const X86_CLI_VEC_FLAGS: [&str; 2] = ["-fopenmp", "-mavx2"];
-const ARM_CLI_VEC_FLAGS: [&str; 1] = ["-fopenmp"];
+const X86_CLI_VEC_FLAGS: [&str; 1] = ["-mavx2"];
+const ARM_CLI_VEC_FLAGS: [&str; 0] = [];
+const OPENMP_CLI_FLAGS: [&str; 1] = ["-fopenmp"];
+const PTHREADS_CLI_FLAGS: [&str; 1] = ["-pthread"];
 
 const MIN_SAMPLES: u32 = 3;
 const MIN_TRIAL_TIME_SECS: f32 = 2.5;
@@ -48,6 +59,170 @@ pub enum BuildError {
         status: process::ExitStatus,
         stderr: String,
     },
+    #[error("Archiver exited with status code: {status}")]
+    ArchiverFailed {
+        status: process::ExitStatus,
+        stderr: String,
+    },
+    #[error("Couldn't emit code for the Impl")]
+    EmitFailed,
+    #[error("Can't emit code: {0}")]
+    Unsupported(String),
+    #[error("Not a valid C function name: {0:?}")]
+    InvalidSymbol(String),
+    #[error("Couldn't load built library: {0}")]
+    LoadFailed(#[from] libloading::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum EmitError {
+    #[error("Couldn't write emitted code")]
+    Fmt(#[from] fmt::Error),
+    #[error("Can't emit code: {0}")]
+    Unsupported(String),
+}
+
+impl From<EmitError> for BuildError {
+    fn from(err: EmitError) -> Self {
+        match err {
+            EmitError::Fmt(_) => BuildError::EmitFailed,
+            EmitError::Unsupported(reason) => BuildError::Unsupported(reason),
+        }
+    }
+}
+
+/// A C compiler with which [CodeGen] can build emitted code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Compiler {
+    /// Clang, found at `$CLANG`.
+    Clang,
+    /// GCC, found at `$GCC`.
+    Gcc,
+}
+
+impl Compiler {
+    pub const ALL: [Compiler; 2] = [Compiler::Clang, Compiler::Gcc];
+
+    /// Returns the compiler used by [CodeGen::build]: Clang if `$CLANG` is set, otherwise GCC if
+    /// `$GCC` is set, and otherwise Clang.
+    pub fn from_env() -> Self {
+        if clang_path().is_none() && gcc_path().is_some() {
+            Compiler::Gcc
+        } else {
+            Compiler::Clang
+        }
+    }
+
+    /// Returns the path to the compiler's executable, or `None` if it isn't configured.
+    pub fn path(self) -> Option<String> {
+        match self {
+            Compiler::Clang => clang_path(),
+            Compiler::Gcc => gcc_path(),
+        }
+    }
+
+    fn cli_flags(self) -> &'static [&'static str] {
+        match self {
+            Compiler::Clang => &CLANG_CLI_FLAGS,
+            Compiler::Gcc => &GCC_CLI_FLAGS,
+        }
+    }
+
+    fn color_flag(self) -> &'static str {
+        match self {
+            Compiler::Clang => "-fcolor-diagnostics",
+            Compiler::Gcc => "-fdiagnostics-color=always",
+        }
+    }
+}
+
+/// Options controlling how emitted C is compiled.
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    pub compiler: Compiler,
+    /// How parallel loops are run. [CpuCodeGenThreadStyle::Highway] can't be built this way.
+    pub thread_style: CpuCodeGenThreadStyle,
+    /// The optimization level, passed as `-O{opt_level}` (e.g., `"3"` or `"s"`).
+    pub opt_level: String,
+    /// The target architecture, passed as `-march`, if any.
+    pub march: Option<String>,
+    /// Flags passed to the compiler after all others.
+    pub extra_flags: Vec<String>,
+    /// The directory to which to write the source and binary. If `None`, a fresh temporary
+    /// directory is used.
+    pub output_dir: Option<PathBuf>,
+    /// Keep the emitted source (and any temporary directory) after building.
+    pub keep_sources: bool,
+}
+
+impl BuildOptions {
+    fn compiler_flags(&self) -> Vec<String> {
+        let mut flags = vec![format!("-O{}", self.opt_level)];
+        if let Some(march) = &self.march {
+            flags.push(format!("-march={march}"));
+        }
+        flags.extend(self.extra_flags.iter().cloned());
+        flags
+    }
+
+    /// Returns the directory in which to build, along with the temporary directory to delete once
+    /// the build's outputs are no longer needed, if any.
+    fn build_dir(&self) -> io::Result<(PathBuf, Option<TempDir>)> {
+        match &self.output_dir {
+            Some(output_dir) => {
+                std::fs::create_dir_all(output_dir)?;
+                Ok((output_dir.clone(), None))
+            }
+            None if self.keep_sources => Ok((tempdir()?.into_path(), None)),
+            None => {
+                let scratch_dir = tempdir()?;
+                Ok((scratch_dir.path().to_path_buf(), Some(scratch_dir)))
+            }
+        }
+    }
+
+    /// Deletes `source_path` unless sources are to be kept, returning the path if kept.
+    fn finish_source(&self, source_path: PathBuf) -> io::Result<Option<PathBuf>> {
+        if self.keep_sources {
+            Ok(Some(source_path))
+        } else {
+            std::fs::remove_file(&source_path)?;
+            Ok(None)
+        }
+    }
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        BuildOptions {
+            compiler: Compiler::from_env(),
+            thread_style: CpuCodeGenThreadStyle::default(),
+            opt_level: String::from("3"),
+            march: None,
+            extra_flags: vec![],
+            output_dir: None,
+            keep_sources: false,
+        }
+    }
+}
+
+/// Returns the flags needed to compile and link code emitted with `thread_style`.
+fn thread_style_cli_flags(thread_style: CpuCodeGenThreadStyle) -> &'static [&'static str] {
+    match thread_style {
+        CpuCodeGenThreadStyle::OpenMP | CpuCodeGenThreadStyle::Highway => &OPENMP_CLI_FLAGS,
+        CpuCodeGenThreadStyle::Pthreads => &PTHREADS_CLI_FLAGS,
+    }
+}
+
+/// The kind of library produced by [CodeGen::build_library].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum LibraryKind {
+    /// A static archive (`.a`).
+    Static,
+    /// A shared object (`.so`).
+    Shared,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -63,10 +238,6 @@ pub enum RunError {
 }
 
 pub trait CodeGen<Tgt: Target> {
-    fn compiler_path() -> Option<String> {
-        clang_path()
-    }
-
     fn cli_vec_flags() -> &'static [&'static str] {
         match Tgt::target_id() {
             TargetId::X86 => &X86_CLI_VEC_FLAGS,
@@ -78,7 +249,7 @@ pub trait CodeGen<Tgt: Target> {
         benchmark: bool,
         include_impl: Option<ImplPrintStyle>,
         out: &mut W,
-    ) -> fmt::Result {
+    ) -> Result<(), EmitError> {
         self.emit_ext(benchmark, include_impl, CpuCodeGenThreadStyle::OpenMP, out)
     }
 
@@ -88,46 +259,84 @@ pub trait CodeGen<Tgt: Target> {
         include_impl: Option<ImplPrintStyle>,
         thread_style: CpuCodeGenThreadStyle, // TODO: CPU-specific type shouldn't be here
         out: &mut W,
-    ) -> fmt::Result;
+    ) -> Result<(), EmitError>;
+
+    /// Build with the default [BuildOptions].
+    fn build(&self, benchmark: bool) -> Result<BuiltArtifact, BuildError> {
+        self.build_with_options(benchmark, &BuildOptions::default())
+    }
+
+    fn build_with_options(
+        &self,
+        benchmark: bool,
+        options: &BuildOptions,
+    ) -> Result<BuiltArtifact, BuildError>;
+
+    /// Emit a C source file defining the kernel as a function named `symbol`.
+    ///
+    /// Unlike [CodeGen::emit], the source has no `main`, so it can be compiled and linked into
+    /// another program. The kernel is declared by [CodeGen::emit_library_header].
+    fn emit_library<W: fmt::Write>(
+        &self,
+        symbol: &str,
+        include_impl: Option<ImplPrintStyle>,
+        out: &mut W,
+    ) -> Result<(), EmitError>;
+
+    /// Emit a C header with the prototype of the kernel emitted by [CodeGen::emit_library].
+    fn emit_library_header<W: fmt::Write>(&self, symbol: &str, out: &mut W) -> fmt::Result;
 
-    fn build(&self, benchmark: bool) -> Result<BuiltArtifact, BuildError>;
+    /// Write `{symbol}.h` and `{symbol}.c` to `output_dir` and compile them into a static
+    /// (`lib{symbol}.a`) or shared (`lib{symbol}.so`) library alongside.
+    ///
+    /// The sources are always kept, so `options.output_dir` and `options.keep_sources` are
+    /// ignored. Parallel loops always use OpenMP, so `options.thread_style` is ignored too.
+    fn build_library(
+        &self,
+        symbol: &str,
+        kind: LibraryKind,
+        output_dir: &Path,
+        options: &BuildOptions,
+    ) -> Result<BuiltLibrary, BuildError>;
+
+    /// Build the kernel as a shared library and load it into this process.
+    ///
+    /// `options.output_dir` and `options.keep_sources` are respected as in
+    /// [CodeGen::build_with_options]. The kernel can only be loaded if [host_can_load] the
+    /// target. Libraries are loaded by path, so a kernel previously built into the same
+    /// `output_dir` should be dropped before building another.
+    fn build_loaded(&self, options: &BuildOptions) -> Result<LoadedKernel, BuildError>;
 
     /// Estimate a good number of inner loop iterations.
-    fn estimate_optimal_iters(&self) -> Result<u32, RunError> {
-        // Collect a single rough sample.
-        let time_check_artifact = self.build(true)?;
-        let rough_secs = time_check_artifact.measure_time(1)?;
-
-        // Choose a good number of iterations for benchmarks' inner loop.
-        Ok(max(
-            MIN_SAMPLES,
-            (MIN_TRIAL_TIME_SECS / rough_secs.as_secs_f32()).ceil() as u32,
-        ))
+    ///
+    /// To benchmark too without building the kernel twice, build it with
+    /// [CodeGen::build_loaded] or [CodeGen::build_with_options] and use its
+    /// `estimate_optimal_iters` and `bench` methods.
+    fn estimate_optimal_iters(&self, options: &BuildOptions) -> Result<u32, RunError> {
+        if host_can_load(Tgt::target_id()) {
+            Ok(self.build_loaded(options)?.estimate_optimal_iters())
+        } else {
+            self.build_with_options(true, options)?
+                .estimate_optimal_iters()
+        }
     }
 
     /// Benchmark `repeat` times.
+    ///
+    /// The kernel is called in-process if [host_can_load] the target. Otherwise, a benchmarking
+    /// binary is built and run.
     fn bench(
         &self,
         inner_loop_iters: u32,
         repeat: Option<usize>,
+        options: &BuildOptions,
     ) -> Result<RobustTimingResult, RunError> {
-        let repeat = repeat.unwrap_or(10); // default: 10
-
-        // Run main benchmark loop.
-        info!("Goal iterations: {inner_loop_iters}");
-        let artifact = self.build(true)?;
-        let mut inner_loop_runtimes = Vec::with_capacity(repeat);
-        for _ in 0..repeat {
-            let time = artifact.measure_time(inner_loop_iters)?;
-            debug!("Sample runtime result {}s", time.as_secs_f32());
-            inner_loop_runtimes.push(time);
+        if host_can_load(Tgt::target_id()) {
+            Ok(self.build_loaded(options)?.bench(inner_loop_iters, repeat))
+        } else {
+            self.build_with_options(true, options)?
+                .bench(inner_loop_iters, repeat)
         }
-
-        Ok(RobustTimingResult {
-            inner_loop_runtimes,
-            inner_loop_iterations: inner_loop_iters,
-            artifact,
-        })
     }
 }
 
@@ -141,7 +350,7 @@ where
         include_impl: Option<ImplPrintStyle>,
         thread_style: CpuCodeGenThreadStyle,
         out: &mut W,
-    ) -> fmt::Result {
+    ) -> Result<(), EmitError> {
         let top_arg_tensors = self
             .parameters()
             .map(|parameter| Rc::new(Tensor::new(parameter.clone())))
@@ -159,78 +368,313 @@ where
         } else {
             generator.emit_load_inputs(&top_arg_tensors, out)?;
             out.write_char('\n')?;
+            generator.emit_write_output(&top_arg_tensors, out)?;
+            out.write_char('\n')?;
             generator.emit_standard_main(&top_arg_tensors, out)?;
         }
         Ok(())
     }
 
-    fn build(&self, benchmark: bool) -> Result<BuiltArtifact, BuildError> {
-        let dirname = tempdir()?.into_path();
+    fn build_with_options(
+        &self,
+        benchmark: bool,
+        options: &BuildOptions,
+    ) -> Result<BuiltArtifact, BuildError> {
+        let (dirname, scratch_dir) = options.build_dir()?;
         let source_path = dirname.join("main.c");
         let binary_path = dirname.join("a.out");
 
         let source_file = std::fs::File::create(&source_path)?;
         // TODO: The following may not prop. IO errors hidden by ToWriteFmt.
-        self.emit(benchmark, None, &mut ToWriteFmt(source_file))
-            .expect("codegen should not fail");
+        self.emit_ext(
+            benchmark,
+            None,
+            options.thread_style,
+            &mut ToWriteFmt(source_file),
+        )?;
 
-        let Some(compiler_path) = Self::compiler_path() else {
-            return Err(BuildError::MissingCompiler);
-        };
-        let mut clang_cmd = Command::new(compiler_path);
-        if do_color() {
-            clang_cmd.arg("-fcolor-diagnostics");
-        }
-        let clang_proc = clang_cmd
+        let clang_proc = compiler_command(options.compiler)?
             .args(Self::cli_vec_flags())
-            .args(CLI_FLAGS)
+            .args(thread_style_cli_flags(options.thread_style))
+            .args(options.compiler.cli_flags())
+            .args(options.compiler_flags())
+            .arg("-o")
             .arg(binary_path.to_string_lossy().as_ref())
             .arg(source_path.to_string_lossy().as_ref())
             .output()?;
-
-        if !clang_proc.status.success() {
-            return Err(BuildError::CompilerFailed {
-                status: clang_proc.status,
-                stderr: String::from_utf8_lossy(&clang_proc.stderr).into(),
-            });
-        } else {
-            // We still want to see warnings.
-            // TODO: Capture this for the caller.
-            io::stderr().write_all(&clang_proc.stderr)?;
-        }
+        let compiler_warnings = check_compiler_output(&clang_proc)?;
 
         Ok(BuiltArtifact::new(
             binary_path,
-            source_path,
-            dirname,
+            options.finish_source(source_path)?,
+            scratch_dir,
             self.parameters().map(|p| p.dtype()).collect(),
+            compiler_warnings,
         ))
     }
+
+    fn build_loaded(&self, options: &BuildOptions) -> Result<LoadedKernel, BuildError> {
+        let (dirname, scratch_dir) = options.build_dir()?;
+        let source_path = dirname.join("kernel.c");
+        let library_path = dirname.join("libkernel.so");
+
+        let mut source = String::new();
+        emit_loadable(self, options.thread_style, &mut source)?;
+        std::fs::write(&source_path, source)?;
+
+        let clang_proc = compiler_command(options.compiler)?
+            .args(Self::cli_vec_flags())
+            .args(thread_style_cli_flags(options.thread_style))
+            .args(LIBRARY_CLI_FLAGS)
+            .args(options.compiler_flags())
+            .arg("-shared")
+            .arg("-o")
+            .arg(&library_path)
+            .arg(&source_path)
+            .output()?;
+        let compiler_warnings = check_compiler_output(&clang_proc)?;
+        options.finish_source(source_path)?;
+
+        // SAFETY: The library was just built from `self`.
+        Ok(unsafe {
+            LoadedKernel::load(
+                library_path,
+                scratch_dir,
+                self.parameters().map(|p| p.dtype()).collect(),
+                self.parameters()
+                    .map(|p| usize::try_from(p.volume().get()).unwrap())
+                    .collect(),
+                self.parameters()
+                    .map(|p| p.layout().is_row_major())
+                    .collect(),
+                compiler_warnings,
+            )
+        }?)
+    }
+
+    fn emit_library<W: fmt::Write>(
+        &self,
+        symbol: &str,
+        include_impl: Option<ImplPrintStyle>,
+        out: &mut W,
+    ) -> Result<(), EmitError> {
+        let top_arg_tensors = self
+            .parameters()
+            .map(|parameter| Rc::new(Tensor::new(parameter.clone())))
+            .collect::<Vec<_>>();
+        let mut generator = CpuCodeGenerator::<Tgt>::new();
+        generator.kernel_name = symbol.to_string();
+        if let Some(impl_style) = include_impl {
+            generator.emit_impl_comment(self, impl_style, out)?;
+            writeln!(out)?;
+        }
+        generator.emit_kernel(self, &top_arg_tensors, false, out)
+    }
+
+    fn emit_library_header<W: fmt::Write>(&self, symbol: &str, out: &mut W) -> fmt::Result {
+        let mut generator = CpuCodeGenerator::<Tgt>::new();
+        generator.kernel_name = symbol.to_string();
+        generator.emit_kernel_prototype(self, out)
+    }
+
+    fn build_library(
+        &self,
+        symbol: &str,
+        kind: LibraryKind,
+        output_dir: &Path,
+        options: &BuildOptions,
+    ) -> Result<BuiltLibrary, BuildError> {
+        if !is_valid_library_symbol(symbol) {
+            return Err(BuildError::InvalidSymbol(symbol.to_string()));
+        }
+
+        let header_path = output_dir.join(format!("{symbol}.h"));
+        let source_path = output_dir.join(format!("{symbol}.c"));
+        let mut header = String::new();
+        self.emit_library_header(symbol, &mut header)
+            .map_err(|_| BuildError::EmitFailed)?;
+        std::fs::write(&header_path, header)?;
+        let mut source = String::new();
+        self.emit_library(symbol, None, &mut source)?;
+        std::fs::write(&source_path, source)?;
+
+        let (library_path, compiler_warnings) = match kind {
+            LibraryKind::Static => {
+                // Compile to an object file in a scratch directory, then archive it.
+                let scratch_dir = tempdir()?;
+                let object_path = scratch_dir.path().join(format!("{symbol}.o"));
+                let clang_proc = compiler_command(options.compiler)?
+                    .args(Self::cli_vec_flags())
+                    .args(OPENMP_CLI_FLAGS)
+                    .args(LIBRARY_CLI_FLAGS)
+                    .args(options.compiler_flags())
+                    .arg("-c")
+                    .arg("-o")
+                    .arg(&object_path)
+                    .arg(&source_path)
+                    .output()?;
+                let compiler_warnings = check_compiler_output(&clang_proc)?;
+
+                let library_path = output_dir.join(format!("lib{symbol}.a"));
+                if library_path.exists() {
+                    // `ar` would otherwise add to, rather than replace, an existing archive.
+                    std::fs::remove_file(&library_path)?;
+                }
+                let ar_proc = Command::new(ar_path())
+                    .arg("rcs")
+                    .arg(&library_path)
+                    .arg(&object_path)
+                    .output()?;
+                if !ar_proc.status.success() {
+                    return Err(BuildError::ArchiverFailed {
+                        status: ar_proc.status,
+                        stderr: String::from_utf8_lossy(&ar_proc.stderr).into(),
+                    });
+                }
+                (library_path, compiler_warnings)
+            }
+            LibraryKind::Shared => {
+                let library_path = output_dir.join(format!("lib{symbol}.so"));
+                let clang_proc = compiler_command(options.compiler)?
+                    .args(Self::cli_vec_flags())
+                    .args(OPENMP_CLI_FLAGS)
+                    .args(LIBRARY_CLI_FLAGS)
+                    .args(options.compiler_flags())
+                    .arg("-shared")
+                    .arg("-o")
+                    .arg(&library_path)
+                    .arg(&source_path)
+                    .output()?;
+                let compiler_warnings = check_compiler_output(&clang_proc)?;
+                (library_path, compiler_warnings)
+            }
+        };
+
+        Ok(BuiltLibrary {
+            library_path,
+            header_path,
+            source_path,
+            compiler_warnings,
+        })
+    }
+}
+
+/// Emit the kernel, along with a function named [loaded::CALL_SYMBOL] which calls it with its
+/// arguments given as an array of pointers, for [CodeGen::build_loaded].
+fn emit_loadable<Tgt: CpuTarget, W: fmt::Write>(
+    imp: &ImplNode<Tgt>,
+    thread_style: CpuCodeGenThreadStyle,
+    out: &mut W,
+) -> Result<(), EmitError> {
+    let top_arg_tensors = imp
+        .parameters()
+        .map(|parameter| Rc::new(Tensor::new(parameter.clone())))
+        .collect::<Vec<_>>();
+    let mut generator = CpuCodeGenerator::<Tgt>::new();
+    generator.kernel_name = String::from("morello_kernel");
+    generator.thread_style = thread_style;
+    generator.emit_kernel(imp, &top_arg_tensors, false, out)?;
+    writeln!(out)?;
+    writeln!(out, "void {}(void *const *args) {{", loaded::CALL_SYMBOL)?;
+    let args = (0..imp.parameter_count())
+        .map(|i| format!("args[{i}]"))
+        .join(", ");
+    writeln!(out, "{}{}({args});", indent(1), generator.kernel_name)?;
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+/// Emit many Impls, and a function dispatching to them by [Spec], into a single C file.
+///
+/// The Specs should be distinct. See [CpuCodeGenerator::emit_kernels] for the form of the emitted
+/// kernels and dispatch function. Returns the kernel names in the order of `kernels`.
+pub fn emit_kernels<Tgt: CpuTarget, W: fmt::Write>(
+    kernels: &[(Spec<Tgt>, ImplNode<Tgt>)],
+    dispatch_name: &str,
+    include_impl: Option<ImplPrintStyle>,
+    out: &mut W,
+) -> Result<Vec<String>, EmitError> {
+    debug_assert_eq!(
+        kernels
+            .iter()
+            .map(|(spec, _)| spec)
+            .collect::<HashSet<_>>()
+            .len(),
+        kernels.len(),
+        "Specs should be distinct"
+    );
+    let top_arg_tensors = kernels
+        .iter()
+        .map(|(_, imp)| {
+            imp.parameters()
+                .map(|parameter| Rc::new(Tensor::new(parameter.clone())))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut generator = CpuCodeGenerator::<Tgt>::new();
+    if let Some(impl_style) = include_impl {
+        for (spec, imp) in kernels {
+            writeln!(out, "// {spec}")?;
+            generator.emit_impl_comment(imp, impl_style, out)?;
+            writeln!(out)?;
+        }
+    }
+    generator.emit_kernels(kernels, &top_arg_tensors, dispatch_name, out)
+}
+
+/// The files written by [CodeGen::build_library].
+pub struct BuiltLibrary {
+    pub library_path: PathBuf,
+    pub header_path: PathBuf,
+    pub source_path: PathBuf,
+    /// Anything the compiler wrote to stderr, such as warnings.
+    pub compiler_warnings: String,
 }
 
 pub struct BuiltArtifact {
     pub(crate) binary_path: PathBuf,
+    source_path: Option<PathBuf>,
     parameter_dtypes: Vec<Dtype>,
+    compiler_warnings: String,
+    // Deleted, along with the binary, when the artifact is dropped.
+    _scratch_dir: Option<TempDir>,
 }
 
 impl BuiltArtifact {
     pub(crate) fn new(
         binary_path: PathBuf,
-        _source_path: PathBuf,
-        _whole_dir: PathBuf,
+        source_path: Option<PathBuf>,
+        scratch_dir: Option<TempDir>,
         parameter_dtypes: Vec<Dtype>,
+        compiler_warnings: String,
     ) -> Self {
-        // While we accept `source_path` and `whole_dir`, we don't do anything with them.
         Self {
             binary_path,
+            source_path,
             parameter_dtypes,
+            compiler_warnings,
+            _scratch_dir: scratch_dir,
         }
     }
 
+    pub fn binary_path(&self) -> &Path {
+        &self.binary_path
+    }
+
+    /// The emitted source, if it was kept (see [BuildOptions::keep_sources]).
+    pub fn source_path(&self) -> Option<&Path> {
+        self.source_path.as_deref()
+    }
+
     pub fn parameter_dtypes(&self) -> &[Dtype] {
         &self.parameter_dtypes
     }
 
+    /// Anything the compiler wrote to stderr, such as warnings.
+    pub fn compiler_warnings(&self) -> &str {
+        &self.compiler_warnings
+    }
+
     pub fn run(&self) -> Result<Output, RunError> {
         Ok(Command::new(&self.binary_path).output()?)
     }
@@ -254,12 +698,37 @@ impl BuiltArtifact {
         parse_benchmark_output(first_line)
             .map_err(|_| RunError::MalformedOutput(String::from_utf8_lossy(&output.stderr).into()))
     }
+
+    /// Estimate a good number of inner loop iterations for [BuiltArtifact::bench].
+    ///
+    /// The artifact must have been built for benchmarking.
+    pub fn estimate_optimal_iters(&self) -> Result<u32, RunError> {
+        Ok(optimal_iters(self.measure_time(1)?))
+    }
+
+    /// Benchmark `repeat` times.
+    ///
+    /// The artifact must have been built for benchmarking.
+    pub fn bench(
+        self,
+        inner_loop_iters: u32,
+        repeat: Option<usize>,
+    ) -> Result<RobustTimingResult, RunError> {
+        let inner_loop_runtimes =
+            sample_runtimes(inner_loop_iters, repeat, |steps| self.measure_time(steps))?;
+        Ok(RobustTimingResult {
+            inner_loop_runtimes,
+            inner_loop_iterations: inner_loop_iters,
+            artifact: Some(self),
+        })
+    }
 }
 
 pub struct RobustTimingResult {
     pub inner_loop_runtimes: Vec<Duration>,
     pub inner_loop_iterations: u32,
-    pub artifact: BuiltArtifact,
+    /// The benchmarking binary, if the kernel wasn't benchmarked in-process.
+    pub artifact: Option<BuiltArtifact>,
 }
 
 impl RobustTimingResult {
@@ -276,6 +745,68 @@ impl RobustTimingResult {
     }
 }
 
+/// Returns a number of inner loop iterations long enough to benchmark, given the runtime of one.
+fn optimal_iters(rough_runtime: Duration) -> u32 {
+    max(
+        MIN_SAMPLES,
+        (MIN_TRIAL_TIME_SECS / rough_runtime.as_secs_f32()).ceil() as u32,
+    )
+}
+
+/// Times `inner_loop_iters` iterations with `measure`, `repeat` times (by default, 10).
+fn sample_runtimes<E>(
+    inner_loop_iters: u32,
+    repeat: Option<usize>,
+    mut measure: impl FnMut(u32) -> Result<Duration, E>,
+) -> Result<Vec<Duration>, E> {
+    let repeat = repeat.unwrap_or(10); // default: 10
+    info!("Goal iterations: {inner_loop_iters}");
+    (0..repeat)
+        .map(|_| {
+            let time = measure(inner_loop_iters)?;
+            debug!("Sample runtime result {}s", time.as_secs_f32());
+            Ok(time)
+        })
+        .collect()
+}
+
+fn compiler_command(compiler: Compiler) -> Result<Command, BuildError> {
+    let Some(compiler_path) = compiler.path() else {
+        return Err(BuildError::MissingCompiler);
+    };
+    let mut clang_cmd = Command::new(compiler_path);
+    if do_color() {
+        clang_cmd.arg(compiler.color_flag());
+    }
+    Ok(clang_cmd)
+}
+
+/// Returns the compiler's stderr, which holds any warnings, if it succeeded.
+fn check_compiler_output(clang_proc: &Output) -> Result<String, BuildError> {
+    let stderr = String::from_utf8_lossy(&clang_proc.stderr).into_owned();
+    if !clang_proc.status.success() {
+        return Err(BuildError::CompilerFailed {
+            status: clang_proc.status,
+            stderr,
+        });
+    }
+    Ok(stderr)
+}
+
+/// Returns `true` if `symbol` can name a kernel emitted by [CodeGen::emit_library].
+pub fn is_valid_library_symbol(symbol: &str) -> bool {
+    is_c_identifier(symbol) && symbol != "main"
+}
+
+fn is_c_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 fn parse_benchmark_output(output: &str) -> Result<Duration, ()> {
     let mut outs = output.split_whitespace();
     if outs.next() != Some("cpu:") {
@@ -306,6 +837,591 @@ fn parse_benchmark_output(output: &str) -> Result<Duration, ()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::layout::row_major;
+    use crate::lspec;
+    use crate::scheduling_sugar::{SchedulingSugar, Subschedule};
+    use crate::spec::{LogicalSpec, PrimitiveBasics, PrimitiveSpecType, Spec};
+    use crate::target::{
+        CpuKernel,
+        CpuMemoryLevel::{GL, RF},
+        X86Target,
+    };
+    use crate::tensorspec::TensorSpecAux;
+    #[cfg(feature = "verification")]
+    use {
+        crate::layout::col_major,
+        crate::target::CpuMemoryLevel::VRF,
+        crate::verification::{
+            self, CheckOptions, DynArray, InputStrategy, TensorFormat, BINARY_FORMAT_MIN_VALUES,
+        },
+        ndarray::{ArrayD, IxDyn},
+        nonzero::nonzero as nz,
+        proptest::prelude::*,
+    };
+
+    fn zero_spec(dtype: Dtype) -> Spec<X86Target> {
+        Spec::<X86Target>(
+            lspec!(Zero([2, 2], (dtype, GL, row_major(2)))),
+            X86Target::max_mem(),
+        )
+    }
+
+    fn zero_impl() -> ImplNode<X86Target> {
+        zero_impl_for(&zero_spec(Dtype::Uint32))
+    }
+
+    fn zero_impl_for(spec: &Spec<X86Target>) -> ImplNode<X86Target> {
+        spec.tile_out(&[1, 1], false)
+            .move_param(0, RF, row_major(2), None)
+            .subschedule(&[0], &|z| z.place(CpuKernel::MemsetZero))
+            .subschedule(&[1], &|m| m.place(CpuKernel::ValueAssign))
+    }
+
+    /// Returns the compilers configured in the environment.
+    ///
+    /// A test looping over none would pass without checking anything, so if there are none this
+    /// panics when `$CI` is set, and otherwise prints that the calling test was skipped.
+    fn available_compilers() -> Vec<Compiler> {
+        let compilers = Compiler::ALL
+            .into_iter()
+            .filter(|compiler| compiler.path().is_some())
+            .collect::<Vec<_>>();
+        if compilers.is_empty() {
+            let test_name = std::thread::current().name().unwrap_or("test").to_string();
+            if std::env::var_os("CI").is_some() {
+                panic!("{test_name} needs $CLANG or $GCC to be set in CI");
+            }
+            eprintln!("Skipping {test_name}: neither $CLANG nor $GCC is set");
+        }
+        compilers
+    }
+
+    /// Builds `imp` with each of the [available_compilers], taking all other options from
+    /// `options`, and calls `check` with each artifact.
+    #[cfg(feature = "verification")]
+    fn build_with_each_compiler<F>(imp: &ImplNode<X86Target>, options: &BuildOptions, mut check: F)
+    where
+        F: FnMut(Compiler, BuiltArtifact),
+    {
+        let has_bf16 = imp.parameters().any(|p| p.dtype() == Dtype::Bfloat16);
+        for compiler in available_compilers() {
+            if compiler == Compiler::Gcc && has_bf16 {
+                // GCC supports `__bf16` on x86 only from version 13.
+                continue;
+            }
+            let options = BuildOptions {
+                compiler,
+                ..options.clone()
+            };
+            let artifact = imp
+                .build_with_options(false, &options)
+                .unwrap_or_else(|e| panic!("{compiler:?}: {e}"));
+            check(compiler, artifact);
+        }
+    }
+
+    #[cfg(feature = "verification")]
+    fn scalar_matmul_impl(spec: &Spec<X86Target>) -> ImplNode<X86Target> {
+        spec.to_accum()
+            .subschedule(&[0], &zero_impl_for)
+            .subschedule(&[1], &|s| {
+                s.tile_out(&[1, 1], false)
+                    .split(1)
+                    .move_param(0, RF, row_major(2), None)
+                    .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                    .subschedule(&[1], &|s| {
+                        s.move_param(1, RF, row_major(2), None)
+                            .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                            .subschedule(&[1], &|s| {
+                                s.move_param(2, RF, row_major(2), None)
+                                    .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                                    .subschedule(&[1], &|s| s.place(CpuKernel::MultAdd))
+                                    .subschedule(&[2], &|s| s.place(CpuKernel::ValueAssign))
+                            })
+                    })
+            })
+    }
+
+    #[cfg(feature = "verification")]
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(8))]
+
+        #[test]
+        fn test_zero_is_correct_with_each_compiler(
+            dtype in any::<Dtype>(), rows in 2..=4u32, cols in 2..=4u32
+        ) {
+            let spec = Spec::<X86Target>(
+                lspec!(Zero([rows, cols], (dtype, GL, row_major(2)))),
+                X86Target::max_mem(),
+            );
+            let imp = zero_impl_for(&spec);
+            build_with_each_compiler(&imp, &BuildOptions::default(), |compiler, artifact| {
+                if let Err(failure) = artifact.check_correctness(&spec) {
+                    panic!("{compiler:?}: {failure}");
+                }
+            });
+        }
+
+        #[test]
+        fn test_matmul_is_correct_with_each_compiler(
+            m in 2..=3u32, k in 2..=3u32, n in 2..=3u32
+        ) {
+            let spec = Spec::<X86Target>(
+                lspec!(Matmul(
+                    [m, k, n],
+                    (u32, GL, row_major(2)),
+                    (u32, GL, row_major(2)),
+                    (u32, GL, row_major(2))
+                )),
+                X86Target::max_mem(),
+            );
+            let imp = scalar_matmul_impl(&spec);
+            build_with_each_compiler(&imp, &BuildOptions::default(), |compiler, artifact| {
+                if let Err(failure) = artifact.check_correctness(&spec) {
+                    panic!("{compiler:?}: {failure}");
+                }
+            });
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_vector_zero_is_correct_with_each_compiler() {
+        let spec = Spec::<X86Target>(
+            lspec!(Zero([2, 8], (u32, GL, row_major(2)))),
+            X86Target::max_mem(),
+        );
+        let imp = spec
+            .tile_out(&[1, 8], false)
+            .move_param(0, VRF, row_major(2), Some(nz!(8u32)))
+            .subschedule(&[0], &|z| z.place(CpuKernel::VectorZero))
+            .subschedule(&[1], &|m| m.place(CpuKernel::VectorAssign));
+        build_with_each_compiler(&imp, &BuildOptions::default(), |compiler, artifact| {
+            if let Err(failure) = artifact.check_correctness(&spec) {
+                panic!("{compiler:?}: {failure}");
+            }
+        });
+    }
+
+    #[test]
+    fn test_pthreads_parallel_loop_is_outlined() {
+        let spec = Spec::<X86Target>(
+            lspec!(Zero([4, 2], (u32, GL, row_major(2)))),
+            X86Target::max_mem(),
+        );
+        let imp = spec
+            .tile_out(&[2, 2], true)
+            .subschedule(&[0], &zero_impl_for);
+        let mut source = String::new();
+        imp.emit_ext(false, None, CpuCodeGenThreadStyle::Pthreads, &mut source)
+            .unwrap();
+        assert!(source.contains("#include <pthread.h>"), "{source}");
+        assert!(source.contains("static void *kernel_worker_"), "{source}");
+        assert!(source.contains("pthread_t threads[2];"), "{source}");
+        assert!(source.contains("if (started[thread_idx])"), "{source}");
+        assert!(!source.contains("#pragma omp"), "{source}");
+    }
+
+    #[test]
+    fn test_pthreads_rejects_parallel_loops_capturing_registers() {
+        let spec = Spec::<X86Target>(
+            lspec!(Matmul(
+                [2, 1, 1],
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2))
+            )),
+            X86Target::max_mem(),
+        );
+        // The right-hand side is loaded into a register outside of the parallel loop.
+        let imp = spec
+            .to_accum()
+            .subschedule(&[0], &zero_impl_for)
+            .subschedule(&[1], &|s| {
+                s.move_param(1, RF, row_major(2), None)
+                    .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                    .subschedule(&[1], &|s| {
+                        s.tile_out(&[1, 1], true)
+                            .move_param(0, RF, row_major(2), None)
+                            .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                            .subschedule(&[1], &|s| {
+                                s.move_param(2, RF, row_major(2), None)
+                                    .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                                    .subschedule(&[1], &|s| s.place(CpuKernel::MultAdd))
+                                    .subschedule(&[2], &|s| s.place(CpuKernel::ValueAssign))
+                            })
+                    })
+            });
+        let mut source = String::new();
+        let err = imp
+            .emit_ext(false, None, CpuCodeGenThreadStyle::Pthreads, &mut source)
+            .unwrap_err();
+        assert!(
+            matches!(&err, EmitError::Unsupported(reason) if reason.contains("scalar register")),
+            "{err}"
+        );
+
+        let options = BuildOptions {
+            thread_style: CpuCodeGenThreadStyle::Pthreads,
+            ..Default::default()
+        };
+        assert!(matches!(
+            imp.build_with_options(false, &options),
+            Err(BuildError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_pthreads_zero_is_correct_with_each_compiler() {
+        // A parallel loop inside a serial loop, so the worker must capture a loop iterator.
+        let spec = Spec::<X86Target>(
+            lspec!(Zero([8, 6], (u32, GL, row_major(2)))),
+            X86Target::max_mem(),
+        );
+        let imp = spec.tile_out(&[4, 6], false).subschedule(&[0], &|s| {
+            s.tile_out(&[1, 3], true).subschedule(&[0], &zero_impl_for)
+        });
+        let options = BuildOptions {
+            thread_style: CpuCodeGenThreadStyle::Pthreads,
+            ..Default::default()
+        };
+        build_with_each_compiler(&imp, &options, |compiler, artifact| {
+            if let Err(failure) = artifact.check_correctness(&spec) {
+                panic!("{compiler:?}: {failure}");
+            }
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_matmul_is_correct_with_adversarial_inputs() {
+        let spec = Spec::<X86Target>(
+            lspec!(Matmul(
+                [3, 4, 2],
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2))
+            )),
+            X86Target::max_mem(),
+        );
+        let options = CheckOptions {
+            strategy: InputStrategy::Adversarial,
+            trials: 8,
+            seed: 0,
+        };
+        let imp = scalar_matmul_impl(&spec);
+        build_with_each_compiler(&imp, &BuildOptions::default(), |compiler, artifact| {
+            if let Err(failure) = artifact.check_correctness_with_options(&spec, &options) {
+                panic!("{compiler:?}: {failure}");
+            }
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_pthreads_matmul_is_correct_with_each_compiler() {
+        let spec = Spec::<X86Target>(
+            lspec!(Matmul(
+                [5, 3, 4],
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2))
+            )),
+            X86Target::max_mem(),
+        );
+        let imp = spec
+            .tile_out(&[1, 4], true)
+            .subschedule(&[0], &scalar_matmul_impl);
+        let options = BuildOptions {
+            thread_style: CpuCodeGenThreadStyle::Pthreads,
+            ..Default::default()
+        };
+        build_with_each_compiler(&imp, &options, |compiler, artifact| {
+            if let Err(failure) = artifact.check_correctness(&spec) {
+                panic!("{compiler:?}: {failure}");
+            }
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_text_and_binary_formats_agree_with_each_compiler() {
+        let spec = Spec::<X86Target>(
+            lspec!(Matmul(
+                [2, 3, 2],
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2))
+            )),
+            X86Target::max_mem(),
+        );
+        let arguments = [[2, 3], [3, 2], [2, 2]]
+            .into_iter()
+            .map(|shape: [usize; 2]| {
+                let values = (1..=shape.iter().product::<usize>() as u32).collect();
+                DynArray::Uint32(ArrayD::from_shape_vec(IxDyn(&shape), values).unwrap())
+            })
+            .collect::<Vec<_>>();
+        let imp = scalar_matmul_impl(&spec);
+        build_with_each_compiler(&imp, &BuildOptions::default(), |compiler, artifact| {
+            let text = artifact
+                .run_with_input_data_as(&arguments, TensorFormat::Text)
+                .unwrap();
+            let binary = artifact
+                .run_with_input_data_as(&arguments, TensorFormat::Binary)
+                .unwrap();
+            assert!(text == binary, "{compiler:?}");
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_loaded_kernel_agrees_with_artifact_with_each_compiler() {
+        if !host_can_load(TargetId::X86) {
+            return;
+        }
+        let spec = Spec::<X86Target>(
+            lspec!(Matmul(
+                [2, 3, 2],
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2))
+            )),
+            X86Target::max_mem(),
+        );
+        let arguments = [[2, 3], [3, 2], [2, 2]]
+            .into_iter()
+            .map(|shape: [usize; 2]| {
+                let values = (1..=shape.iter().product::<usize>() as u32).collect();
+                DynArray::Uint32(ArrayD::from_shape_vec(IxDyn(&shape), values).unwrap())
+            })
+            .collect::<Vec<_>>();
+        let imp = scalar_matmul_impl(&spec);
+        build_with_each_compiler(&imp, &BuildOptions::default(), |compiler, artifact| {
+            let options = BuildOptions {
+                compiler,
+                ..Default::default()
+            };
+            let kernel = imp.build_loaded(&options).unwrap();
+            if let Err(failure) = kernel.check_correctness(&spec) {
+                panic!("{compiler:?}: {failure}");
+            }
+            assert!(
+                kernel.run_with_input_data(&arguments).unwrap()
+                    == artifact.run_with_input_data(&arguments).unwrap(),
+                "{compiler:?}"
+            );
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_loaded_kernel_rejects_col_major_parameter() {
+        if !host_can_load(TargetId::X86) {
+            return;
+        }
+        let spec = Spec::<X86Target>(
+            lspec!(Matmul(
+                [2, 3, 2],
+                (u32, GL, row_major(2)),
+                (u32, GL, col_major(2)),
+                (u32, GL, row_major(2))
+            )),
+            X86Target::max_mem(),
+        );
+        let arguments = [[2, 3], [3, 2], [2, 2]]
+            .into_iter()
+            .map(|shape: [usize; 2]| DynArray::Uint32(ArrayD::zeros(IxDyn(&shape))))
+            .collect::<Vec<_>>();
+        let imp = scalar_matmul_impl(&spec);
+        for compiler in available_compilers() {
+            let options = BuildOptions {
+                compiler,
+                ..Default::default()
+            };
+            let kernel = imp.build_loaded(&options).unwrap();
+            assert!(
+                matches!(
+                    kernel.run_with_input_data(&arguments),
+                    Err(verification::RunError::NonRowMajorParameter(1))
+                ),
+                "{compiler:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_large_zero_is_correct_in_binary_format() {
+        let spec = Spec::<X86Target>(
+            lspec!(Zero([64, 64], (f32, GL, row_major(2)))),
+            X86Target::max_mem(),
+        );
+        assert!(spec.0.parameters()[0].volume().get() as usize >= BINARY_FORMAT_MIN_VALUES);
+        let artifact = zero_impl_for(&spec).build(false).unwrap();
+        artifact.check_correctness(&spec).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_build_options_output_dir_keeps_sources_only_when_asked() {
+        let imp = zero_impl();
+        for keep_sources in [false, true] {
+            let output_dir = tempfile::tempdir().unwrap();
+            let options = BuildOptions {
+                output_dir: Some(output_dir.path().to_path_buf()),
+                keep_sources,
+                ..Default::default()
+            };
+            let artifact = imp.build_with_options(false, &options).unwrap();
+            assert!(artifact.binary_path().starts_with(output_dir.path()));
+            assert_eq!(artifact.source_path().is_some(), keep_sources);
+            assert_eq!(output_dir.path().join("main.c").exists(), keep_sources);
+            artifact
+                .check_correctness(&zero_spec(Dtype::Uint32))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_emit_library_has_named_kernel_and_no_main() {
+        let mut source = String::new();
+        zero_impl()
+            .emit_library("zero_2x2", None, &mut source)
+            .unwrap();
+        assert!(source.contains("void zero_2x2("), "{source}");
+        assert!(!source.contains("main("), "{source}");
+        assert!(!source.contains("void kernel("), "{source}");
+    }
+
+    #[test]
+    fn test_emit_library_header_declares_kernel() {
+        let mut header = String::new();
+        zero_impl()
+            .emit_library_header("zero_2x2", &mut header)
+            .unwrap();
+        assert!(header.contains("#ifndef MORELLO_ZERO_2X2_H"), "{header}");
+        assert!(
+            header.contains("void zero_2x2(\n  uint32_t *__restrict__ p0\n);"),
+            "{header}"
+        );
+    }
+
+    #[test]
+    fn test_built_library_links_into_c_program() {
+        const DRIVER: &str = r#"#include <stdio.h>
+#include "zero_2x2.h"
+
+int main(void) {
+  uint32_t buffer[4] = {1, 2, 3, 4};
+  zero_2x2(buffer);
+  printf("%u %u %u %u\n", buffer[0], buffer[1], buffer[2], buffer[3]);
+  return 0;
+}
+"#;
+
+        let imp = zero_impl();
+        for compiler in available_compilers() {
+            for kind in [LibraryKind::Static, LibraryKind::Shared] {
+                let dir = tempfile::tempdir().unwrap();
+                let options = BuildOptions {
+                    compiler,
+                    ..Default::default()
+                };
+                imp.build_library("zero_2x2", kind, dir.path(), &options)
+                    .unwrap();
+                let driver_path = dir.path().join("driver.c");
+                std::fs::write(&driver_path, DRIVER).unwrap();
+
+                let binary_path = dir.path().join("driver");
+                let link_proc = compiler_command(compiler)
+                    .unwrap()
+                    .args(OPENMP_CLI_FLAGS)
+                    .arg("-I")
+                    .arg(dir.path())
+                    .arg("-o")
+                    .arg(&binary_path)
+                    .arg(&driver_path)
+                    .arg("-L")
+                    .arg(dir.path())
+                    .arg("-lzero_2x2")
+                    .arg(format!("-Wl,-rpath,{}", dir.path().display()))
+                    .output()
+                    .unwrap();
+                if let Err(e) = check_compiler_output(&link_proc) {
+                    panic!("{compiler:?}, {kind:?}: {e:?}");
+                }
+
+                let output = Command::new(&binary_path).output().unwrap();
+                assert!(output.status.success(), "{compiler:?}, {kind:?}");
+                assert_eq!(
+                    String::from_utf8_lossy(&output.stdout),
+                    "0 0 0 0\n",
+                    "{compiler:?}, {kind:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_emit_kernels_shares_headers_and_dispatches_by_spec() {
+        let kernels = [Dtype::Uint32, Dtype::Uint8]
+            .map(|dtype| {
+                let spec = zero_spec(dtype);
+                let imp = zero_impl_for(&spec);
+                (spec, imp)
+            })
+            .to_vec();
+        let mut source = String::new();
+        let names = emit_kernels(&kernels, "dispatch", None, &mut source).unwrap();
+
+        assert_eq!(names.len(), 2);
+        assert_ne!(names[0], names[1]);
+        assert_eq!(source.matches("#include <stdint.h>").count(), 1, "{source}");
+        assert!(!source.contains("main("), "{source}");
+        for ((spec, _), name) in kernels.iter().zip(&names) {
+            assert!(source.contains(&format!("void {name}(")), "{source}");
+            assert!(
+                source.contains(&format!("{{\"{spec}\", {name}_dispatch}}")),
+                "{source}"
+            );
+        }
+        assert!(
+            source.contains("int dispatch(const char *spec, void *const *args) {"),
+            "{source}"
+        );
+    }
+
+    #[test]
+    fn test_emit_kernels_rejects_thread_styles_with_extra_arguments() {
+        let spec = zero_spec(Dtype::Uint32);
+        let imp = zero_impl_for(&spec);
+        let kernels = [(spec, imp)];
+        let top_arg_tensors = [kernels[0]
+            .1
+            .parameters()
+            .map(|parameter| Rc::new(Tensor::new(parameter.clone())))
+            .collect::<Vec<_>>()];
+        let mut generator = CpuCodeGenerator::<X86Target>::new();
+        generator.thread_style = CpuCodeGenThreadStyle::Highway;
+        let mut source = String::new();
+        assert!(matches!(
+            generator.emit_kernels(&kernels, &top_arg_tensors, "dispatch", &mut source),
+            Err(EmitError::Unsupported(_))
+        ));
+        assert!(source.is_empty(), "{source}");
+    }
+
+    #[test]
+    fn test_is_c_identifier() {
+        assert!(is_c_identifier("kernel"));
+        assert!(is_c_identifier("_matmul_4x4"));
+        assert!(!is_c_identifier(""));
+        assert!(!is_c_identifier("4x4"));
+        assert!(!is_c_identifier("zero-2x2"));
+        assert!(!is_valid_library_symbol("main"));
+    }
 
     #[test]
     fn test_parse_benchmark_output_valid_input() {
@@ -10,12 +10,20 @@ impl NameGenerator {
     }
 
     pub fn fresh_name(&mut self) -> String {
-        let new_name = String::from_iter(ASCII_PAIRS[self.names_generated]);
+        // Once the pairs are exhausted (e.g., when emitting many kernels into one file), continue
+        // with pairs suffixed by a number.
+        let pair = String::from_iter(ASCII_PAIRS[self.names_generated % ASCII_PAIRS.len()]);
+        let round = self.names_generated / ASCII_PAIRS.len();
         self.names_generated += 1;
-        if new_name == "do" {
-            return self.fresh_name();
+        if round == 0 {
+            // Skip the C keywords.
+            if pair == "do" || pair == "if" {
+                return self.fresh_name();
+            }
+            pair
+        } else {
+            format!("{pair}{round}")
         }
-        new_name
     }
 }
 
@@ -24,3 +32,17 @@ impl Default for NameGenerator {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_fresh_names_are_unique_identifiers() {
+        let mut namer = NameGenerator::new();
+        let names = (0..3000).map(|_| namer.fresh_name()).collect::<Vec<_>>();
+        assert_eq!(names.iter().collect::<HashSet<_>>().len(), names.len());
+        assert!(!names.iter().any(|n| n == "do" || n == "if"));
+    }
+}
@@ -0,0 +1,248 @@
+/// Helpers for Morello-generated Rust.
+///
+/// Each helper wraps `core::arch` intrinsics when they are enabled for the compilation target and
+/// otherwise falls back to portable code producing the same results, so callers need no `unsafe`.
+#[allow(dead_code, clippy::all)]
+mod morello_rt {
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    use core::arch::aarch64::*;
+    #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+    use core::arch::x86_64::*;
+
+    #[inline(always)]
+    pub fn bf16_to_f32(x: u16) -> f32 {
+        f32::from_bits(u32::from(x) << 16)
+    }
+
+    /// Rounds `x` to the nearest bfloat16, with ties to even, and returns its bits.
+    #[inline(always)]
+    pub fn f32_to_bf16(x: f32) -> u16 {
+        let bits = x.to_bits();
+        if x.is_nan() {
+            // Keep NaNs quiet, since truncating the mantissa could make one an infinity.
+            return ((bits >> 16) | 0x40) as u16;
+        }
+        ((bits + 0x7fff + ((bits >> 16) & 1)) >> 16) as u16
+    }
+
+    /// Adds `a * b` to `acc` lane-wise. All slices must have 8 values.
+    #[inline(always)]
+    pub fn mul_add_f32x8(acc: &mut [f32], a: &[f32], b: &[f32]) {
+        assert!(acc.len() == 8 && a.len() == 8 && b.len() == 8);
+        #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+        unsafe {
+            // SAFETY: The lengths are checked above and AVX2 is enabled.
+            let product = _mm256_mul_ps(_mm256_loadu_ps(a.as_ptr()), _mm256_loadu_ps(b.as_ptr()));
+            let sum = _mm256_add_ps(_mm256_loadu_ps(acc.as_ptr()), product);
+            _mm256_storeu_ps(acc.as_mut_ptr(), sum);
+        }
+        #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+        unsafe {
+            // SAFETY: The lengths are checked above and NEON is enabled.
+            for i in [0, 4] {
+                let product = vmulq_f32(vld1q_f32(a.as_ptr().add(i)), vld1q_f32(b.as_ptr().add(i)));
+                let sum = vaddq_f32(vld1q_f32(acc.as_ptr().add(i)), product);
+                vst1q_f32(acc.as_mut_ptr().add(i), sum);
+            }
+        }
+        #[cfg(not(any(
+            all(target_arch = "x86_64", target_feature = "avx2"),
+            all(target_arch = "aarch64", target_feature = "neon")
+        )))]
+        for i in 0..8 {
+            acc[i] += a[i] * b[i];
+        }
+    }
+
+    /// Sums 8 values in the same order as `sum8` in the C backend.
+    #[inline(always)]
+    pub fn sum8(x: &[f32]) -> f32 {
+        assert_eq!(x.len(), 8);
+        #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+        unsafe {
+            // SAFETY: The length is checked above and AVX2 is enabled.
+            let v = _mm256_loadu_ps(x.as_ptr());
+            let sum_quad = _mm_add_ps(_mm256_castps256_ps128(v), _mm256_extractf128_ps(v, 1));
+            let sum_dual = _mm_add_ps(sum_quad, _mm_movehl_ps(sum_quad, sum_quad));
+            let sum = _mm_add_ss(sum_dual, _mm_shuffle_ps(sum_dual, sum_dual, 0x1));
+            _mm_cvtss_f32(sum)
+        }
+        #[cfg(not(all(target_arch = "x86_64", target_feature = "avx2")))]
+        {
+            ((x[0] + x[4]) + (x[2] + x[6])) + ((x[1] + x[5]) + (x[3] + x[7]))
+        }
+    }
+
+    /// Converts 16 bfloat16s to the float32s of the upper and lower 8, in that order.
+    #[inline(always)]
+    pub fn cvt_bf16_f32_halves(x: &[u16], upper: &mut [f32], lower: &mut [f32]) {
+        assert!(x.len() == 16 && upper.len() == 8 && lower.len() == 8);
+        #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+        unsafe {
+            // SAFETY: The lengths are checked above and AVX2 is enabled.
+            let v = _mm256_loadu_si256(x.as_ptr() as *const __m256i);
+            for (half, out) in [(_mm256_extracti128_si256(v, 1), upper), (_mm256_castsi256_si128(v), lower)] {
+                let widened = _mm256_slli_epi32(_mm256_cvtepu16_epi32(half), 16);
+                _mm256_storeu_ps(out.as_mut_ptr(), _mm256_castsi256_ps(widened));
+            }
+        }
+        #[cfg(not(all(target_arch = "x86_64", target_feature = "avx2")))]
+        for i in 0..8 {
+            upper[i] = bf16_to_f32(x[8 + i]);
+            lower[i] = bf16_to_f32(x[i]);
+        }
+    }
+
+    /// Converts the even- and odd-indexed values of 16 bfloat16s to float32s.
+    #[inline(always)]
+    pub fn cvt_bf16_f32_even_odd(x: &[u16], even: &mut [f32], odd: &mut [f32]) {
+        assert!(x.len() == 16 && even.len() == 8 && odd.len() == 8);
+        #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+        unsafe {
+            // SAFETY: The lengths are checked above and AVX2 is enabled.
+            let v = _mm256_loadu_si256(x.as_ptr() as *const __m256i);
+            let shifted = _mm256_slli_epi32(v, 16);
+            let blended = _mm256_blend_epi16(_mm256_setzero_si256(), v, 0xAA);
+            _mm256_storeu_ps(even.as_mut_ptr(), _mm256_castsi256_ps(shifted));
+            _mm256_storeu_ps(odd.as_mut_ptr(), _mm256_castsi256_ps(blended));
+        }
+        #[cfg(not(all(target_arch = "x86_64", target_feature = "avx2")))]
+        for i in 0..8 {
+            even[i] = bf16_to_f32(x[2 * i]);
+            odd[i] = bf16_to_f32(x[2 * i + 1]);
+        }
+    }
+
+    /// Adds to each of 16 int16s the saturated sum of the products of a broadcast pair of
+    /// unsigned bytes with a corresponding pair of signed bytes (as in `_mm256_maddubs_epi16`).
+    #[inline(always)]
+    pub fn maddubs_add_i16x16(acc: &mut [i16], a: &[u8], b: &[i8]) {
+        assert!(acc.len() == 16 && a.len() == 2 && b.len() == 32);
+        #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+        unsafe {
+            // SAFETY: The lengths are checked above and AVX2 is enabled.
+            let broadcast = _mm256_set1_epi16(i16::from_le_bytes([a[0], a[1]]));
+            let products =
+                _mm256_maddubs_epi16(broadcast, _mm256_loadu_si256(b.as_ptr() as *const __m256i));
+            let sum = _mm256_add_epi16(_mm256_loadu_si256(acc.as_ptr() as *const __m256i), products);
+            _mm256_storeu_si256(acc.as_mut_ptr() as *mut __m256i, sum);
+        }
+        #[cfg(not(all(target_arch = "x86_64", target_feature = "avx2")))]
+        for i in 0..16 {
+            let dot = i32::from(a[0]) * i32::from(b[2 * i]) + i32::from(a[1]) * i32::from(b[2 * i + 1]);
+            let saturated = dot.clamp(i32::from(i16::MIN), i32::from(i16::MAX)) as i16;
+            acc[i] = acc[i].wrapping_add(saturated);
+        }
+    }
+
+    /// Interleaves the bytes of `a` and `b`, writing the first half of the result to `lo` and the
+    /// second to `hi`. All slices must have 16 or 32 one-byte values.
+    #[inline(always)]
+    pub fn interleave_bytes<T: Copy>(a: &[T], b: &[T], lo: &mut [T], hi: &mut [T]) {
+        assert_eq!(core::mem::size_of::<T>(), 1);
+        let n = a.len();
+        assert!((n == 16 || n == 32) && b.len() == n && lo.len() == n && hi.len() == n);
+        #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+        unsafe {
+            // SAFETY: The lengths and value sizes are checked above and AVX2 is enabled.
+            if n == 16 {
+                let av = _mm_loadu_si128(a.as_ptr() as *const __m128i);
+                let bv = _mm_loadu_si128(b.as_ptr() as *const __m128i);
+                _mm_storeu_si128(lo.as_mut_ptr() as *mut __m128i, _mm_unpacklo_epi8(av, bv));
+                _mm_storeu_si128(hi.as_mut_ptr() as *mut __m128i, _mm_unpackhi_epi8(av, bv));
+            } else {
+                let av = _mm256_loadu_si256(a.as_ptr() as *const __m256i);
+                let bv = _mm256_loadu_si256(b.as_ptr() as *const __m256i);
+                let l = _mm256_unpacklo_epi8(av, bv);
+                let h = _mm256_unpackhi_epi8(av, bv);
+                _mm256_storeu_si256(lo.as_mut_ptr() as *mut __m256i, _mm256_permute2f128_si256(l, h, 0x20));
+                _mm256_storeu_si256(hi.as_mut_ptr() as *mut __m256i, _mm256_permute2f128_si256(l, h, 0x31));
+            }
+        }
+        #[cfg(not(all(target_arch = "x86_64", target_feature = "avx2")))]
+        for i in 0..n / 2 {
+            lo[2 * i] = a[i];
+            lo[2 * i + 1] = b[i];
+            hi[2 * i] = a[n / 2 + i];
+            hi[2 * i + 1] = b[n / 2 + i];
+        }
+    }
+
+    /// Values which can be read from and written to little-endian files.
+    pub trait LeBytes: Copy {
+        const SIZE: usize;
+        fn from_le(bytes: &[u8]) -> Self;
+        fn to_le(self, out: &mut Vec<u8>);
+    }
+
+    macro_rules! impl_le_bytes {
+        ($($t:ty),*) => {
+            $(impl LeBytes for $t {
+                const SIZE: usize = core::mem::size_of::<$t>();
+                fn from_le(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+                fn to_le(self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+            })*
+        };
+    }
+
+    impl_le_bytes!(u8, i8, u16, i16, u32, i32, f32);
+
+    /// Fills `dest` with the little-endian values in `bytes`.
+    fn fill_from_le<T: LeBytes>(bytes: &[u8], dest: &mut [T]) -> std::io::Result<()> {
+        if bytes.len() < dest.len() * T::SIZE {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        for (d, chunk) in dest.iter_mut().zip(bytes.chunks_exact(T::SIZE)) {
+            *d = T::from_le(chunk);
+        }
+        Ok(())
+    }
+
+    /// Fills `dest` with the little-endian values in the file at `path`.
+    pub fn load_input<T: LeBytes>(path: &str, dest: &mut [T]) -> std::io::Result<()> {
+        fill_from_le(&std::fs::read(path)?, dest)
+    }
+
+    /// Returns a tensor file header: the magic, then little-endian `u32` fields.
+    fn tensor_file_header(dtype_code: u32, shape: &[u32]) -> Vec<u8> {
+        let mut header = b"MRLT".to_vec();
+        for field in [dtype_code, shape.len() as u32].iter().chain(shape) {
+            header.extend_from_slice(&field.to_le_bytes());
+        }
+        header
+    }
+
+    /// Fills `dest` from the tensor file at `path`, checking its header matches the tensor.
+    pub fn load_tensor_file<T: LeBytes>(
+        path: &str,
+        dtype_code: u32,
+        shape: &[u32],
+        dest: &mut [T],
+    ) -> std::io::Result<()> {
+        let bytes = std::fs::read(path)?;
+        let header = tensor_file_header(dtype_code, shape);
+        if !bytes.starts_with(&header) {
+            return Err(std::io::ErrorKind::InvalidData.into());
+        }
+        fill_from_le(&bytes[header.len()..], dest)
+    }
+
+    /// Writes `src` to a tensor file at `path`.
+    pub fn write_tensor_file<T: LeBytes>(
+        path: &str,
+        dtype_code: u32,
+        shape: &[u32],
+        src: &[T],
+    ) -> std::io::Result<()> {
+        let mut bytes = tensor_file_header(dtype_code, shape);
+        bytes.reserve(src.len() * T::SIZE);
+        for &value in src {
+            value.to_le(&mut bytes);
+        }
+        std::fs::write(path, bytes)
+    }
+}
@@ -1,7 +1,7 @@
 #include <immintrin.h>
 
 // From Marat Dukhan: https://stackoverflow.com/a/13222410/110389
-inline float sum8(__m256 x) {
+static inline float sum8(__m256 x) {
   const __m128 hiQuad = _mm256_extractf128_ps(x, 1);
   const __m128 loQuad = _mm256_castps256_ps128(x);
   const __m128 sumQuad = _mm_add_ps(loQuad, hiQuad);
@@ -14,11 +14,11 @@ inline float sum8(__m256 x) {
   return _mm_cvtss_f32(sum);
 }
 
-inline __m256 cvtbf16_fp32(const __m128i a) {
+static inline __m256 cvtbf16_fp32(const __m128i a) {
   return _mm256_castsi256_ps(_mm256_slli_epi32(_mm256_cvtepu16_epi32(a), 16));
 }
 
-inline void cvtbf16_fp32_256(const __m256i a, __m256 *o1, __m256 *o2) {
+static inline void cvtbf16_fp32_256(const __m256i a, __m256 *o1, __m256 *o2) {
   *o1 = cvtbf16_fp32(_mm256_extractf128_si256(a, 1));
   *o2 = cvtbf16_fp32(_mm256_extractf128_si256(a, 0));
 }
@@ -0,0 +1,1366 @@
+use itertools::{Either, Itertools};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::process::Command;
+use std::rc::Rc;
+use tempfile::tempdir;
+
+use super::c_utils::{CBuffer, CExprVar};
+use super::clang::rustc_path;
+use super::cpu::{axis_order_and_steps, expr_to_c, get_vector, zero_points};
+use super::namegen::NameGenerator;
+use super::tensor_file;
+use super::{check_compiler_output, BuildError, BuiltArtifact, EmitError};
+use crate::common::{DimSize, Dtype};
+use crate::expr::{AffineForm, NonAffine, NonAffineExpr, Substitute};
+use crate::imp::blocks::Block;
+use crate::imp::kernels::KernelApp;
+use crate::imp::loops::Loop;
+use crate::imp::moves::TensorOrCacheView;
+use crate::imp::{Impl, ImplNode};
+use crate::layout::BufferVar;
+use crate::pprint::{pprint_write, ImplPrintStyle};
+use crate::target::cpu::{
+    DOT_PRODUCT_ACCUM_COUNT, DOT_PRODUCT_BF16_ACCUM_COUNT, DOT_PRODUCT_BF16_STRIP_SIZE,
+    DOT_PRODUCT_STRIP_SIZE,
+};
+use crate::target::{CpuKernel, CpuMemoryLevel, CpuTarget, Target, TargetId};
+use crate::utils::{indent, LinePrefixWrite};
+use crate::views::{Param, Tensor, View};
+
+const RUSTC_FLAGS: [&str; 4] = ["--edition=2021", "-C", "opt-level=3", "-Awarnings"];
+const X86_RUSTC_FLAGS: [&str; 2] = ["-C", "target-feature=+avx2"];
+const ARM_RUSTC_FLAGS: [&str; 2] = ["-C", "target-feature=+neon"];
+
+const ALLOWED_LINTS: &str =
+    "#[allow(unused_mut, unused_parens, unused_variables, clippy::all, clippy::pedantic)]";
+
+/// Lowers an Impl to a Rust function taking slices.
+///
+/// Loops become `for` loops (parallel loops run serially), buffers introduced by moves become
+/// local arrays, [Vec]s, or values, and vector registers become fixed-size arrays. Kernels whose
+/// C lowering uses intrinsics call the safe wrappers in `partials/prelude.rs`, which is written
+/// ahead of the function by [Self::emit_prelude].
+pub struct RustCodeGenerator<'a, Tgt: Target> {
+    pub namer: NameGenerator,
+    pub name_env: HashMap<Rc<Tensor<Tgt>>, CBuffer>,
+    pub loop_iter_bindings: HashMap<BufferVar, Either<String, i32>>,
+    pub param_bindings: HashMap<Param<Tgt>, &'a dyn View<Tgt = Tgt>>,
+    pub kernel_name: String,
+}
+
+impl<'a, Tgt: CpuTarget> RustCodeGenerator<'a, Tgt> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a pretty-printed Impl as a Rust comment.
+    pub fn emit_impl_comment<W: Write>(
+        &mut self,
+        imp: &'a ImplNode<Tgt>,
+        impl_style: ImplPrintStyle,
+        out: &mut W,
+    ) -> fmt::Result {
+        let mut commenting_out = LinePrefixWrite::new(out, "// ");
+        pprint_write(&mut commenting_out, imp, impl_style)?;
+        Ok(())
+    }
+
+    /// Write the `morello_rt` module of helpers called by emitted kernels.
+    pub fn emit_prelude<W: Write>(&self, out: &mut W) -> fmt::Result {
+        out.write_str(include_str!("../codegen/partials/prelude.rs"))
+    }
+
+    /// Write a function named `self.kernel_name` implementing `imp`.
+    ///
+    /// The function takes a slice for each parameter. All but the last (the output) are immutable.
+    pub fn emit_kernel<W: Write>(
+        &mut self,
+        imp: &'a ImplNode<Tgt>,
+        top_arg_tensors: &'a [Rc<Tensor<Tgt>>],
+        out: &mut W,
+    ) -> Result<(), EmitError> {
+        debug_assert_eq!(top_arg_tensors.len(), usize::from(imp.parameter_count()));
+
+        let parameter_count = top_arg_tensors.len();
+        writeln!(out, "{ALLOWED_LINTS}")?;
+        writeln!(out, "pub fn {}(", self.kernel_name)?;
+        for (operand_idx, tensor) in top_arg_tensors.iter().enumerate() {
+            let dtype = tensor.spec().dtype();
+            let parameter_name = self.namer.fresh_name();
+            writeln!(
+                out,
+                "  {parameter_name}: &{}[{}],",
+                if operand_idx + 1 < parameter_count {
+                    ""
+                } else {
+                    "mut "
+                },
+                rust_type(dtype)
+            )?;
+            self.name_env.insert(
+                Rc::clone(tensor),
+                CBuffer::Ptr {
+                    name: parameter_name,
+                    dtype,
+                },
+            );
+        }
+        writeln!(out, ") {{")?;
+
+        let tensors_as_trait_obj_ptrs = top_arg_tensors
+            .iter()
+            .map(|tensor| tensor.as_ref() as &dyn View<Tgt = Tgt>)
+            .collect::<Vec<_>>();
+        imp.bind(&tensors_as_trait_obj_ptrs, &mut self.param_bindings);
+        self.emit(out, imp, 1)?;
+
+        writeln!(out, "}}")?;
+        Ok(())
+    }
+
+    /// Write a `main` which behaves like the C backend's: it optionally loads each parameter from
+    /// a file of little-endian values named on the command line, calls the kernel, and prints the
+    /// output tensor.
+    pub fn emit_main<W: Write>(
+        &mut self,
+        top_arg_tensors: &'a [Rc<Tensor<Tgt>>],
+        out: &mut W,
+    ) -> Result<(), EmitError> {
+        if top_arg_tensors
+            .iter()
+            .any(|t| !t.spec().layout().is_row_major())
+        {
+            return Err(EmitError::Unsupported(String::from(
+                "the Rust backend's main can only load row-major tensors",
+            )));
+        }
+
+        writeln!(out, "fn main() {{")?;
+        writeln!(
+            out,
+            "{}let args = std::env::args().collect::<Vec<_>>();",
+            indent(1)
+        )?;
+        let mut buffer_names = vec![];
+        for tensor in top_arg_tensors {
+            let spec = tensor.spec();
+            let name = self.namer.fresh_name();
+            writeln!(
+                out,
+                "{}let mut {name} = vec![{}; {}];",
+                indent(1),
+                rust_zero(spec.dtype()),
+                spec.volume()
+            )?;
+            buffer_names.push(name);
+        }
+
+        // With `--binary OUTPUT`, inputs and the output are in the tensor file format.
+        writeln!(
+            out,
+            "{}let binary = args.len() == {} && args[1] == \"--binary\";",
+            indent(1),
+            top_arg_tensors.len() + 3
+        )?;
+        writeln!(
+            out,
+            "{}if args.len() == {} || binary {{",
+            indent(1),
+            top_arg_tensors.len() + 1
+        )?;
+        writeln!(
+            out,
+            "{}let paths = &args[if binary {{ 3 }} else {{ 1 }}..];",
+            indent(2)
+        )?;
+        for (idx, (name, tensor)) in buffer_names.iter().zip(top_arg_tensors).enumerate() {
+            let spec = tensor.spec();
+            writeln!(
+                out,
+                "{}let loaded = if binary {{ morello_rt::load_tensor_file(&paths[{idx}], {}, &{}, &mut {name}) }} else {{ morello_rt::load_input(&paths[{idx}], &mut {name}) }};",
+                indent(2),
+                tensor_file::dtype_code(spec.dtype()),
+                rs_shape(spec.shape()),
+            )?;
+            writeln!(out, "{}if loaded.is_err() {{", indent(2))?;
+            writeln!(
+                out,
+                "{}eprintln!(\"Error loading input tensors.\");",
+                indent(3)
+            )?;
+            writeln!(out, "{}std::process::exit(2);", indent(3))?;
+            writeln!(out, "{}}}", indent(2))?;
+        }
+        writeln!(out, "{}}} else if args.len() != 1 {{", indent(1))?;
+        writeln!(
+            out,
+            "{}eprintln!(\"Unexpected number of arguments.\");",
+            indent(2)
+        )?;
+        writeln!(out, "{}std::process::exit(1);", indent(2))?;
+        writeln!(out, "{}}}\n", indent(1))?;
+
+        writeln!(
+            out,
+            "{}{}({});\n",
+            indent(1),
+            self.kernel_name,
+            buffer_names
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| if idx + 1 < buffer_names.len() {
+                    format!("&{name}")
+                } else {
+                    format!("&mut {name}")
+                })
+                .join(", ")
+        )?;
+
+        let output = top_arg_tensors.last().unwrap().spec();
+        let output_name = buffer_names.last().unwrap();
+        writeln!(out, "{}if binary {{", indent(1))?;
+        writeln!(
+            out,
+            "{}if morello_rt::write_tensor_file(&args[2], {}, &{}, &{output_name}).is_err() {{",
+            indent(2),
+            tensor_file::dtype_code(output.dtype()),
+            rs_shape(output.shape()),
+        )?;
+        writeln!(
+            out,
+            "{}eprintln!(\"Error writing output tensor.\");",
+            indent(3)
+        )?;
+        writeln!(out, "{}std::process::exit(3);", indent(3))?;
+        writeln!(out, "{}}}", indent(2))?;
+        writeln!(out, "{}return;", indent(2))?;
+        writeln!(out, "{}}}", indent(1))?;
+
+        // Otherwise, print the output in the C backend's format: the shape, then one line per row.
+        let row_len = output.shape().last().unwrap();
+        writeln!(
+            out,
+            "{}println!(\"{}\");",
+            indent(1),
+            output.shape().iter().join("x")
+        )?;
+        writeln!(
+            out,
+            "{}for row in {output_name}.chunks({row_len}) {{",
+            indent(1)
+        )?;
+        let value_expr = if output.dtype() == Dtype::Bfloat16 {
+            "morello_rt::bf16_to_f32(*v)"
+        } else {
+            "v"
+        };
+        writeln!(
+            out,
+            "{}println!(\"{{}}\", row.iter().map(|v| {value_expr}.to_string()).collect::<Vec<_>>().join(\" \"));",
+            indent(2)
+        )?;
+        writeln!(out, "{}}}", indent(1))?;
+        writeln!(out, "}}")?;
+        Ok(())
+    }
+
+    fn make_buffer(
+        &mut self,
+        shape: &[DimSize],
+        vector_size: Option<DimSize>,
+        dtype: Dtype,
+        level: CpuMemoryLevel,
+    ) -> CBuffer {
+        debug_assert_eq!(vector_size.is_some(), level == CpuMemoryLevel::VRF);
+
+        let name = self.namer.fresh_name();
+        let size = shape.iter().map(|d| d.get()).product::<u32>();
+        match level {
+            CpuMemoryLevel::VRF => {
+                let vector_size = vector_size.unwrap();
+                let vec_type = get_vector(Tgt::vec_types(), dtype, vector_size);
+                let vector_size = vector_size.get();
+                debug_assert_eq!(size % vector_size, 0);
+                if size == vector_size {
+                    CBuffer::SingleVecVar { name, vec_type }
+                } else {
+                    let inner_vecs = (0..(size / vector_size))
+                        .map(|_| CBuffer::SingleVecVar {
+                            name: self.namer.fresh_name(),
+                            vec_type,
+                        })
+                        .collect::<Vec<_>>();
+                    CBuffer::VecVars { inner_vecs }
+                }
+            }
+            CpuMemoryLevel::RF => {
+                if size > 1 {
+                    CBuffer::StackArray { name, size, dtype }
+                } else {
+                    CBuffer::ValueVar { name, dtype }
+                }
+            }
+            CpuMemoryLevel::L1 | CpuMemoryLevel::GL => {
+                // Keep large buffers off of the stack, as in the C backend.
+                if size * u32::from(dtype.size()) > 256 {
+                    CBuffer::HeapArray { name, size, dtype }
+                } else {
+                    CBuffer::StackArray { name, size, dtype }
+                }
+            }
+        }
+    }
+
+    fn emit_buffer_decl<W: Write>(
+        &self,
+        w: &mut W,
+        buffer: &CBuffer,
+        depth: usize,
+    ) -> Result<(), EmitError> {
+        match buffer {
+            CBuffer::HeapArray { name, size, dtype } => writeln!(
+                w,
+                "{}let mut {name} = vec![{}; {size}];",
+                indent(depth),
+                rust_zero(*dtype)
+            )?,
+            CBuffer::StackArray { name, size, dtype } => writeln!(
+                w,
+                "{}let mut {name} = [{}; {size}];",
+                indent(depth),
+                rust_zero(*dtype)
+            )?,
+            CBuffer::ValueVar { name, dtype } => writeln!(
+                w,
+                "{}let mut {name}: {} = {};",
+                indent(depth),
+                rust_type(*dtype),
+                rust_zero(*dtype)
+            )?,
+            CBuffer::SingleVecVar { name, vec_type } => writeln!(
+                w,
+                "{}let mut {name} = [{}; {}];",
+                indent(depth),
+                rust_zero(vec_type.dtype),
+                vec_type.value_cnt
+            )?,
+            CBuffer::VecVars { inner_vecs } => {
+                for inner_vec in inner_vecs {
+                    self.emit_buffer_decl(w, inner_vec, depth)?;
+                }
+            }
+            CBuffer::Ptr { name, .. } => {
+                return Err(EmitError::Unsupported(format!(
+                    "the Rust backend can't declare `{name}`, which points into another buffer"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn emit<W: Write>(
+        &mut self,
+        w: &mut W,
+        imp: &ImplNode<Tgt>,
+        depth: usize,
+    ) -> Result<(), EmitError> {
+        match imp {
+            ImplNode::Loop(l) => {
+                // As in the C backend, loops over tensors spread across several vector variables
+                // are unrolled.
+                if l.tiles.iter().any(|loop_tile| {
+                    self.name_env
+                        .get(loop_tile.tile.backing_tensor(&self.param_bindings).unwrap())
+                        .unwrap()
+                        .needs_unroll()
+                }) {
+                    self.emit_unrolled_loop(w, l, depth)
+                } else {
+                    self.emit_rolled_loop(w, l, depth)
+                }
+            }
+            ImplNode::MoveLet(move_let) => {
+                if let TensorOrCacheView::Tensor(tensor) = &move_let.introduced {
+                    let spec = move_let.introduced.spec();
+                    let dest_buffer = self.make_buffer(
+                        spec.shape(),
+                        spec.vector_size(),
+                        spec.dtype(),
+                        spec.level(),
+                    );
+                    self.emit_buffer_decl(w, &dest_buffer, depth)?;
+                    self.name_env.insert(Rc::clone(tensor), dest_buffer);
+                }
+
+                if let Some(prologue) = move_let.prologue() {
+                    self.emit(w, prologue, depth)?;
+                }
+                self.emit(w, move_let.main_stage(), depth)?;
+                if let Some(epilogue) = move_let.epilogue() {
+                    self.emit(w, epilogue, depth)?;
+                }
+
+                if let TensorOrCacheView::Tensor(tensor) = &move_let.introduced {
+                    self.name_env.remove(&**tensor).unwrap();
+                }
+                Ok(())
+            }
+            ImplNode::Block(Block { stages, .. }) => {
+                for stage in stages {
+                    self.emit(w, stage, depth)?;
+                }
+                Ok(())
+            }
+            ImplNode::Pipeline(_) => Err(EmitError::Unsupported(String::from(
+                "the Rust backend doesn't support Pipeline",
+            ))),
+            ImplNode::SpecApp(p) => {
+                writeln!(
+                    w,
+                    "{}unimplemented!(\"Missing Impl: {}({})\");",
+                    indent(depth),
+                    p.0,
+                    p.1.iter().map(|_| "_").join(", ")
+                )?;
+                Ok(())
+            }
+            ImplNode::Kernel(KernelApp {
+                kernel_type,
+                arguments,
+                spec: _,
+            }) => self.emit_kernel_app(w, *kernel_type, arguments, depth),
+        }
+    }
+
+    fn emit_kernel_app<W: Write>(
+        &mut self,
+        w: &mut W,
+        kernel_type: CpuKernel,
+        arguments: &[Param<Tgt>],
+        depth: usize,
+    ) -> Result<(), EmitError> {
+        let dtypes = arguments
+            .iter()
+            .map(|a| a.spec().dtype())
+            .collect::<Vec<_>>();
+        match kernel_type {
+            CpuKernel::MultAdd => {
+                let exprs =
+                    self.param_args_to_indices(arguments, |_, a, b| Ok(self.rs_index(a, b)))?;
+                writeln!(
+                    w,
+                    "{}{};  // MultAdd",
+                    indent(depth),
+                    mult_add(&dtypes, &exprs[0], &exprs[1], &exprs[2])
+                )?;
+            }
+            CpuKernel::ValueAssign => {
+                let exprs =
+                    self.param_args_to_indices(arguments, |_, a, b| Ok(self.rs_index(a, b)))?;
+                writeln!(w, "{}{} = {};", indent(depth), exprs[1], exprs[0])?;
+            }
+            CpuKernel::CastBf16F32 => {
+                let exprs =
+                    self.param_args_to_indices(arguments, |_, a, b| Ok(self.rs_index(a, b)))?;
+                writeln!(
+                    w,
+                    "{}{} = morello_rt::bf16_to_f32({});",
+                    indent(depth),
+                    exprs[1],
+                    exprs[0]
+                )?;
+            }
+            CpuKernel::VectorCastBf16F32 => {
+                let vector_size = arguments[1].spec().vector_size().unwrap().get();
+                let exprs = self.param_args_to_indices(arguments, |i, a, b| match i {
+                    0 => self.rs_slice(a, b, 0, 16),
+                    _ => Ok(format!(
+                        "{}, {}",
+                        self.rs_slice_mut(a, b, 0, 8)?,
+                        self.rs_slice_mut(
+                            a,
+                            &(b.clone() + i32::try_from(vector_size).unwrap()),
+                            0,
+                            8
+                        )?
+                    )),
+                })?;
+                writeln!(
+                    w,
+                    "{}morello_rt::cvt_bf16_f32_halves({}, {});",
+                    indent(depth),
+                    exprs[0],
+                    exprs[1]
+                )?;
+            }
+            CpuKernel::MemsetZero => {
+                debug_assert_eq!(arguments.len(), 1);
+                let value_cnt = u32::try_from(arguments[0].1.bytes_used()).unwrap()
+                    / u32::from(dtypes[0].size());
+                let exprs = self.param_args_to_indices(arguments, |_, a, b| match a {
+                    CBuffer::ValueVar { name, .. } => {
+                        Ok(format!("{name} = {}", rust_zero(dtypes[0])))
+                    }
+                    _ => Ok(format!(
+                        "{}.fill({})",
+                        self.rs_range(a, b, 0, value_cnt)?,
+                        rust_zero(dtypes[0])
+                    )),
+                })?;
+                writeln!(w, "{}{};", indent(depth), exprs[0])?;
+            }
+            CpuKernel::VectorZero => {
+                let exprs = self.param_args_to_indices(arguments, |_, a, b| self.rs_vec(a, b))?;
+                writeln!(
+                    w,
+                    "{}{}.fill({});  // VectorZero",
+                    indent(depth),
+                    exprs[0],
+                    rust_zero(dtypes[0])
+                )?;
+            }
+            CpuKernel::VectorAssign => {
+                let volume = arguments[0].spec().volume().get();
+                let exprs = self
+                    .param_args_to_indices(arguments, |_, a, b| self.rs_range(a, b, 0, volume))?;
+                writeln!(
+                    w,
+                    "{}{}.copy_from_slice(&{});  // VectorAssign",
+                    indent(depth),
+                    exprs[1],
+                    exprs[0]
+                )?;
+            }
+            CpuKernel::BroadcastVecMultAdd => {
+                let vector_size = arguments[2].spec().vector_size().unwrap().get();
+                let volume = arguments[2].spec().volume().get();
+                debug_assert_eq!(volume % vector_size, 0);
+                for vector_idx in 0..volume / vector_size {
+                    let lane = self.namer.fresh_name();
+                    let exprs = self.param_args_to_indices(arguments, |i, a, b| match i {
+                        0 => Ok(self.rs_index(a, b)),
+                        _ => Ok(format!(
+                            "{}[{lane}]",
+                            self.rs_vec(
+                                a,
+                                &(b.clone() + i32::try_from(vector_idx * vector_size).unwrap())
+                            )?
+                        )),
+                    })?;
+                    writeln!(
+                        w,
+                        "{}for {lane} in 0..{vector_size} {{  // BroadcastVecMultAdd",
+                        indent(depth)
+                    )?;
+                    writeln!(
+                        w,
+                        "{}{};",
+                        indent(depth + 1),
+                        mult_add(&dtypes, &exprs[0], &exprs[1], &exprs[2])
+                    )?;
+                    writeln!(w, "{}}}", indent(depth))?;
+                }
+            }
+            CpuKernel::BroadcastVecMultAddBf16F32 => {
+                let vector_size_bf16 = arguments[1].spec().vector_size().unwrap().get();
+                let volume = arguments[1].spec().volume().get();
+                debug_assert_eq!(volume % vector_size_bf16, 0);
+                writeln!(w, "{}// BroadcastVecMultAddBf16F32", indent(depth))?;
+                for vector_idx in 0..volume / vector_size_bf16 {
+                    let lane = self.namer.fresh_name();
+                    let offset = i32::try_from(vector_idx * vector_size_bf16).unwrap();
+                    let exprs = self.param_args_to_indices(arguments, |i, a, b| match i {
+                        0 => Ok(self.rs_index(a, b)),
+                        _ => Ok(format!(
+                            "{}[{lane}]",
+                            self.rs_vec(a, &(b.clone() + offset))?
+                        )),
+                    })?;
+                    writeln!(w, "{}for {lane} in 0..{vector_size_bf16} {{", indent(depth))?;
+                    writeln!(
+                        w,
+                        "{}{} += morello_rt::bf16_to_f32({}) * morello_rt::bf16_to_f32({});",
+                        indent(depth + 1),
+                        exprs[2],
+                        exprs[0],
+                        exprs[1]
+                    )?;
+                    writeln!(w, "{}}}", indent(depth))?;
+                }
+            }
+            CpuKernel::TwoVecBroadcastVecMultAddU8S8S16 => {
+                let vector_size = arguments[2].spec().vector_size().unwrap().get();
+                let volume = arguments[2].spec().volume().get();
+                debug_assert_eq!(volume % vector_size, 0);
+                for vector_idx in 0..volume / vector_size {
+                    let offset = i32::try_from(vector_idx * vector_size).unwrap();
+                    let exprs = self.param_args_to_indices(arguments, |i, a, b| match i {
+                        0 => self.rs_slice(a, b, 0, 2),
+                        1 => Ok(format!("&{}", self.rs_vec(a, &(b.clone() + offset))?)),
+                        _ => Ok(format!("&mut {}", self.rs_vec(a, &(b.clone() + offset))?)),
+                    })?;
+                    writeln!(
+                        w,
+                        "{}morello_rt::maddubs_add_i16x16({}, {}, {});  // TwoVecBroadcastVecMultAddU8S8S16",
+                        indent(depth),
+                        exprs[2],
+                        exprs[0],
+                        exprs[1]
+                    )?;
+                }
+            }
+            CpuKernel::DotProductLoop => {
+                let k = arguments[0].spec().shape()[1].get();
+                let strip = DOT_PRODUCT_STRIP_SIZE.get();
+                debug_assert_eq!(k % strip, 0);
+                let step = self.namer.fresh_name();
+                let accums = (0..DOT_PRODUCT_ACCUM_COUNT)
+                    .map(|_| self.namer.fresh_name())
+                    .collect::<Vec<_>>();
+                writeln!(w, "{}// DotProductLoop", indent(depth))?;
+                for accum in &accums {
+                    writeln!(w, "{}let mut {accum} = [0f32; {strip}];", indent(depth))?;
+                }
+                writeln!(
+                    w,
+                    "{}for {step} in (0..{k}).step_by({}) {{",
+                    indent(depth),
+                    DOT_PRODUCT_ACCUM_COUNT * strip
+                )?;
+                for (i, accum) in accums.iter().enumerate() {
+                    let strip_offset = u32::try_from(i).unwrap() * strip;
+                    let exprs = self.param_args_to_indices(&arguments[..2], |_, a, b| {
+                        self.rs_slice_at(a, b, &format!("{step} + {strip_offset}"), strip)
+                    })?;
+                    writeln!(
+                        w,
+                        "{}morello_rt::mul_add_f32x8(&mut {accum}, {}, {});",
+                        indent(depth + 1),
+                        exprs[0],
+                        exprs[1]
+                    )?;
+                }
+                writeln!(w, "{}}}", indent(depth))?;
+                let out =
+                    self.param_args_to_indices(&arguments[2..], |_, a, b| Ok(self.rs_index(a, b)))?;
+                writeln!(
+                    w,
+                    "{}{} = morello_rt::sum8(&{});",
+                    indent(depth),
+                    out[0],
+                    accums[0]
+                )?;
+                for accum in &accums[1..] {
+                    writeln!(
+                        w,
+                        "{}{} += morello_rt::sum8(&{accum});",
+                        indent(depth),
+                        out[0]
+                    )?;
+                }
+            }
+            CpuKernel::DotProductLoopBf16Bf16F32
+            | CpuKernel::DotProductLoopF32Bf16F32
+            | CpuKernel::DotProductLoopF32InterleavedBf16F32 => {
+                self.emit_bf16_dot_product_loop(w, kernel_type, arguments, depth)?;
+            }
+            CpuKernel::PhysicalTransposeByte128 | CpuKernel::PhysicalTransposeByte256 => {
+                let half = if kernel_type == CpuKernel::PhysicalTransposeByte128 {
+                    16
+                } else {
+                    32
+                };
+                let exprs = self.param_args_to_indices(arguments, |i, a, b| {
+                    let lower = self.rs_vec(a, b)?;
+                    let higher = self.rs_vec(a, &(b.clone() + half))?;
+                    if i == 0 {
+                        Ok(format!("&{lower}, &{higher}"))
+                    } else {
+                        Ok(format!("&mut {lower}, &mut {higher}"))
+                    }
+                })?;
+                writeln!(
+                    w,
+                    "{}morello_rt::interleave_bytes({}, {});",
+                    indent(depth),
+                    exprs[0],
+                    exprs[1]
+                )?;
+            }
+            CpuKernel::VectorInterleaveBf16F32 => {
+                let exprs = self.param_args_to_indices(arguments, |i, a, b| {
+                    if i == 0 {
+                        Ok(format!("&{}", self.rs_vec(a, b)?))
+                    } else {
+                        Ok(format!(
+                            "&mut {}, &mut {}",
+                            self.rs_vec(a, b)?,
+                            self.rs_vec(a, &(b.clone() + 8))?
+                        ))
+                    }
+                })?;
+                writeln!(
+                    w,
+                    "{}morello_rt::cvt_bf16_f32_even_odd({}, {});",
+                    indent(depth),
+                    exprs[0],
+                    exprs[1]
+                )?;
+            }
+            CpuKernel::VectorDeinterleaveF32Bf16 => {
+                return Err(EmitError::Unsupported(String::from(
+                    "the Rust backend doesn't support the VectorDeinterleaveF32Bf16 kernel",
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Emits the bfloat16 dot product loops, which accumulate as their C lowerings do.
+    fn emit_bf16_dot_product_loop<W: Write>(
+        &mut self,
+        w: &mut W,
+        kernel_type: CpuKernel,
+        arguments: &[Param<Tgt>],
+        depth: usize,
+    ) -> Result<(), EmitError> {
+        let k = arguments[0].spec().shape()[1].get();
+        let strip = DOT_PRODUCT_BF16_STRIP_SIZE.get();
+        let step = self.namer.fresh_name();
+        let accums = (0..DOT_PRODUCT_BF16_ACCUM_COUNT)
+            .map(|_| self.namer.fresh_name())
+            .collect::<Vec<_>>();
+        writeln!(w, "{}// {kernel_type:?}", indent(depth))?;
+        for accum in &accums {
+            writeln!(w, "{}let mut {accum} = [0f32; 8];", indent(depth))?;
+        }
+        writeln!(
+            w,
+            "{}for {step} in (0..{k}).step_by({}) {{",
+            indent(depth),
+            DOT_PRODUCT_ACCUM_COUNT * strip
+        )?;
+        for i in 0..accums.len() {
+            let strip_offset = u32::try_from(i).unwrap() * strip;
+            let at = format!("{step} + {strip_offset}");
+            // Names for the (first, second) halves of the lhs and rhs strips, in f32.
+            let halves = [0, 1].map(|_| (self.namer.fresh_name(), self.namer.fresh_name()));
+            for (operand, (first, second)) in halves.iter().enumerate() {
+                writeln!(w, "{}let mut {first} = [0f32; 8];", indent(depth + 1))?;
+                writeln!(w, "{}let mut {second} = [0f32; 8];", indent(depth + 1))?;
+                let is_f32 = operand == 0 && kernel_type != CpuKernel::DotProductLoopBf16Bf16F32;
+                let source = self
+                    .param_args_to_indices(&arguments[operand..=operand], |_, a, b| {
+                        self.rs_slice_at(a, b, &at, strip)
+                    })?;
+                if is_f32 {
+                    writeln!(
+                        w,
+                        "{}{first}.copy_from_slice(&({})[..8]);",
+                        indent(depth + 1),
+                        source[0]
+                    )?;
+                    writeln!(
+                        w,
+                        "{}{second}.copy_from_slice(&({})[8..]);",
+                        indent(depth + 1),
+                        source[0]
+                    )?;
+                } else if kernel_type == CpuKernel::DotProductLoopF32Bf16F32 {
+                    writeln!(
+                        w,
+                        "{}morello_rt::cvt_bf16_f32_halves({}, &mut {first}, &mut {second});",
+                        indent(depth + 1),
+                        source[0]
+                    )?;
+                } else {
+                    writeln!(
+                        w,
+                        "{}morello_rt::cvt_bf16_f32_even_odd({}, &mut {first}, &mut {second});",
+                        indent(depth + 1),
+                        source[0]
+                    )?;
+                }
+            }
+
+            let [(lhs_first, lhs_second), (rhs_first, rhs_second)] = &halves;
+            // The Bf16Bf16 C lowering multiplies the odd-indexed values into accumulator `i`.
+            let (into_i, into_other) = if kernel_type == CpuKernel::DotProductLoopBf16Bf16F32 {
+                ((lhs_second, rhs_second), (lhs_first, rhs_first))
+            } else {
+                ((lhs_first, rhs_first), (lhs_second, rhs_second))
+            };
+            for ((lhs, rhs), accum) in [
+                (into_i, &accums[i]),
+                (into_other, &accums[(i + 2) % accums.len()]),
+            ] {
+                writeln!(
+                    w,
+                    "{}morello_rt::mul_add_f32x8(&mut {accum}, &{lhs}, &{rhs});",
+                    indent(depth + 1)
+                )?;
+            }
+        }
+        writeln!(w, "{}}}", indent(depth))?;
+        let out = self.param_args_to_indices(&arguments[2..], |_, a, b| Ok(self.rs_index(a, b)))?;
+        for accum in &accums {
+            writeln!(
+                w,
+                "{}{} += morello_rt::sum8(&{accum});",
+                indent(depth),
+                out[0]
+            )?;
+        }
+        Ok(())
+    }
+
+    fn emit_rolled_loop<W: Write>(
+        &mut self,
+        w: &mut W,
+        l: &Loop<Tgt>,
+        depth: usize,
+    ) -> Result<(), EmitError> {
+        let axes_to_emit = axis_order_and_steps(l).collect::<Vec<_>>();
+        let iter_var_names = axes_to_emit
+            .iter()
+            .map(|(axis, _)| (*axis, self.namer.fresh_name()))
+            .collect::<HashMap<_, _>>();
+        for loop_tile in &l.tiles {
+            for tt in loop_tile.tile.tile_dim_terms() {
+                let BufferVar::TileIdx(dim, _) = tt else {
+                    unreachable!();
+                };
+                let axis = loop_tile.axes[usize::from(dim)];
+                if let Some(axis_loop_iter_name) = iter_var_names.get(&axis) {
+                    self.loop_iter_bindings
+                        .insert(tt.clone(), Either::Left(axis_loop_iter_name.clone()));
+                }
+            }
+        }
+
+        if l.parallel {
+            writeln!(w, "{}// Parallel loop, run serially.", indent(depth))?;
+        }
+        for (axis, steps) in &axes_to_emit {
+            let var_name = iter_var_names.get(axis).unwrap();
+            writeln!(w, "{}for {var_name} in 0..{steps}usize {{", indent(depth))?;
+        }
+        self.emit(w, &l.body, depth + 1)?;
+        for _ in 0..axes_to_emit.len() {
+            writeln!(w, "{}}}", indent(depth))?;
+        }
+        Ok(())
+    }
+
+    fn emit_unrolled_loop<W: Write>(
+        &mut self,
+        w: &mut W,
+        l: &Loop<Tgt>,
+        depth: usize,
+    ) -> Result<(), EmitError> {
+        let axes_to_emit = axis_order_and_steps(l).collect::<Vec<_>>();
+        for pt in axes_to_emit
+            .iter()
+            .map(|&(_, steps)| 0..steps)
+            .multi_cartesian_product()
+        {
+            let axes_to_indices = axes_to_emit
+                .iter()
+                .zip(pt)
+                .map(|((axis, _), axis_step)| (*axis, axis_step))
+                .collect::<HashMap<_, _>>();
+            for loop_tile in &l.tiles {
+                for tt in loop_tile.tile.tile_dim_terms() {
+                    let BufferVar::TileIdx(dim, _) = &tt else {
+                        unreachable!();
+                    };
+                    let axis = loop_tile.axes[usize::from(*dim)];
+                    if let Some(axis_step) = axes_to_indices.get(&axis) {
+                        self.loop_iter_bindings.insert(
+                            tt.clone(),
+                            Either::Right(i32::try_from(*axis_step).unwrap()),
+                        );
+                    }
+                }
+            }
+            self.emit(w, &l.body, depth)?;
+        }
+        Ok(())
+    }
+
+    fn param_args_to_indices<F>(
+        &self,
+        arguments: &[Param<Tgt>],
+        f: F,
+    ) -> Result<Vec<String>, EmitError>
+    where
+        F: Fn(usize, &CBuffer, &NonAffineExpr<BufferVar>) -> Result<String, EmitError>,
+    {
+        arguments
+            .iter()
+            .enumerate()
+            .map(|(idx, arg)| {
+                let backing_tensor = arg.backing_tensor(&self.param_bindings).unwrap();
+                let buffer = self.name_env.get(backing_tensor).unwrap();
+                let buffer_indexing_expr =
+                    zero_points(arg.make_buffer_indexing_expr(&self.param_bindings));
+                f(idx, buffer, &buffer_indexing_expr)
+            })
+            .collect()
+    }
+
+    fn sub_expr_bindings(&self, unbound_expr: NonAffineExpr<BufferVar>) -> NonAffineExpr<CExprVar> {
+        unbound_expr.map_vars(&mut |v| match self.loop_iter_bindings.get(&v) {
+            Some(Either::Left(var_name)) => {
+                AffineForm::from(NonAffine::Leaf(CExprVar::CName(var_name.clone())))
+            }
+            Some(Either::Right(c)) => NonAffineExpr::constant(*c),
+            None => AffineForm::from(NonAffine::Leaf(CExprVar::Buffer(v))),
+        })
+    }
+
+    /// Returns a Rust place expression for the value at `expr` in `buffer`.
+    fn rs_index(&self, buffer: &CBuffer, expr: &NonAffineExpr<BufferVar>) -> String {
+        match buffer {
+            CBuffer::ValueVar { name, .. } => name.clone(),
+            CBuffer::VecVars { .. } => {
+                let (inner, offset) =
+                    buffer.inner_vec_from_expr(&self.sub_expr_bindings(expr.clone()));
+                format!("{}[{offset}]", inner.name().unwrap())
+            }
+            _ => format!(
+                "{}[{}]",
+                buffer.name().unwrap(),
+                expr_to_c(&self.sub_expr_bindings(expr.clone()))
+            ),
+        }
+    }
+
+    /// Returns the name of the vector variable holding the vector at `expr`.
+    fn rs_vec(
+        &self,
+        buffer: &CBuffer,
+        expr: &NonAffineExpr<BufferVar>,
+    ) -> Result<String, EmitError> {
+        match buffer {
+            CBuffer::SingleVecVar { name, .. } => {
+                assert!(expr == 0, "expr must be 0, but was: {expr:?}");
+                Ok(name.clone())
+            }
+            CBuffer::VecVars { .. } => {
+                let (inner, offset) =
+                    buffer.inner_vec_from_expr(&self.sub_expr_bindings(expr.clone()));
+                debug_assert_eq!(offset, 0);
+                Ok(inner.name().unwrap().to_string())
+            }
+            _ => Err(EmitError::Unsupported(format!(
+                "the Rust backend can only pass vector registers to vector kernels, not `{}`",
+                buffer.name().unwrap()
+            ))),
+        }
+    }
+
+    /// Returns a range expression for `len` values starting `extra` values after `expr`.
+    fn rs_range(
+        &self,
+        buffer: &CBuffer,
+        expr: &NonAffineExpr<BufferVar>,
+        extra: u32,
+        len: u32,
+    ) -> Result<String, EmitError> {
+        self.rs_range_at(buffer, expr, &extra.to_string(), len)
+    }
+
+    fn rs_range_at(
+        &self,
+        buffer: &CBuffer,
+        expr: &NonAffineExpr<BufferVar>,
+        extra: &str,
+        len: u32,
+    ) -> Result<String, EmitError> {
+        let (name, start) = match buffer {
+            CBuffer::SingleVecVar { .. } | CBuffer::VecVars { .. } => {
+                let subbed = self.sub_expr_bindings(expr.clone());
+                let (inner, offset) = match buffer {
+                    CBuffer::VecVars { .. } => buffer.inner_vec_from_expr(&subbed),
+                    _ => (buffer, usize::try_from(subbed.1).unwrap()),
+                };
+                (inner.name().unwrap().to_string(), offset.to_string())
+            }
+            CBuffer::ValueVar { name, .. } => {
+                return Err(EmitError::Unsupported(format!(
+                    "the Rust backend can't take a slice of the scalar register `{name}`"
+                )));
+            }
+            _ => (
+                buffer.name().unwrap().to_string(),
+                expr_to_c(&self.sub_expr_bindings(expr.clone())),
+            ),
+        };
+        let start = if extra == "0" {
+            start
+        } else {
+            format!("{start} + {extra}")
+        };
+        Ok(format!("{name}[{start}..{start} + {len}]"))
+    }
+
+    fn rs_slice(
+        &self,
+        buffer: &CBuffer,
+        expr: &NonAffineExpr<BufferVar>,
+        extra: u32,
+        len: u32,
+    ) -> Result<String, EmitError> {
+        Ok(format!("&{}", self.rs_range(buffer, expr, extra, len)?))
+    }
+
+    fn rs_slice_mut(
+        &self,
+        buffer: &CBuffer,
+        expr: &NonAffineExpr<BufferVar>,
+        extra: u32,
+        len: u32,
+    ) -> Result<String, EmitError> {
+        Ok(format!("&mut {}", self.rs_range(buffer, expr, extra, len)?))
+    }
+
+    fn rs_slice_at(
+        &self,
+        buffer: &CBuffer,
+        expr: &NonAffineExpr<BufferVar>,
+        extra: &str,
+        len: u32,
+    ) -> Result<String, EmitError> {
+        Ok(format!("&{}", self.rs_range_at(buffer, expr, extra, len)?))
+    }
+}
+
+/// Emits and builds Rust implementations of Impls. See [RustCodeGenerator].
+pub trait RustCodeGen<Tgt: Target> {
+    /// Emit a Rust source file defining the kernel as a function named `symbol`.
+    ///
+    /// If `include_main` is set, the file also defines a `main` which, like the C backend's, can
+    /// be run by a [BuiltArtifact].
+    fn emit_rust<W: Write>(
+        &self,
+        symbol: &str,
+        include_main: bool,
+        include_impl: Option<ImplPrintStyle>,
+        out: &mut W,
+    ) -> Result<(), EmitError>;
+
+    /// Compile a Rust source file with a `main` using `rustc`.
+    fn build_rust(&self) -> Result<BuiltArtifact, BuildError>;
+}
+
+impl<Tgt: CpuTarget> RustCodeGen<Tgt> for ImplNode<Tgt> {
+    fn emit_rust<W: Write>(
+        &self,
+        symbol: &str,
+        include_main: bool,
+        include_impl: Option<ImplPrintStyle>,
+        out: &mut W,
+    ) -> Result<(), EmitError> {
+        let top_arg_tensors = self
+            .parameters()
+            .map(|parameter| Rc::new(Tensor::new(parameter.clone())))
+            .collect::<Vec<_>>();
+        let mut generator = RustCodeGenerator::<Tgt>::new();
+        generator.kernel_name = symbol.to_string();
+        if let Some(impl_style) = include_impl {
+            generator.emit_impl_comment(self, impl_style, out)?;
+            writeln!(out)?;
+        }
+        generator.emit_prelude(out)?;
+        writeln!(out)?;
+        generator.emit_kernel(self, &top_arg_tensors, out)?;
+        if include_main {
+            writeln!(out)?;
+            generator.emit_main(&top_arg_tensors, out)?;
+        }
+        Ok(())
+    }
+
+    fn build_rust(&self) -> Result<BuiltArtifact, BuildError> {
+        let scratch_dir = tempdir()?;
+        let source_path = scratch_dir.path().join("main.rs");
+        let binary_path = scratch_dir.path().join("a.out");
+
+        let mut source = String::new();
+        self.emit_rust("kernel", true, None, &mut source)?;
+        std::fs::write(&source_path, source)?;
+
+        let rustc_proc = Command::new(rustc_path())
+            .args(RUSTC_FLAGS)
+            .args(match Tgt::target_id() {
+                TargetId::X86 => X86_RUSTC_FLAGS.as_slice(),
+                TargetId::Arm => ARM_RUSTC_FLAGS.as_slice(),
+            })
+            .arg("-o")
+            .arg(&binary_path)
+            .arg(&source_path)
+            .output()?;
+        let compiler_warnings = check_compiler_output(&rustc_proc)?;
+
+        Ok(BuiltArtifact::new(
+            binary_path,
+            None,
+            Some(scratch_dir),
+            self.parameters().map(|p| p.dtype()).collect(),
+            compiler_warnings,
+        ))
+    }
+}
+
+impl<'a, Tgt: Target> Default for RustCodeGenerator<'a, Tgt> {
+    fn default() -> Self {
+        RustCodeGenerator {
+            namer: Default::default(),
+            name_env: Default::default(),
+            loop_iter_bindings: Default::default(),
+            param_bindings: Default::default(),
+            kernel_name: String::from("kernel"),
+        }
+    }
+}
+
+/// Returns the Rust type used for values of `dtype`. bfloat16s are stored as their bits.
+pub fn rust_type(dtype: Dtype) -> &'static str {
+    match dtype {
+        Dtype::Uint8 => "u8",
+        Dtype::Sint8 => "i8",
+        Dtype::Uint16 | Dtype::Bfloat16 => "u16",
+        Dtype::Sint16 => "i16",
+        Dtype::Uint32 => "u32",
+        Dtype::Sint32 => "i32",
+        Dtype::Float32 => "f32",
+    }
+}
+
+fn rust_zero(dtype: Dtype) -> String {
+    format!("0{}", rust_type(dtype))
+}
+
+/// Returns a Rust array expression of `shape`'s dimensions as `u32`s.
+fn rs_shape(shape: &[DimSize]) -> String {
+    format!("[{}]", shape.iter().map(|d| format!("{d}u32")).join(", "))
+}
+
+/// Returns a statement adding `a * b` to `c`.
+///
+/// Integers wrap, matching the C backend's unsigned arithmetic. Bfloat16 accumulators are widened
+/// to `f32` for the addition and rounded back, as C does for `__bf16`.
+fn mult_add(dtypes: &[Dtype], a: &str, b: &str, c: &str) -> String {
+    let widen_operands = || {
+        [(dtypes[0], a), (dtypes[1], b)].map(|(dtype, e)| match dtype {
+            Dtype::Float32 => e.to_string(),
+            Dtype::Bfloat16 => format!("morello_rt::bf16_to_f32({e})"),
+            _ => format!("({e} as f32)"),
+        })
+    };
+    match dtypes[2] {
+        Dtype::Float32 => {
+            let [a, b] = widen_operands();
+            format!("{c} += {a} * {b}")
+        }
+        Dtype::Bfloat16 => {
+            let [a, b] = widen_operands();
+            format!("{c} = morello_rt::f32_to_bf16(morello_rt::bf16_to_f32({c}) + {a} * {b})")
+        }
+        out_dtype => format!(
+            "{c} = ({c} as i64).wrapping_add(({a} as i64).wrapping_mul({b} as i64)) as {}",
+            rust_type(out_dtype)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::row_major;
+    use crate::lspec;
+    use crate::scheduling_sugar::{SchedulingSugar, Subschedule};
+    use crate::spec::{LogicalSpec, PrimitiveBasics, PrimitiveSpecType, Spec};
+    use crate::target::{
+        CpuMemoryLevel::{GL, RF, VRF},
+        X86Target,
+    };
+    use crate::tensorspec::TensorSpecAux;
+    use nonzero::nonzero as nz;
+    #[cfg(feature = "verification")]
+    use {
+        crate::db::{Database, InMemoryDatabase},
+        crate::search::top_down,
+        crate::spec::arb_canonical_spec,
+        proptest::prelude::*,
+    };
+
+    fn zero_2x2() -> (Spec<X86Target>, ImplNode<X86Target>) {
+        let spec = Spec::<X86Target>(
+            lspec!(Zero([2, 2], (u32, GL, row_major(2)))),
+            X86Target::max_mem(),
+        );
+        let imp = spec
+            .tile_out(&[1, 1], false)
+            .move_param(0, RF, row_major(2), None)
+            .subschedule(&[0], &|z| z.place(CpuKernel::MemsetZero))
+            .subschedule(&[1], &|m| m.place(CpuKernel::ValueAssign));
+        (spec, imp)
+    }
+
+    /// Synthesizes `spec`, then builds the Impl as Rust and checks it. Does nothing if no Impl
+    /// fits within `spec`'s memory limits or the Rust backend can't emit the Impl.
+    #[cfg(feature = "verification")]
+    fn check_synthesized_rust(spec: &Spec<X86Target>) -> Result<(), TestCaseError> {
+        let db = InMemoryDatabase::new(false, 1, None);
+        top_down(&db, spec, 1, Some(nz!(1usize)));
+        let Some(imp) = db.get_impl(spec).and_then(|impls| impls.into_iter().next()) else {
+            return Ok(());
+        };
+        let artifact = match imp.build_rust() {
+            Ok(artifact) => artifact,
+            Err(BuildError::Unsupported(_)) => return Ok(()),
+            Err(err) => return Err(TestCaseError::fail(format!("{spec}: {err}"))),
+        };
+        if let Err(failure) = artifact.check_correctness(spec) {
+            prop_assert!(false, "{spec}: {failure}");
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "verification")]
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(8))]
+
+        #[test]
+        fn test_build_rust_synthesized_spec_is_correct(
+            spec in arb_canonical_spec::<X86Target>(Some(nz!(2u32)), Some(2048))
+        ) {
+            check_synthesized_rust(&spec)?;
+        }
+
+        /// Matmuls wide enough for vector kernels, with `f32` and `bf16` operands and outputs.
+        #[test]
+        fn test_build_rust_synthesized_vector_matmul_is_correct(
+            m in 1..=2u32,
+            k in prop_oneof![Just(8u32), Just(16)],
+            dtypes in proptest::array::uniform3(
+                prop_oneof![Just(Dtype::Float32), Just(Dtype::Bfloat16)]
+            )
+        ) {
+            let [lhs, rhs, out] = dtypes;
+            let spec = Spec::<X86Target>(
+                lspec!(Matmul(
+                    [m, k, 8],
+                    (lhs, GL, row_major(2)),
+                    (rhs, GL, row_major(2)),
+                    (out, GL, row_major(2))
+                )),
+                X86Target::max_mem(),
+            );
+            check_synthesized_rust(&spec)?;
+        }
+    }
+
+    #[test]
+    fn test_emit_rust_takes_slices() {
+        let (_, imp) = zero_2x2();
+        let mut source = String::new();
+        imp.emit_rust("zero_2x2", false, None, &mut source).unwrap();
+        assert!(source.contains("mod morello_rt {"), "{source}");
+        assert!(
+            source.contains("pub fn zero_2x2(\n  aa: &mut [u32],\n)"),
+            "{source}"
+        );
+        assert!(!source.contains("fn main("), "{source}");
+        let kernel = &source[source.find("pub fn zero_2x2(").unwrap()..];
+        assert!(!kernel.contains("unsafe"), "{kernel}");
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_build_rust_zero_is_correct() {
+        let (spec, imp) = zero_2x2();
+        let artifact = imp.build_rust().unwrap();
+        artifact.check_correctness(&spec).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_build_rust_text_and_binary_formats_agree() {
+        use crate::verification::{DynArray, TensorFormat};
+
+        let (_, imp) = zero_2x2();
+        let artifact = imp.build_rust().unwrap();
+        let arguments = [DynArray::Uint32(ndarray::ArrayD::from_elem(
+            ndarray::IxDyn(&[2, 2]),
+            7,
+        ))];
+        let text = artifact
+            .run_with_input_data_as(&arguments, TensorFormat::Text)
+            .unwrap();
+        let binary = artifact
+            .run_with_input_data_as(&arguments, TensorFormat::Binary)
+            .unwrap();
+        assert!(text == binary);
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_build_rust_scalar_matmul_is_correct() {
+        let spec = Spec::<X86Target>(
+            lspec!(Matmul(
+                [2, 3, 2],
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2))
+            )),
+            X86Target::max_mem(),
+        );
+        let imp = spec
+            .to_accum()
+            .subschedule(&[0], &|z| {
+                z.tile_out(&[1, 1], false)
+                    .move_param(0, RF, row_major(2), None)
+                    .subschedule(&[0], &|z| z.place(CpuKernel::MemsetZero))
+                    .subschedule(&[1], &|m| m.place(CpuKernel::ValueAssign))
+            })
+            .subschedule(&[1], &|s| {
+                s.tile_out(&[1, 1], false)
+                    .split(1)
+                    .move_param(0, RF, row_major(2), None)
+                    .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                    .subschedule(&[1], &|s| {
+                        s.move_param(1, RF, row_major(2), None)
+                            .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                            .subschedule(&[1], &|s| {
+                                s.move_param(2, RF, row_major(2), None)
+                                    .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                                    .subschedule(&[1], &|s| s.place(CpuKernel::MultAdd))
+                                    .subschedule(&[2], &|s| s.place(CpuKernel::ValueAssign))
+                            })
+                    })
+            });
+        let artifact = imp.build_rust().unwrap();
+        artifact.check_correctness(&spec).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "verification")]
+    fn test_build_rust_vector_zero_is_correct() {
+        let spec = Spec::<X86Target>(
+            lspec!(Zero([2, 8], (u32, GL, row_major(2)))),
+            X86Target::max_mem(),
+        );
+        let imp = spec
+            .move_param(0, VRF, row_major(2), Some(nz!(8u32)))
+            .subschedule(&[0], &|z| z.place(CpuKernel::VectorZero))
+            .subschedule(&[1], &|m| {
+                m.tile_out(&[1, 8], false).place(CpuKernel::VectorAssign)
+            });
+        let artifact = imp.build_rust().unwrap();
+        artifact.check_correctness(&spec).unwrap();
+    }
+}
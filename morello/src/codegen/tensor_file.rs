@@ -0,0 +1,80 @@
+//! The binary tensor file format read and written by generated programs given `--binary`.
+//!
+//! A file is a header followed by the tensor's values in row-major order. The header is the
+//! four bytes of [TENSOR_FILE_MAGIC], the [dtype_code] of the values, the rank, and then each
+//! dimension's size. Header fields after the magic and all values are little-endian, and header
+//! fields are 32-bit unsigned integers.
+
+use crate::common::Dtype;
+
+pub const TENSOR_FILE_MAGIC: [u8; 4] = *b"MRLT";
+
+/// Returns the number identifying `dtype` in a tensor file header.
+pub const fn dtype_code(dtype: Dtype) -> u32 {
+    match dtype {
+        Dtype::Uint8 => 0,
+        Dtype::Sint8 => 1,
+        Dtype::Uint16 => 2,
+        Dtype::Sint16 => 3,
+        Dtype::Uint32 => 4,
+        Dtype::Sint32 => 5,
+        Dtype::Float32 => 6,
+        Dtype::Bfloat16 => 7,
+    }
+}
+
+/// Returns the size in bytes of the header of a tensor file of the given rank.
+pub fn header_len(rank: usize) -> usize {
+    TENSOR_FILE_MAGIC.len() + 4 * (2 + rank)
+}
+
+/// Returns the header of a tensor file holding a tensor of `dtype` and `shape`.
+pub fn encode_header(dtype: Dtype, shape: &[u32]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(header_len(shape.len()));
+    header.extend_from_slice(&TENSOR_FILE_MAGIC);
+    header.extend_from_slice(&dtype_code(dtype).to_le_bytes());
+    header.extend_from_slice(&u32::try_from(shape.len()).unwrap().to_le_bytes());
+    for &dim in shape {
+        header.extend_from_slice(&dim.to_le_bytes());
+    }
+    header
+}
+
+/// Parses the header at the start of `bytes`, returning the dtype code and shape.
+///
+/// Returns `None` if `bytes` doesn't start with a well-formed header.
+pub fn decode_header(bytes: &[u8]) -> Option<(u32, Vec<u32>)> {
+    let rest = bytes.strip_prefix(&TENSOR_FILE_MAGIC)?;
+    let mut fields = rest
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()));
+    let code = fields.next()?;
+    let rank = usize::try_from(fields.next()?).ok()?;
+    let shape = fields.by_ref().take(rank).collect::<Vec<_>>();
+    if shape.len() != rank {
+        return None;
+    }
+    Some((code, shape))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trips() {
+        let header = encode_header(Dtype::Float32, &[3, 1, 4]);
+        assert_eq!(header.len(), header_len(3));
+        assert_eq!(
+            decode_header(&header),
+            Some((dtype_code(Dtype::Float32), vec![3, 1, 4]))
+        );
+    }
+
+    #[test]
+    fn test_decode_header_rejects_truncated_and_unmarked_input() {
+        let header = encode_header(Dtype::Uint8, &[2, 2]);
+        assert_eq!(decode_header(&header[..header.len() - 1]), None);
+        assert_eq!(decode_header(&header[1..]), None);
+    }
+}
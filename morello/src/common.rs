@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::num::NonZeroU32;
+use std::str::FromStr;
 
 use crate::grid::canon::CanonicalBimap;
 use crate::grid::general::BiMap;
@@ -10,7 +11,7 @@ pub type Shape = Vec<DimSize>;
 pub type Contig = u8;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
-#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[cfg_attr(any(test, feature = "proptest"), derive(proptest_derive::Arbitrary))]
 pub enum Dtype {
     Uint8,
     Sint8,
@@ -63,6 +64,25 @@ impl Display for Dtype {
     }
 }
 
+impl FromStr for Dtype {
+    type Err = ();
+
+    /// Parses the names written by [Dtype]'s [Display] implementation.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "u8" => Ok(Dtype::Uint8),
+            "i8" => Ok(Dtype::Sint8),
+            "u16" => Ok(Dtype::Uint16),
+            "i16" => Ok(Dtype::Sint16),
+            "u32" => Ok(Dtype::Uint32),
+            "i32" => Ok(Dtype::Sint32),
+            "f32" => Ok(Dtype::Float32),
+            "bf16" => Ok(Dtype::Bfloat16),
+            _ => Err(()),
+        }
+    }
+}
+
 impl BiMap for DtypeBimap {
     type Domain = Dtype;
     type Codomain = u8;
@@ -0,0 +1,724 @@
+//! Executes [ImplNode] trees directly, without generating or compiling code.
+//!
+//! Buffers are allocated for each [Tensor] and indexed with the same buffer indexing expressions
+//! used by code generation, so a [Layout](crate::layout::Layout) or tiling bug shows up here as
+//! well as in generated code, while a bug in code generation alone does not. Kernels are
+//! evaluated with reference semantics rather than by emulating their vector instructions.
+
+use crate::common::Dtype;
+use crate::expr::{NonAffine, NonAffineExpr, Term};
+use crate::imp::blocks::Block;
+use crate::imp::kernels::KernelApp;
+use crate::imp::loops::Loop;
+use crate::imp::moves::TensorOrCacheView;
+use crate::imp::{Impl, ImplNode};
+use crate::layout::BufferVar;
+use crate::opaque_symbol::OpaqueSymbol;
+use crate::target::{CpuKernel, CpuTarget};
+use crate::verification::DynArray;
+use crate::views::{Param, Tensor, View};
+
+use itertools::Itertools;
+use ndarray::prelude::*;
+
+use std::collections::HashMap;
+
+#[derive(thiserror::Error, Debug)]
+pub enum InterpretError {
+    #[error("Impl contains an unimplemented Spec: {0}")]
+    MissingImpl(String),
+    #[error("Interpreting {0} is not supported")]
+    Unsupported(&'static str),
+    #[error("Index {index} is out of bounds for a buffer of {len} values")]
+    OutOfBounds { index: i32, len: usize },
+}
+
+struct Interpreter<'a, Tgt: CpuTarget> {
+    param_bindings: HashMap<Param<Tgt>, &'a dyn View<Tgt = Tgt>>,
+    loop_iter_bindings: HashMap<BufferVar, i32>,
+    /// The buffer backing each [Tensor] in scope, in physical (layout) order.
+    buffers: HashMap<OpaqueSymbol, DynArray<Ix1>>,
+}
+
+/// Execute `imp` on copies of `arguments` and return the resulting output.
+///
+/// Like [crate::codegen::BuiltArtifact::run_with_input_data], arguments (including the output)
+/// are given in row-major order and the output is returned in row-major order. Unlike generated
+/// code, parameters need not be row-major; arguments are copied into buffers with each
+/// parameter's layout.
+pub fn interpret<Tgt: CpuTarget>(
+    imp: &ImplNode<Tgt>,
+    arguments: &[DynArray<IxDyn>],
+) -> Result<DynArray<IxDyn>, InterpretError> {
+    assert_eq!(arguments.len(), usize::from(imp.parameter_count()));
+    let top_arg_tensors = imp
+        .parameters()
+        .map(|parameter| Tensor::new(parameter.clone()))
+        .collect::<Vec<_>>();
+    let tensors_as_trait_obj_ptrs = top_arg_tensors
+        .iter()
+        .map(|tensor| tensor as &dyn View<Tgt = Tgt>)
+        .collect::<Vec<_>>();
+
+    let mut interpreter = Interpreter {
+        param_bindings: HashMap::new(),
+        loop_iter_bindings: HashMap::new(),
+        buffers: HashMap::new(),
+    };
+    imp.bind(&tensors_as_trait_obj_ptrs, &mut interpreter.param_bindings);
+
+    for (tensor, argument) in top_arg_tensors.iter().zip(arguments) {
+        assert_eq!(argument.dtype(), tensor.spec().dtype());
+        interpreter.buffers.insert(
+            tensor.identifier(),
+            new_buffer(tensor.spec().dtype(), tensor.spec().volume().get()),
+        );
+        interpreter.scatter(tensor, argument)?;
+    }
+
+    interpreter.interpret(imp)?;
+    interpreter.gather(top_arg_tensors.last().unwrap())
+}
+
+impl<'a, Tgt: CpuTarget> Interpreter<'a, Tgt> {
+    fn interpret(&mut self, imp: &ImplNode<Tgt>) -> Result<(), InterpretError> {
+        match imp {
+            ImplNode::Loop(l) => self.interpret_loop(l),
+            ImplNode::MoveLet(move_let) => {
+                if let TensorOrCacheView::Tensor(tensor) = &move_let.introduced {
+                    let spec = tensor.spec();
+                    self.buffers.insert(
+                        tensor.identifier(),
+                        new_buffer(spec.dtype(), spec.volume().get()),
+                    );
+                }
+                for child in imp.children() {
+                    self.interpret(child)?;
+                }
+                if let TensorOrCacheView::Tensor(tensor) = &move_let.introduced {
+                    self.buffers.remove(&tensor.identifier());
+                }
+                Ok(())
+            }
+            ImplNode::Block(Block { stages, .. }) => {
+                for stage in stages {
+                    self.interpret(stage)?;
+                }
+                Ok(())
+            }
+            ImplNode::Pipeline(_) => Err(InterpretError::Unsupported("Pipeline")),
+            ImplNode::SpecApp(p) => Err(InterpretError::MissingImpl(p.0.to_string())),
+            ImplNode::Kernel(KernelApp {
+                kernel_type,
+                arguments,
+                spec: _,
+            }) => self.interpret_kernel(*kernel_type, arguments),
+        }
+    }
+
+    /// Runs the loop body once per step, sequentially, even if the loop is parallel.
+    fn interpret_loop(&mut self, l: &Loop<Tgt>) -> Result<(), InterpretError> {
+        let axes_and_steps = l
+            .tiles
+            .iter()
+            .flat_map(|loop_tile| {
+                loop_tile.axes.iter().enumerate().map(move |(dim, &axis)| {
+                    (axis, loop_tile.tile.steps_dim(dim.try_into().unwrap()))
+                })
+            })
+            .unique_by(|&(axis, _)| axis)
+            .collect::<Vec<_>>();
+
+        for pt in axes_and_steps
+            .iter()
+            .map(|&(_, steps)| 0..steps)
+            .multi_cartesian_product()
+        {
+            for loop_tile in &l.tiles {
+                for tt in loop_tile.tile.tile_dim_terms() {
+                    let BufferVar::TileIdx(dim, _) = &tt else {
+                        unreachable!();
+                    };
+                    let axis = loop_tile.axes[usize::from(*dim)];
+                    let axis_idx = axes_and_steps.iter().position(|&(a, _)| a == axis).unwrap();
+                    self.loop_iter_bindings
+                        .insert(tt, i32::try_from(pt[axis_idx]).unwrap());
+                }
+            }
+            self.interpret(&l.body)?;
+        }
+        Ok(())
+    }
+
+    fn interpret_kernel(
+        &mut self,
+        kernel_type: CpuKernel,
+        arguments: &[Param<Tgt>],
+    ) -> Result<(), InterpretError> {
+        match kernel_type {
+            CpuKernel::MemsetZero | CpuKernel::VectorZero => {
+                let mut out = self.gather(&arguments[0])?;
+                out.zero();
+                self.scatter(&arguments[0], &out)
+            }
+            CpuKernel::ValueAssign
+            | CpuKernel::VectorAssign
+            | CpuKernel::PhysicalTransposeByte128
+            | CpuKernel::PhysicalTransposeByte256 => {
+                // Physical transposes move values into a buffer with a different layout, which
+                // leaves their logical values unchanged.
+                let src = self.gather(&arguments[0])?;
+                self.scatter(&arguments[1], &src)
+            }
+            CpuKernel::CastBf16F32
+            | CpuKernel::VectorCastBf16F32
+            | CpuKernel::VectorInterleaveBf16F32
+            | CpuKernel::VectorDeinterleaveF32Bf16 => {
+                let src = self.gather(&arguments[0])?;
+                self.scatter(&arguments[1], &cast(&src, arguments[1].spec().dtype()))
+            }
+            CpuKernel::MultAdd
+            | CpuKernel::BroadcastVecMultAdd
+            | CpuKernel::BroadcastVecMultAddBf16F32
+            | CpuKernel::TwoVecBroadcastVecMultAddU8S8S16
+            | CpuKernel::DotProductLoop
+            | CpuKernel::DotProductLoopBf16Bf16F32
+            | CpuKernel::DotProductLoopF32Bf16F32
+            | CpuKernel::DotProductLoopF32InterleavedBf16F32 => {
+                // Each of these accumulates a matrix multiplication into its output, differing
+                // only in operand shapes, dtypes, and instructions.
+                let [lhs, rhs, out] = [0, 1, 2].map(|i| {
+                    self.gather(&arguments[i]).map(|a| {
+                        a.into_dimensionality::<Ix2>()
+                            .expect("kernel operands should be rank 2")
+                    })
+                });
+                let mut out = out?;
+                lhs?.dot_inplace(&rhs?, &mut out);
+                self.scatter(&arguments[2], &out.into_dyn())
+            }
+        }
+    }
+
+    /// Returns the buffer offsets of each value of `view`, in row-major order, along with the
+    /// identifier of its backing [Tensor].
+    fn offsets(
+        &self,
+        view: &dyn View<Tgt = Tgt>,
+    ) -> Result<(OpaqueSymbol, Vec<usize>), InterpretError> {
+        let tensor = view
+            .backing_tensor(&self.param_bindings)
+            .expect("view should have a backing tensor");
+        let buffer_len = self.buffers[&tensor.identifier()].len();
+        let expr = view.make_buffer_indexing_expr(&self.param_bindings);
+        let shape = view
+            .shape()
+            .iter()
+            .map(|d| usize::try_from(d.get()).unwrap())
+            .collect::<Vec<_>>();
+        let offsets = ndarray::indices(IxDyn(&shape))
+            .into_iter()
+            .map(|pt| {
+                let index = eval_expr(&expr, &|v| match v {
+                    BufferVar::Pt(dim, _) => i32::try_from(pt[usize::from(*dim)]).unwrap(),
+                    BufferVar::TileIdx(_, _) => *self
+                        .loop_iter_bindings
+                        .get(v)
+                        .expect("tile index should be bound by an enclosing loop"),
+                });
+                usize::try_from(index)
+                    .ok()
+                    .filter(|&i| i < buffer_len)
+                    .ok_or(InterpretError::OutOfBounds {
+                        index,
+                        len: buffer_len,
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((tensor.identifier(), offsets))
+    }
+
+    /// Reads the values of `view` into a new, row-major array.
+    fn gather(&self, view: &dyn View<Tgt = Tgt>) -> Result<DynArray<IxDyn>, InterpretError> {
+        let (tensor_id, offsets) = self.offsets(view)?;
+        let shape = IxDyn(
+            &view
+                .shape()
+                .iter()
+                .map(|d| usize::try_from(d.get()).unwrap())
+                .collect::<Vec<_>>(),
+        );
+        let gathered = match &self.buffers[&tensor_id] {
+            DynArray::Uint8(b) => DynArray::Uint8(gather_values(b, &offsets, shape)),
+            DynArray::Sint8(b) => DynArray::Sint8(gather_values(b, &offsets, shape)),
+            DynArray::Uint16(b) => DynArray::Uint16(gather_values(b, &offsets, shape)),
+            DynArray::Sint16(b) => DynArray::Sint16(gather_values(b, &offsets, shape)),
+            DynArray::Uint32(b) => DynArray::Uint32(gather_values(b, &offsets, shape)),
+            DynArray::Sint32(b) => DynArray::Sint32(gather_values(b, &offsets, shape)),
+            DynArray::Float32(b) => DynArray::Float32(gather_values(b, &offsets, shape)),
+            DynArray::Bfloat16(b) => DynArray::Bfloat16(gather_values(b, &offsets, shape)),
+        };
+        Ok(gathered)
+    }
+
+    /// Writes the values of the row-major `values` into `view`.
+    fn scatter(
+        &mut self,
+        view: &dyn View<Tgt = Tgt>,
+        values: &DynArray<IxDyn>,
+    ) -> Result<(), InterpretError> {
+        let (tensor_id, offsets) = self.offsets(view)?;
+        match (self.buffers.get_mut(&tensor_id).unwrap(), values) {
+            (DynArray::Uint8(b), DynArray::Uint8(v)) => scatter_values(b, &offsets, v),
+            (DynArray::Sint8(b), DynArray::Sint8(v)) => scatter_values(b, &offsets, v),
+            (DynArray::Uint16(b), DynArray::Uint16(v)) => scatter_values(b, &offsets, v),
+            (DynArray::Sint16(b), DynArray::Sint16(v)) => scatter_values(b, &offsets, v),
+            (DynArray::Uint32(b), DynArray::Uint32(v)) => scatter_values(b, &offsets, v),
+            (DynArray::Sint32(b), DynArray::Sint32(v)) => scatter_values(b, &offsets, v),
+            (DynArray::Float32(b), DynArray::Float32(v)) => scatter_values(b, &offsets, v),
+            (DynArray::Bfloat16(b), DynArray::Bfloat16(v)) => scatter_values(b, &offsets, v),
+            _ => panic!("Mismatched types"),
+        }
+        Ok(())
+    }
+}
+
+fn new_buffer(dtype: Dtype, volume: u32) -> DynArray<Ix1> {
+    let volume = usize::try_from(volume).unwrap();
+    match dtype {
+        Dtype::Uint8 => DynArray::Uint8(Array::zeros(volume)),
+        Dtype::Sint8 => DynArray::Sint8(Array::zeros(volume)),
+        Dtype::Uint16 => DynArray::Uint16(Array::zeros(volume)),
+        Dtype::Sint16 => DynArray::Sint16(Array::zeros(volume)),
+        Dtype::Uint32 => DynArray::Uint32(Array::zeros(volume)),
+        Dtype::Sint32 => DynArray::Sint32(Array::zeros(volume)),
+        Dtype::Float32 => DynArray::Float32(Array::zeros(volume)),
+        Dtype::Bfloat16 => DynArray::Bfloat16(Array::from_elem(volume, half::bf16::ZERO)),
+    }
+}
+
+fn gather_values<T: Copy>(buffer: &Array1<T>, offsets: &[usize], shape: IxDyn) -> ArrayD<T> {
+    Array::from_iter(offsets.iter().map(|&o| buffer[o]))
+        .into_shape(shape)
+        .unwrap()
+}
+
+fn scatter_values<T: Copy>(buffer: &mut Array1<T>, offsets: &[usize], values: &ArrayD<T>) {
+    debug_assert_eq!(offsets.len(), values.len());
+    for (&o, &v) in offsets.iter().zip(values.iter()) {
+        buffer[o] = v;
+    }
+}
+
+fn cast(array: &DynArray<IxDyn>, dtype: Dtype) -> DynArray<IxDyn> {
+    match dtype {
+        Dtype::Uint8 => DynArray::Uint8(array.saturating_cast()),
+        Dtype::Sint8 => DynArray::Sint8(array.saturating_cast()),
+        Dtype::Uint16 => DynArray::Uint16(array.saturating_cast()),
+        Dtype::Sint16 => DynArray::Sint16(array.saturating_cast()),
+        Dtype::Uint32 => DynArray::Uint32(array.saturating_cast()),
+        Dtype::Sint32 => DynArray::Sint32(array.saturating_cast()),
+        Dtype::Float32 => DynArray::Float32(array.saturating_cast()),
+        Dtype::Bfloat16 => DynArray::Bfloat16(array.saturating_cast()),
+    }
+}
+
+fn eval_expr(expr: &NonAffineExpr<BufferVar>, var_value: &impl Fn(&BufferVar) -> i32) -> i32 {
+    expr.0
+        .iter()
+        .map(|Term(coef, sym)| coef * eval_non_affine(sym, var_value))
+        .sum::<i32>()
+        + expr.1
+}
+
+fn eval_non_affine(subexpr: &NonAffine<BufferVar>, var_value: &impl Fn(&BufferVar) -> i32) -> i32 {
+    match subexpr {
+        NonAffine::Constant(c) => *c,
+        NonAffine::Leaf(v) => var_value(v),
+        NonAffine::FloorDiv(e, d) => eval_expr(e, var_value).div_euclid(i32::try_from(*d).unwrap()),
+        NonAffine::Mod(e, m) => eval_expr(e, var_value).rem_euclid(i32::try_from(*m).unwrap()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::{host_can_load, BuildOptions, CodeGen, Compiler};
+    use crate::common::DimSize;
+    use crate::db::{Database, InMemoryDatabase};
+    use crate::layout::{col_major, row_major, Layout, PhysDim};
+    use crate::lspec;
+    use crate::scheduling_sugar::{SchedulingSugar, Subschedule};
+    use crate::search::top_down;
+    use crate::spec::{arb_canonical_spec, LogicalSpec, PrimitiveBasics, PrimitiveSpecType, Spec};
+    use crate::target::{
+        CpuMemoryLevel::{self, GL, RF, VRF},
+        Target, TargetId, X86Target,
+    };
+    use crate::tensorspec::TensorSpec;
+    use crate::verification::{
+        make_array_input_dyn, reduction_len, reduction_magnitudes, Mismatch, Tolerance,
+    };
+    use half::bf16;
+    use nonzero::nonzero as nz;
+    use proptest::prelude::*;
+
+    /// Returns a spec for a fully contiguous tensor. `vector_size` is only used in [VRF].
+    fn tensor_spec(
+        shape: &[u32],
+        dtype: Dtype,
+        level: CpuMemoryLevel,
+        layout: Layout,
+        vector_size: Option<DimSize>,
+    ) -> TensorSpec<X86Target> {
+        TensorSpec::new_noncanon(
+            shape.iter().map(|&d| DimSize::new(d).unwrap()).collect(),
+            dtype,
+            layout.contiguous_full(),
+            true,
+            level,
+            layout,
+            vector_size,
+        )
+    }
+
+    fn array<T>(shape: &[usize], values: Vec<T>) -> ArrayD<T> {
+        ArrayD::from_shape_vec(IxDyn(shape), values).unwrap()
+    }
+
+    /// Applies `kernel` to tensors with `specs` holding `arguments`, which are row-major. Returns
+    /// the last argument afterwards, in row-major order and as its raw buffer.
+    fn run_kernel(
+        kernel: CpuKernel,
+        specs: &[TensorSpec<X86Target>],
+        arguments: &[DynArray<IxDyn>],
+    ) -> (DynArray<IxDyn>, DynArray<Ix1>) {
+        let tensors = specs
+            .iter()
+            .map(|spec| Tensor::new(spec.clone()))
+            .collect::<Vec<_>>();
+        let params = specs
+            .iter()
+            .enumerate()
+            .map(|(i, spec)| Param::new(i.try_into().unwrap(), spec.clone()))
+            .collect::<Vec<_>>();
+        let mut interpreter = Interpreter {
+            param_bindings: params
+                .iter()
+                .cloned()
+                .zip(tensors.iter().map(|t| t as &dyn View<Tgt = X86Target>))
+                .collect(),
+            loop_iter_bindings: HashMap::new(),
+            buffers: HashMap::new(),
+        };
+        for ((tensor, param), argument) in tensors.iter().zip(&params).zip(arguments) {
+            let spec = tensor.spec();
+            interpreter.buffers.insert(
+                tensor.identifier(),
+                new_buffer(spec.dtype(), spec.volume().get()),
+            );
+            interpreter.scatter(param, argument).unwrap();
+        }
+        interpreter.interpret_kernel(kernel, &params).unwrap();
+        let output = interpreter.gather(params.last().unwrap()).unwrap();
+        let buffer = interpreter.buffers[&tensors.last().unwrap().identifier()].clone();
+        (output, buffer)
+    }
+
+    fn zero_impl_for(spec: &Spec<X86Target>) -> ImplNode<X86Target> {
+        spec.tile_out(&[1, 1], false)
+            .move_param(0, RF, row_major(2), None)
+            .subschedule(&[0], &|z| z.place(CpuKernel::MemsetZero))
+            .subschedule(&[1], &|m| m.place(CpuKernel::ValueAssign))
+    }
+
+    fn scalar_matmul_impl(spec: &Spec<X86Target>) -> ImplNode<X86Target> {
+        spec.to_accum()
+            .subschedule(&[0], &zero_impl_for)
+            .subschedule(&[1], &|s| {
+                s.tile_out(&[1, 1], false)
+                    .split(1)
+                    .move_param(0, RF, row_major(2), None)
+                    .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                    .subschedule(&[1], &|s| {
+                        s.move_param(1, RF, row_major(2), None)
+                            .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                            .subschedule(&[1], &|s| {
+                                s.move_param(2, RF, row_major(2), None)
+                                    .subschedule(&[0], &|s| s.place(CpuKernel::ValueAssign))
+                                    .subschedule(&[1], &|s| s.place(CpuKernel::MultAdd))
+                                    .subschedule(&[2], &|s| s.place(CpuKernel::ValueAssign))
+                            })
+                    })
+            })
+    }
+
+    #[test]
+    fn test_interpreted_zero_is_correct() {
+        for dtype in [Dtype::Uint8, Dtype::Sint32, Dtype::Float32, Dtype::Bfloat16] {
+            let spec = Spec::<X86Target>(
+                lspec!(Zero([3, 2], (dtype, GL, row_major(2)))),
+                X86Target::max_mem(),
+            );
+            if let Err(failure) = zero_impl_for(&spec).check_correctness_interpreted(&spec) {
+                panic!("{dtype:?}: {failure}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_interpreted_matmul_is_correct() {
+        let spec = Spec::<X86Target>(
+            lspec!(Matmul(
+                [2, 3, 4],
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2))
+            )),
+            X86Target::max_mem(),
+        );
+        scalar_matmul_impl(&spec)
+            .check_correctness_interpreted(&spec)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_interpreted_matmul_with_col_major_operand_is_correct() {
+        let spec = Spec::<X86Target>(
+            lspec!(Matmul(
+                [3, 2, 3],
+                (f32, GL, row_major(2)),
+                (f32, GL, col_major(2)),
+                (f32, GL, row_major(2))
+            )),
+            X86Target::max_mem(),
+        );
+        scalar_matmul_impl(&spec)
+            .check_correctness_interpreted(&spec)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_vector_zero_and_assign_kernels() {
+        let vector = tensor_spec(&[1, 8], Dtype::Uint32, VRF, row_major(2), Some(nz!(8u32)));
+        let memory = tensor_spec(&[1, 8], Dtype::Uint32, GL, row_major(2), None);
+        let values = DynArray::Uint32(array(&[1, 8], (1..=8).collect()));
+
+        let (zeroed, _) = run_kernel(CpuKernel::VectorZero, &[vector.clone()], &[values.clone()]);
+        assert_eq!(zeroed, DynArray::Uint32(ArrayD::zeros(IxDyn(&[1, 8]))));
+
+        let (assigned, _) = run_kernel(
+            CpuKernel::VectorAssign,
+            &[memory, vector],
+            &[values.clone(), zeroed],
+        );
+        assert_eq!(assigned, values);
+    }
+
+    #[test]
+    fn test_broadcast_vec_mult_add_kernel() {
+        let (output, _) = run_kernel(
+            CpuKernel::BroadcastVecMultAdd,
+            &[
+                tensor_spec(&[1, 1], Dtype::Float32, RF, row_major(2), None),
+                tensor_spec(&[1, 8], Dtype::Float32, VRF, row_major(2), Some(nz!(8u32))),
+                tensor_spec(&[1, 8], Dtype::Float32, VRF, row_major(2), Some(nz!(8u32))),
+            ],
+            &[
+                DynArray::Float32(array(&[1, 1], vec![2.0])),
+                DynArray::Float32(array(&[1, 8], (0..8).map(|i| i as f32).collect())),
+                DynArray::Float32(array(&[1, 8], vec![1.0; 8])),
+            ],
+        );
+        let expected = (0..8).map(|i| 1.0 + 2.0 * i as f32).collect();
+        assert_eq!(output, DynArray::Float32(array(&[1, 8], expected)));
+    }
+
+    #[test]
+    fn test_bf16_cast_kernels() {
+        let values = (0..16).map(|i| i as f32 - 4.5).collect::<Vec<_>>();
+        let bf16_values = DynArray::Bfloat16(array(
+            &[1, 16],
+            values.iter().map(|&v| bf16::from_f32(v)).collect(),
+        ));
+        let expected = DynArray::Float32(array(&[1, 16], values));
+        let interleaved = Layout::new(vec![
+            (0, PhysDim::Dynamic),
+            (1, PhysDim::Dynamic),
+            (1, PhysDim::OddEven(nz!(16u32))),
+        ]);
+        for (kernel, out_level, out_layout, vector_size) in [
+            (CpuKernel::CastBf16F32, RF, row_major(2), None),
+            (
+                CpuKernel::VectorCastBf16F32,
+                VRF,
+                row_major(2),
+                Some(nz!(8u32)),
+            ),
+            (
+                CpuKernel::VectorInterleaveBf16F32,
+                VRF,
+                interleaved,
+                Some(nz!(8u32)),
+            ),
+        ] {
+            let (output, _) = run_kernel(
+                kernel,
+                &[
+                    tensor_spec(&[1, 16], Dtype::Bfloat16, GL, row_major(2), None),
+                    tensor_spec(&[1, 16], Dtype::Float32, out_level, out_layout, vector_size),
+                ],
+                &[
+                    bf16_values.clone(),
+                    DynArray::Float32(ArrayD::zeros(IxDyn(&[1, 16]))),
+                ],
+            );
+            assert_eq!(output, expected, "{kernel:?}");
+        }
+    }
+
+    #[test]
+    fn test_physical_transpose_kernel_keeps_logical_values() {
+        let values = DynArray::Uint8(array(&[2, 16], (0..32).collect()));
+        let (output, buffer) = run_kernel(
+            CpuKernel::PhysicalTransposeByte128,
+            &[
+                tensor_spec(&[2, 16], Dtype::Uint8, GL, row_major(2), None),
+                tensor_spec(&[2, 16], Dtype::Uint8, GL, col_major(2), None),
+            ],
+            &[
+                values.clone(),
+                DynArray::Uint8(ArrayD::zeros(IxDyn(&[2, 16]))),
+            ],
+        );
+        assert_eq!(output, values);
+        // The rows are interleaved in the output's buffer.
+        let interleaved = (0..16).flat_map(|i| [i, i + 16]).collect::<Vec<u8>>();
+        assert_eq!(buffer, DynArray::Uint8(Array1::from(interleaved)));
+    }
+
+    #[test]
+    fn test_dot_product_kernels() {
+        let k = 32;
+        let lhs = (0..k).map(|i| (i % 5) as f32).collect::<Vec<_>>();
+        let rhs = (0..k).map(|i| (i % 3) as f32 - 1.0).collect::<Vec<_>>();
+        let dot = lhs.iter().zip(&rhs).map(|(l, r)| l * r).sum::<f32>();
+        let to_bf16 = |values: &[f32], shape: &[usize]| {
+            DynArray::Bfloat16(array(
+                shape,
+                values.iter().map(|&v| bf16::from_f32(v)).collect(),
+            ))
+        };
+        let k_u32 = u32::try_from(k).unwrap();
+        let lhs_spec = |dtype| tensor_spec(&[1, k_u32], dtype, GL, row_major(2), None);
+        let rhs_spec = |dtype| tensor_spec(&[k_u32, 1], dtype, GL, col_major(2), None);
+        let out_spec = tensor_spec(&[1, 1], Dtype::Float32, RF, row_major(2), None);
+        for (kernel, lhs_dtype, rhs_dtype) in [
+            (CpuKernel::DotProductLoop, Dtype::Float32, Dtype::Float32),
+            (
+                CpuKernel::DotProductLoopBf16Bf16F32,
+                Dtype::Bfloat16,
+                Dtype::Bfloat16,
+            ),
+            (
+                CpuKernel::DotProductLoopF32Bf16F32,
+                Dtype::Float32,
+                Dtype::Bfloat16,
+            ),
+        ] {
+            let lhs_array = match lhs_dtype {
+                Dtype::Bfloat16 => to_bf16(&lhs, &[1, k]),
+                _ => DynArray::Float32(array(&[1, k], lhs.clone())),
+            };
+            let rhs_array = match rhs_dtype {
+                Dtype::Bfloat16 => to_bf16(&rhs, &[k, 1]),
+                _ => DynArray::Float32(array(&[k, 1], rhs.clone())),
+            };
+            let (output, _) = run_kernel(
+                kernel,
+                &[lhs_spec(lhs_dtype), rhs_spec(rhs_dtype), out_spec.clone()],
+                &[
+                    lhs_array,
+                    rhs_array,
+                    DynArray::Float32(array(&[1, 1], vec![1.0])),
+                ],
+            );
+            // The kernels accumulate into their outputs.
+            assert_eq!(
+                output,
+                DynArray::Float32(array(&[1, 1], vec![1.0 + dot])),
+                "{kernel:?}"
+            );
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(8))]
+
+        #[test]
+        fn test_interpreter_agrees_with_compiled_impls(
+            spec in arb_canonical_spec::<X86Target>(Some(nz!(2u32)), Some(2048))
+        ) {
+            let parameters = spec.0.parameters();
+            // Generated programs only load row-major inputs in main memory.
+            prop_assume!(parameters
+                .iter()
+                .all(|p| p.level() == GL && p.layout().is_row_major()));
+            // GCC supports `__bf16` on x86 only from version 13.
+            let has_bf16 = parameters.iter().any(|p| p.dtype() == Dtype::Bfloat16);
+            let Some(compiler) = Compiler::ALL.into_iter().find(|&compiler| {
+                compiler.path().is_some() && !(compiler == Compiler::Gcc && has_bf16)
+            }) else {
+                return Ok(());
+            };
+            if !host_can_load(TargetId::X86) {
+                return Ok(());
+            }
+
+            let db = InMemoryDatabase::new(false, 1, None);
+            top_down(&db, &spec, 1, Some(nz!(1usize)));
+            let Some(imp) = db.get_impl(&spec).and_then(|impls| impls.into_iter().next()) else {
+                return Ok(());
+            };
+            let arguments = parameters.iter().map(make_array_input_dyn).collect::<Vec<_>>();
+            let interpreted = match interpret(&imp, &arguments) {
+                Err(InterpretError::Unsupported(_)) => return Ok(()),
+                result => result
+                    .map_err(|e| TestCaseError::fail(format!("{spec}: couldn't interpret: {e}")))?,
+            };
+            let artifact = imp
+                .build_with_options(false, &BuildOptions { compiler, ..Default::default() })
+                .map_err(|e| TestCaseError::fail(format!("{spec}: couldn't build: {e}")))?;
+            let compiled = artifact
+                .run_with_input_data(&arguments)
+                .map_err(|e| TestCaseError::fail(format!("{spec}: couldn't run: {e}")))?;
+            // Kernels are interpreted with reference semantics, so may round differently.
+            let tolerance = Tolerance::for_reduction(compiled.dtype(), reduction_len(&spec.0));
+            let magnitudes = reduction_magnitudes(&spec.0, &arguments);
+            if let Err(mismatch) =
+                compiled.compare_with_magnitudes(&interpreted, &magnitudes, tolerance)
+            {
+                prop_assert!(false, "{spec}: {mismatch}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_interpreting_unimplemented_spec_fails() {
+        let spec = Spec::<X86Target>(
+            lspec!(Zero([2, 2], (u32, GL, row_major(2)))),
+            X86Target::max_mem(),
+        );
+        let imp = spec.tile_out(&[1, 1], false);
+        let arguments = [DynArray::Uint32(ArrayD::zeros(IxDyn(&[2, 2])))];
+        assert!(matches!(
+            interpret(&imp, &arguments),
+            Err(InterpretError::MissingImpl(_))
+        ));
+        let failure = imp.check_correctness_interpreted(&spec).unwrap_err();
+        assert!(
+            matches!(failure.mismatch, Mismatch::RunFailed(_)),
+            "{failure}"
+        );
+    }
+}
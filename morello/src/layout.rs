@@ -496,7 +496,9 @@ impl Layout {
             .map(|x| x.get())
             .collect();
         for (dim, phys_dim) in dims.iter().rev() {
-            let remaining_size = &mut logical_shape_remaining[usize::from(*dim)];
+            let Some(remaining_size) = logical_shape_remaining.get_mut(usize::from(*dim)) else {
+                return Err(LayoutError::InvalidShape(logical_shape.into()));
+            };
             debug_assert_ne!(
                 remaining_size, &0,
                 "Logical dimension {} with unpacked sized already seen in {:?}",
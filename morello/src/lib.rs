@@ -9,6 +9,8 @@ pub mod db;
 pub mod expr;
 pub mod grid;
 pub mod imp;
+#[cfg(feature = "verification")]
+pub mod interpreter;
 pub mod layout;
 pub mod memorylimits;
 pub mod nameenv;
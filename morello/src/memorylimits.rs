@@ -343,14 +343,14 @@ impl<Tgt: Target> BiMap for MemoryLimitsBimap<Tgt> {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 pub fn arb_memorylimits<Tgt: Target>(
     maximum_memory: &MemVec,
 ) -> impl proptest::strategy::Strategy<Value = MemoryLimits> {
     arb_memorylimits_ext(&MemVec::zero::<Tgt>(), maximum_memory)
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 pub fn arb_memorylimits_ext(
     minimum_memory: &MemVec,
     maximum_memory: &MemVec,
@@ -28,7 +28,7 @@ use crate::views::{CacheView, Param, Tensor, Tile, TileError, View, ViewExt};
 /// [Action]s contain the minimal amount of information needed to distinguish a one scheduling
 /// decision from another, which makes it appropriate for storing in a database so that the
 /// corresponding Impl node can be computed given the Spec.
-#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, strum::IntoStaticStr)]
 #[cfg_attr(test, derive(Hash))]
 pub enum Action<Tgt: Target> {
     TileOut(TileOut),
@@ -1,5 +1,5 @@
 use crate::common::{DimSize, Dtype};
-use crate::db::FilesDatabase;
+use crate::db::Database;
 use crate::grid::canon::CanonicalBimap;
 use crate::grid::general::BiMap;
 use crate::imp::{Impl, ImplNode};
@@ -42,8 +42,9 @@ pub trait SchedulingSugar<Tgt: Target> {
     ) -> ImplNode<Tgt>;
     fn spatial_split(&self) -> ImplNode<Tgt>;
     fn place(&self, kernel_type: Tgt::Kernel) -> ImplNode<Tgt>;
-    fn synthesize(&self, db: &FilesDatabase, jobs: Option<NonZeroUsize>) -> ImplNode<Tgt>
+    fn synthesize<D>(&self, db: &D, jobs: Option<NonZeroUsize>) -> ImplNode<Tgt>
     where
+        D: Database + Sync,
         Tgt: Target,
         Tgt::Level: CanonicalBimap,
         <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>;
@@ -144,8 +145,9 @@ impl<Tgt: Target> SchedulingSugar<Tgt> for Spec<Tgt> {
         Action::Place(kernel_type).apply(self).unwrap()
     }
 
-    fn synthesize(&self, db: &FilesDatabase, jobs: Option<NonZeroUsize>) -> ImplNode<Tgt>
+    fn synthesize<D>(&self, db: &D, jobs: Option<NonZeroUsize>) -> ImplNode<Tgt>
     where
+        D: Database + Sync,
         Tgt: Target,
         Tgt::Level: CanonicalBimap,
         <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
@@ -224,8 +226,9 @@ impl<Tgt: Target> SchedulingSugar<Tgt> for ImplNode<Tgt> {
         apply_to_leaf_spec(self, |spec| spec.place(kernel_type))
     }
 
-    fn synthesize(&self, db: &FilesDatabase, jobs: Option<NonZeroUsize>) -> ImplNode<Tgt>
+    fn synthesize<D>(&self, db: &D, jobs: Option<NonZeroUsize>) -> ImplNode<Tgt>
     where
+        D: Database + Sync,
         Tgt: Target,
         Tgt::Level: CanonicalBimap,
         <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
@@ -1,18 +1,25 @@
 use itertools::Itertools;
+use parking_lot::Mutex;
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use serde::Serialize;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::hash_map::Entry;
 use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
 use std::mem::{replace, take};
 use std::num::NonZeroUsize;
 use std::rc::Rc;
+use std::sync::atomic::{self, AtomicBool, AtomicU64, AtomicUsize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::cost::Cost;
-use crate::db::{ActionCostVec, ActionIdx, FilesDatabase, GetPreference};
+use crate::cost::{Cost, MainCost};
+use crate::db::{ActionCostVec, ActionIdx, Database, GetPreference};
 use crate::grid::canon::CanonicalBimap;
 use crate::grid::general::BiMap;
 use crate::imp::{Impl, ImplExt, ImplNode};
+use crate::memorylimits::MemVec;
 use crate::scheduling::ApplyError;
 use crate::spec::Spec;
 use crate::target::Target;
@@ -20,17 +27,31 @@ use crate::target::Target;
 type RequestId = (usize, usize);
 type WorkingPartialImplHandle<Tgt> = (Spec<Tgt>, RequestId);
 
-struct TopDownSearch<'d> {
-    db: &'d FilesDatabase,
+/// The minimum time between reports to a [SearchObserver] while a stage is running.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+struct TopDownSearch<'d, D> {
+    db: &'d D,
     top_k: usize,
     thread_idx: usize,
     thread_count: usize,
-    hits: u64,
-    misses: u64,
+    state: &'d SearchState<'d>,
+    last_report: Cell<Instant>,
+}
+
+/// The state of a search shared by all of its threads.
+struct SearchState<'c> {
+    control: &'c SearchControl<'c>,
+    started: Instant,
+    stage: AtomicUsize,
+    stage_count: usize,
+    specs_completed: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
 }
 
-struct BlockSearch<'a, 'd, Tgt: Target> {
-    search: &'a TopDownSearch<'d>,
+struct BlockSearch<'a, 'd, Tgt: Target, D> {
+    search: &'a TopDownSearch<'d, D>,
     working_set: HashMap<Spec<Tgt>, Rc<RefCell<SpecTask<Tgt>>>>,
     working_set_running: usize,
     // The following two fields map requested Specs (the keys) to the recipients
@@ -50,6 +71,7 @@ enum SpecTask<Tgt: Target> {
         partial_impls_incomplete: usize,
         request_batches_returned: usize,
         max_children: usize, // TODO: Combine with request_batches_returned
+        trace: Option<SpecTrace>,
     },
     // TODO: Shouldn't need this second bool to track if it's from the database
     Complete(ActionCostVec, bool),
@@ -82,15 +104,129 @@ enum ImplReducerResults {
     Many(BTreeSet<(Cost, ActionIdx)>),
 }
 
+/// How to search for an Impl.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum SearchStrategy {
+    /// Find an optimal Impl with [top_down].
+    #[default]
+    Exact,
+    /// Find an Impl quickly with [greedy_search].
+    Greedy,
+    /// Find an Impl with [beam_search].
+    Beam,
+}
+
+/// When to stop a search and to whom to report its progress.
+#[derive(Default, Clone, Copy)]
+pub struct SearchControl<'a> {
+    /// When to stop searching. Only Specs finished by then are put into the database.
+    pub deadline: Option<Instant>,
+    /// Stops the search, like a passed deadline, once cancelled.
+    pub cancellation: Option<&'a CancellationToken>,
+    pub observer: Option<&'a dyn SearchObserver>,
+    /// Records the candidates considered for each Spec. Only the first thread's Specs are
+    /// recorded, so search with one job for a complete trace.
+    pub trace: Option<&'a TraceRecorder>,
+}
+
+/// A flag, shared between threads, which asks a search to stop.
+///
+/// The search checks the flag between batches of sub-Spec requests, so it may continue briefly
+/// after [CancellationToken::cancel] is called.
+#[derive(Debug, Default, Clone)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+/// Receives [SearchProgress] reports from a running search.
+///
+/// Reports are made from a search thread, so implementations should return quickly.
+pub trait SearchObserver: Sync {
+    fn report(&self, progress: &SearchProgress);
+}
+
+/// A snapshot of a running search.
+///
+/// Goals are searched in stages, one per group of goals sharing a database page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchProgress {
+    /// The stage being searched, counting from zero. Equal to `stage_count` once finished.
+    pub stage: usize,
+    pub stage_count: usize,
+    /// The number of Specs whose decisions have been put into the database.
+    pub specs_completed: u64,
+    /// The number of Specs in the working set of the block being searched.
+    pub working_set_size: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub elapsed: Duration,
+    /// The estimated time until all stages are finished, extrapolated from the stages finished
+    /// so far. `None` until the first stage is finished, so always `None` while a search of a
+    /// single stage runs.
+    pub eta: Option<Duration>,
+}
+
+/// Collects a [SpecTrace] for each Spec a search finishes.
+#[derive(Debug, Default)]
+pub struct TraceRecorder {
+    traces: Mutex<Vec<SpecTrace>>,
+    /// The only Spec to record, as displayed, if not all.
+    only: Option<String>,
+}
+
+/// The actions considered for a Spec by a search, and what became of each.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SpecTrace {
+    /// The (canonical) Spec, as displayed.
+    pub spec: String,
+    pub candidates: Vec<CandidateTrace>,
+    /// The chosen actions and their costs. Empty if the Spec has no Impl.
+    pub decisions: Vec<(ActionIdx, Cost)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CandidateTrace {
+    pub action_idx: ActionIdx,
+    /// The action, as debug-formatted.
+    pub action: String,
+    #[serde(flatten)]
+    pub outcome: CandidateOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum CandidateOutcome {
+    /// Applying the action failed with this [ApplyError].
+    NotApplied { error: String },
+    /// Some sub-Spec of the resulting partial Impl has no Impl.
+    Unsat,
+    /// The Impl's cost was computed from the costs of its sub-Specs, in order.
+    Evaluated { child_costs: Vec<Cost>, cost: Cost },
+    /// The sub-Specs haven't all been resolved. Never seen in a finished Spec's trace.
+    Pending,
+}
+
+/// The result of [top_down_with_budget] and [top_down_with_control].
+#[derive(Debug)]
+pub struct AnytimeResult<Tgt: Target> {
+    /// The best complete Impl found and its cost, or `None` if none was found.
+    pub best: Option<(ImplNode<Tgt>, Cost)>,
+    /// Whether the search finished before it was stopped. If so, `best` is optimal (or `None`
+    /// because the goal is unsatisfiable) and the goal's decision was put into the database.
+    pub optimal: bool,
+    pub hits: u64,
+    pub misses: u64,
+}
+
 // Computes an optimal Impl for `goal` and stores it in `db`.
-pub fn top_down<Tgt>(
-    db: &FilesDatabase,
+pub fn top_down<Tgt, D>(
+    db: &D,
     goal: &Spec<Tgt>,
     top_k: usize,
     jobs: Option<NonZeroUsize>,
 ) -> (Vec<(ActionIdx, Cost)>, u64, u64)
 where
     Tgt: Target,
+    D: Database + Sync,
     Tgt::Level: CanonicalBimap,
     <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
 {
@@ -99,14 +235,105 @@ where
     (r.into_iter().next().unwrap().0, h, m)
 }
 
-pub fn top_down_many<'d, Tgt>(
-    db: &'d FilesDatabase,
+/// Searches for an Impl of `goal` until finished or until `deadline`, whichever is first.
+///
+/// If the deadline passes first, returns the best complete Impl found so far: one built by
+/// [greedy_search] on the decisions put into the database for sub-Specs which the search did
+/// finish. Decisions for unfinished Specs, including `goal`, are not put into the database.
+pub fn top_down_with_budget<Tgt, D>(
+    db: &D,
+    goal: &Spec<Tgt>,
+    deadline: Instant,
+    jobs: Option<NonZeroUsize>,
+) -> AnytimeResult<Tgt>
+where
+    Tgt: Target,
+    D: Database + Sync,
+    Tgt::Level: CanonicalBimap,
+    <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
+{
+    let control = SearchControl {
+        deadline: Some(deadline),
+        ..SearchControl::default()
+    };
+    top_down_with_control(db, goal, jobs, &control)
+}
+
+/// Like [top_down_with_budget], but stops as set by `control` and reports progress to its
+/// observer.
+pub fn top_down_with_control<Tgt, D>(
+    db: &D,
+    goal: &Spec<Tgt>,
+    jobs: Option<NonZeroUsize>,
+    control: &SearchControl,
+) -> AnytimeResult<Tgt>
+where
+    Tgt: Target,
+    D: Database + Sync,
+    Tgt::Level: CanonicalBimap,
+    <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
+{
+    let mut canonical_goal = goal.clone();
+    canonical_goal
+        .canonicalize()
+        .expect("should be possible to canonicalize goal Spec");
+
+    let (results, hits, misses) =
+        top_down_many_with_control(db, &[canonical_goal.clone()], 1, jobs, control);
+    let (best, optimal) = match results.into_iter().next().unwrap() {
+        Some(ActionCostVec(decisions)) => {
+            let best = decisions.into_iter().next().map(|(_, cost)| {
+                let imp = db
+                    .get_impl(&canonical_goal)
+                    .expect("goal should have been put into the database")
+                    .swap_remove(0);
+                (imp, cost)
+            });
+            (best, true)
+        }
+        None => (greedy_search(db, &canonical_goal), false),
+    };
+    AnytimeResult {
+        best,
+        optimal,
+        hits,
+        misses,
+    }
+}
+
+pub fn top_down_many<'d, Tgt, D>(
+    db: &'d D,
     goals: &[Spec<Tgt>],
     top_k: usize,
     jobs: Option<NonZeroUsize>,
 ) -> (Vec<ActionCostVec>, u64, u64)
 where
     Tgt: Target,
+    D: Database + Sync,
+    Tgt::Level: CanonicalBimap,
+    <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
+{
+    let control = SearchControl::default();
+    let (results, hits, misses) = top_down_many_with_control(db, goals, top_k, jobs, &control);
+    let results = results
+        .into_iter()
+        .map(|r| r.expect("search without a deadline should finish"))
+        .collect();
+    (results, hits, misses)
+}
+
+/// Like [top_down_many], but stops as set by `control`, returning `None` for each goal not
+/// finished, and reports progress to its observer.
+pub fn top_down_many_with_control<'d, Tgt, D>(
+    db: &'d D,
+    goals: &[Spec<Tgt>],
+    top_k: usize,
+    jobs: Option<NonZeroUsize>,
+    control: &SearchControl,
+) -> (Vec<Option<ActionCostVec>>, u64, u64)
+where
+    Tgt: Target,
+    D: Database + Sync,
     Tgt::Level: CanonicalBimap,
     <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
 {
@@ -138,25 +365,33 @@ where
         .map(|j| j.get())
         .unwrap_or_else(rayon::current_num_threads);
 
-    let mut combined_results = vec![Default::default(); canonical_goals.len()];
-    let mut combined_hits = 0;
-    let mut combined_misses = 0;
+    let state = SearchState {
+        control,
+        started: Instant::now(),
+        stage: AtomicUsize::new(0),
+        stage_count: grouped_canonical_goals.len(),
+        specs_completed: AtomicU64::new(0),
+        hits: AtomicU64::new(0),
+        misses: AtomicU64::new(0),
+    };
+    let mut combined_results = vec![None; canonical_goals.len()];
     let mut goal_group = Vec::new();
-    for page_group in grouped_canonical_goals.values() {
+    for (stage, page_group) in grouped_canonical_goals.values().enumerate() {
+        state.stage.store(stage, atomic::Ordering::Relaxed);
+        state.report(0);
         goal_group.clear();
         goal_group.extend(page_group.iter().map(|&i| canonical_goals[i].clone()));
 
-        let (result, hits, misses) = if thread_count == 1 {
-            let search = TopDownSearch::<'d> {
+        let result = if thread_count == 1 {
+            let search = TopDownSearch::<'_, D> {
                 db,
                 top_k,
                 thread_idx: 0,
                 thread_count: 1,
-                hits: 0,
-                misses: 1,
+                state: &state,
+                last_report: Cell::new(Instant::now()),
             };
-            let r = BlockSearch::synthesize(&goal_group, &search, None);
-            (r, search.hits, search.misses)
+            BlockSearch::synthesize(&goal_group, &search, None)
         } else {
             let tasks = (0..thread_count)
                 .zip(std::iter::repeat(canonical_goals.clone()))
@@ -166,43 +401,61 @@ where
             tasks
                 .into_par_iter()
                 .map(|(i, gs)| {
-                    let search = TopDownSearch::<'d> {
+                    let search = TopDownSearch::<'_, D> {
                         db,
                         top_k,
                         thread_idx: i,
                         thread_count,
-                        hits: 0,
-                        misses: 1,
+                        state: &state,
+                        last_report: Cell::new(Instant::now()),
                     };
-                    let r = BlockSearch::synthesize(&gs, &search, None);
-                    (r, search.hits, search.misses)
+                    BlockSearch::synthesize(&gs, &search, None)
                 })
                 .collect::<Vec<_>>()
                 .pop()
                 .unwrap()
         };
 
+        let Some(result) = result else {
+            // The search was stopped, so leave the remaining goals unfinished.
+            return (
+                combined_results,
+                state.hits.into_inner(),
+                state.misses.into_inner(),
+            );
+        };
         for (r, i) in result.into_iter().zip(page_group) {
-            combined_results[*i] = r;
+            combined_results[*i] = Some(r);
         }
-        combined_hits += hits;
-        combined_misses += misses;
     }
-
-    (combined_results, combined_hits, combined_misses)
+    state
+        .stage
+        .store(grouped_canonical_goals.len(), atomic::Ordering::Relaxed);
+    state.report(0);
+
+    (
+        combined_results,
+        state.hits.into_inner(),
+        state.misses.into_inner(),
+    )
 }
 
-impl<'a, 'd, Tgt> BlockSearch<'a, 'd, Tgt>
+impl<'a, 'd, Tgt, D> BlockSearch<'a, 'd, Tgt, D>
 where
     Tgt: Target,
+    D: Database,
     Tgt::Level: CanonicalBimap,
     <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
 {
+    /// Computes and puts optimal decisions for `goals`.
+    ///
+    /// Returns `None` if the search was stopped first. In that case, only Specs which were
+    /// finished are put into the database.
     fn synthesize(
         goals: &[Spec<Tgt>],
-        search: &'a TopDownSearch<'d>,
+        search: &'a TopDownSearch<'d, D>,
         prefetch_after: Option<&Spec<Tgt>>,
-    ) -> Vec<ActionCostVec> {
+    ) -> Option<Vec<ActionCostVec>> {
         debug_assert!(goals.iter().all_unique());
 
         let mut block = BlockSearch {
@@ -240,8 +493,12 @@ where
                 }
 
                 let subblock_goals = subblock.keys().cloned().collect::<Vec<_>>();
-                let subblock_results =
-                    Self::synthesize(&subblock_goals, search, prefetch_to_push_down);
+                let Some(subblock_results) =
+                    Self::synthesize(&subblock_goals, search, prefetch_to_push_down)
+                else {
+                    block.put_completed();
+                    return None;
+                };
                 for (subspec, subspec_result) in subblock_goals.into_iter().zip(subblock_results) {
                     block.resolve_request_external(&mut subblock, &subspec, subspec_result);
                 }
@@ -258,6 +515,11 @@ where
             if block.working_set_running == 0 {
                 break;
             }
+            if search.should_stop() {
+                block.put_completed();
+                return None;
+            }
+            search.report_progress(block.working_set.len());
 
             let ws_vec = block
                 .working_set
@@ -291,24 +553,30 @@ where
                 };
                 let action_costs = take(task_result);
                 if !*from_db {
-                    search.db.put(g.clone(), action_costs.0.clone());
+                    search.put(g.clone(), action_costs.0.clone());
                 }
                 action_costs
             })
             .collect::<Vec<_>>();
 
         // Anything left in the working set is not a goal but should still be put
-        for (spec, task) in block.working_set.drain() {
-            let SpecTask::Complete(task_result, from_db) = &mut *task.borrow_mut() else {
-                unreachable!("Expected goal to be complete.");
-            };
-            let action_costs = take(task_result);
-            if !*from_db {
-                search.db.put(spec.clone(), action_costs.0.clone());
+        debug_assert_eq!(block.working_set_running, 0);
+        block.put_completed();
+
+        Some(final_results)
+    }
+
+    /// Puts the decisions of completed [SpecTask]s not from the database, emptying the working
+    /// set.
+    ///
+    /// Running tasks are dropped without being put, since their decisions may not be optimal.
+    fn put_completed(&mut self) {
+        for (spec, task) in self.working_set.drain() {
+            if let SpecTask::Complete(task_result, false) = &mut *task.borrow_mut() {
+                self.search.put(spec, take(task_result).0);
             }
         }
-
-        final_results
+        self.working_set_running = 0;
     }
 
     fn visit_spec_internal(
@@ -334,11 +602,17 @@ where
                 // Check the database and immediately return if present.
                 let task = match self.search.db.get_with_preference(spec) {
                     GetPreference::Hit(v) => {
-                        // TODO: Re-enable search hits and misses tracking
-                        // search.hits += 1;
+                        self.search
+                            .state
+                            .hits
+                            .fetch_add(1, atomic::Ordering::Relaxed);
                         SpecTask::Complete(v, true)
                     }
                     GetPreference::Miss(preferences) => {
+                        self.search
+                            .state
+                            .misses
+                            .fetch_add(1, atomic::Ordering::Relaxed);
                         let started = SpecTask::start(spec.clone(), preferences, self.search);
                         if matches!(&started, SpecTask::Running { .. }) {
                             self.working_set_running += 1;
@@ -346,7 +620,6 @@ where
                         started
                     }
                 };
-                // search.misses += 1;
                 let task_rc = Rc::new(RefCell::new(task));
                 e.insert(Rc::clone(&task_rc));
                 task_rc
@@ -381,7 +654,7 @@ where
                         }
                         SpecTask::Complete(subtask_result, _) => {
                             let cost = subtask_result.iter().next().map(|v| v.1.clone());
-                            task.resolve_request(request_id, cost);
+                            task.resolve_request(request_id, cost, self.search.trace_recorder());
                             // At this point, the task_ref might have completed (be
                             // `SpecTask::Complete`). We want to propagate the completion to any
                             // tasks waiting within the working set, but we don't want to recurse
@@ -410,6 +683,7 @@ where
             None,
             subspec,
             results,
+            self.search.trace_recorder(),
         );
     }
 
@@ -426,6 +700,7 @@ where
             Some(&mut self.working_block_requests),
             subspec,
             results,
+            self.search.trace_recorder(),
         );
     }
 
@@ -436,6 +711,7 @@ where
         next_subblock: Option<&mut HashMap<Spec<Tgt>, Vec<WorkingPartialImplHandle<Tgt>>>>,
         subspec: &Spec<Tgt>,
         results: ActionCostVec,
+        recorder: Option<&TraceRecorder>,
     ) {
         let Some(rs) = subblock.remove(subspec) else {
             return;
@@ -450,7 +726,7 @@ where
                 let mut requester = requester_task.borrow_mut();
                 // The SpecTask might already be Complete if it was unsat'ed by a prior resolution.
                 if matches!(&*requester, SpecTask::Running { .. }) {
-                    requester.resolve_request(request_id, cost.clone());
+                    requester.resolve_request(request_id, cost.clone(), recorder);
                     if let SpecTask::Complete(completed_requester_results, _) = &*requester {
                         // TODO: Avoid this clone by consuming the sub-block. (Do at the call site.)
                         *working_set_running -= 1;
@@ -463,6 +739,7 @@ where
                             None,
                             &wb_spec,
                             cloned_results,
+                            recorder,
                         );
                     }
                 }
@@ -522,16 +799,373 @@ where
     }
 }
 
+impl<D> TopDownSearch<'_, D> {
+    fn should_stop(&self) -> bool {
+        let control = self.state.control;
+        control
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+            || control
+                .cancellation
+                .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Returns the [TraceRecorder] to which this thread should record Specs, if any.
+    fn trace_recorder(&self) -> Option<&TraceRecorder> {
+        self.state.control.trace.filter(|_| self.thread_idx == 0)
+    }
+
+    /// Reports progress to the observer, if any, unless this isn't the first thread or the last
+    /// report was recent.
+    fn report_progress(&self, working_set_size: usize) {
+        if self.thread_idx != 0 || self.last_report.get().elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+        self.last_report.set(Instant::now());
+        self.state.report(working_set_size);
+    }
+}
+
+impl<D: Database> TopDownSearch<'_, D> {
+    fn put<Tgt>(&self, spec: Spec<Tgt>, decisions: Vec<(ActionIdx, Cost)>)
+    where
+        Tgt: Target,
+        Tgt::Level: CanonicalBimap,
+        <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
+    {
+        self.db.put(spec, decisions);
+        self.state
+            .specs_completed
+            .fetch_add(1, atomic::Ordering::Relaxed);
+    }
+}
+
+impl SearchState<'_> {
+    fn report(&self, working_set_size: usize) {
+        let Some(observer) = self.control.observer else {
+            return;
+        };
+        let stage = self.stage.load(atomic::Ordering::Relaxed);
+        let elapsed = self.started.elapsed();
+        let eta =
+            (stage > 0).then(|| elapsed.mul_f64((self.stage_count - stage) as f64 / stage as f64));
+        observer.report(&SearchProgress {
+            stage,
+            stage_count: self.stage_count,
+            specs_completed: self.specs_completed.load(atomic::Ordering::Relaxed),
+            working_set_size,
+            hits: self.hits.load(atomic::Ordering::Relaxed),
+            misses: self.misses.load(atomic::Ordering::Relaxed),
+            elapsed,
+            eta,
+        });
+    }
+}
+
+impl fmt::Debug for SearchControl<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SearchControl")
+            .field("deadline", &self.deadline)
+            .field("cancellation", &self.cancellation)
+            .field("observer", &self.observer.is_some())
+            .field("trace", &self.trace)
+            .finish()
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(atomic::Ordering::Relaxed)
+    }
+}
+
+impl TraceRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a recorder which records only the trace of `spec`, once canonicalized.
+    pub fn only<Tgt: Target>(spec: &Spec<Tgt>) -> Self {
+        let mut canonical_spec = spec.clone();
+        // A Spec which can't be canonicalized is never searched, so is never recorded.
+        let _ = canonical_spec.canonicalize();
+        TraceRecorder {
+            traces: Mutex::default(),
+            only: Some(canonical_spec.to_string()),
+        }
+    }
+
+    /// Returns the recorded traces in the order their Specs were finished.
+    pub fn into_traces(self) -> Vec<SpecTrace> {
+        self.traces.into_inner()
+    }
+
+    /// Returns whether the trace of `spec` would be recorded. Searches check this before
+    /// building a trace.
+    fn records(&self, spec: &str) -> bool {
+        match &self.only {
+            Some(only) => only == spec,
+            None => true,
+        }
+    }
+
+    /// Records `trace`, unless this recorder records only another Spec's trace.
+    fn record(&self, trace: SpecTrace) {
+        if self.records(&trace.spec) {
+            self.traces.lock().push(trace);
+        }
+    }
+}
+
+impl SpecTrace {
+    fn candidate_mut(&mut self, action_idx: ActionIdx) -> &mut CandidateTrace {
+        self.candidates
+            .iter_mut()
+            .find(|c| c.action_idx == action_idx)
+            .expect("every action should have a candidate")
+    }
+}
+
+impl<F: Fn(&SearchProgress) + Sync> SearchObserver for F {
+    fn report(&self, progress: &SearchProgress) {
+        self(progress)
+    }
+}
+
+/// Searches for an Impl of `goal` by applying, at each Spec, the action with the lowest
+/// [estimated cost](estimate_cost).
+///
+/// Equivalent to [beam_search] with a width of one.
+pub fn greedy_search<Tgt, D>(db: &D, goal: &Spec<Tgt>) -> Option<(ImplNode<Tgt>, Cost)>
+where
+    Tgt: Target,
+    D: Database,
+    Tgt::Level: CanonicalBimap,
+    <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
+{
+    beam_search(db, goal, NonZeroUsize::MIN)
+}
+
+/// Like [greedy_search], but expands at most `max_nodes` Specs. See [beam_search_with_budget].
+pub fn greedy_search_with_budget<Tgt, D>(
+    db: &D,
+    goal: &Spec<Tgt>,
+    max_nodes: u64,
+) -> Option<(ImplNode<Tgt>, Cost)>
+where
+    Tgt: Target,
+    D: Database,
+    Tgt::Level: CanonicalBimap,
+    <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
+{
+    beam_search_with_budget(db, goal, NonZeroUsize::MIN, max_nodes)
+}
+
+/// Searches for an Impl of `goal`, considering only the `width` actions with the lowest
+/// [estimated cost](estimate_cost) at each Spec.
+///
+/// Of the actions considered, the one with the lowest cost is chosen. If an action has no Impl
+/// for some sub-Spec, the next action by estimated cost is considered in its place. This is much
+/// faster than [top_down] but, unless `width` is at least the number of actions, may not find the
+/// optimal Impl. Decisions found in `db` are used, but none are put into it. Returns `None` if no
+/// Impl was found.
+pub fn beam_search<Tgt, D>(
+    db: &D,
+    goal: &Spec<Tgt>,
+    width: NonZeroUsize,
+) -> Option<(ImplNode<Tgt>, Cost)>
+where
+    Tgt: Target,
+    D: Database,
+    Tgt::Level: CanonicalBimap,
+    <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
+{
+    beam_search_with_budget(db, goal, width, u64::MAX)
+}
+
+/// Like [beam_search], but expands at most `max_nodes` Specs.
+///
+/// A Spec is expanded when its actions are first considered, so Specs with decisions in `db`
+/// don't count against the budget. Once it is spent, Specs not yet expanded are treated as having
+/// no Impl, so the result is the best Impl complete by then, or `None` if there was none.
+pub fn beam_search_with_budget<Tgt, D>(
+    db: &D,
+    goal: &Spec<Tgt>,
+    width: NonZeroUsize,
+    max_nodes: u64,
+) -> Option<(ImplNode<Tgt>, Cost)>
+where
+    Tgt: Target,
+    D: Database,
+    Tgt::Level: CanonicalBimap,
+    <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
+{
+    let mut canonical_goal = goal.clone();
+    canonical_goal
+        .canonicalize()
+        .expect("should be possible to canonicalize goal Spec");
+    let mut nodes_left = max_nodes;
+    beam_impl(
+        db,
+        &canonical_goal,
+        width,
+        &mut nodes_left,
+        &mut HashMap::new(),
+    )
+}
+
+/// The recursive part of [beam_search]. `memo` records the results for Specs already visited,
+/// and `nodes_left` is decremented for each Spec expanded.
+fn beam_impl<Tgt, D>(
+    db: &D,
+    spec: &Spec<Tgt>,
+    width: NonZeroUsize,
+    nodes_left: &mut u64,
+    memo: &mut HashMap<Spec<Tgt>, Option<(ImplNode<Tgt>, Cost)>>,
+) -> Option<(ImplNode<Tgt>, Cost)>
+where
+    Tgt: Target,
+    D: Database,
+    Tgt::Level: CanonicalBimap,
+    <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
+{
+    if let Some(result) = memo.get(spec) {
+        return result.clone();
+    }
+    if let GetPreference::Hit(decisions) = db.get_with_preference(spec) {
+        let result = decisions.0.into_iter().next().map(|(_, cost)| {
+            let imp = db.get_impl(spec).unwrap().swap_remove(0);
+            (imp, cost)
+        });
+        memo.insert(spec.clone(), result.clone());
+        return result;
+    }
+    if *nodes_left == 0 {
+        return None;
+    }
+    *nodes_left -= 1;
+
+    // Estimate the cost of each applicable action from its sub-Specs. Ties are broken by action
+    // index so that results are deterministic.
+    let mut candidates = spec
+        .0
+        .actions(db.tiling_depth())
+        .into_iter()
+        .enumerate()
+        .filter_map(|(action_idx, action)| {
+            let partial_impl = action.apply(spec).ok()?;
+            let mut subspecs = Vec::new();
+            partial_impl.visit_subspecs(|s| {
+                subspecs.push(s.clone());
+                true
+            });
+            let estimate = compute_impl_cost(
+                &partial_impl,
+                &mut subspecs.iter().map(|s| estimate_cost(db, s)),
+            );
+            Some((estimate, action_idx, partial_impl, subspecs))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+    // Record the Spec as unsatisfiable while visiting its sub-Specs, in case any depend on it.
+    memo.insert(spec.clone(), None);
+    let mut result: Option<(ImplNode<Tgt>, Cost)> = None;
+    let mut considered = 0;
+    for (_, _, partial_impl, subspecs) in candidates {
+        let Some(children) = subspecs
+            .iter()
+            .map(|s| beam_impl(db, s, width, nodes_left, memo))
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        let cost = compute_impl_cost(
+            &partial_impl,
+            &mut children.iter().map(|(_, cost)| cost.clone()),
+        );
+        if result.as_ref().map_or(true, |(_, best)| cost < *best) {
+            let imp =
+                replace_subspecs(&partial_impl, &mut children.into_iter().map(|(imp, _)| imp));
+            result = Some((imp, cost));
+        }
+        considered += 1;
+        if considered == width.get() {
+            break;
+        }
+    }
+    memo.insert(spec.clone(), result.clone());
+    result
+}
+
+/// Estimates the cost of an Impl of `spec` without searching.
+///
+/// Uses the decision in `db`, if any. Otherwise, the main cost is the Spec's FLOPs or, for Specs
+/// without FLOPs, the volume of its output, and the Impl is assumed to use no memory.
+fn estimate_cost<Tgt, D>(db: &D, spec: &Spec<Tgt>) -> Cost
+where
+    Tgt: Target,
+    D: Database,
+    Tgt::Level: CanonicalBimap,
+    <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
+{
+    if let GetPreference::Hit(decisions) = db.get_with_preference(spec) {
+        return match decisions.0.into_iter().next() {
+            Some((_, cost)) => cost,
+            None => Cost {
+                main: MainCost::MAX,
+                peaks: MemVec::zero::<Tgt>(),
+                depth: 0,
+            },
+        };
+    }
+    let work = spec
+        .flops()
+        .unwrap_or_else(|| spec.0.output().volume().get().into());
+    Cost {
+        main: work.try_into().unwrap_or(MainCost::MAX),
+        peaks: MemVec::zero::<Tgt>(),
+        depth: 0,
+    }
+}
+
+/// Replaces the Spec applications in `imp`, in order, with Impls from `impls`.
+fn replace_subspecs<Tgt, I>(imp: &ImplNode<Tgt>, impls: &mut I) -> ImplNode<Tgt>
+where
+    Tgt: Target,
+    I: Iterator<Item = ImplNode<Tgt>>,
+{
+    match imp {
+        ImplNode::SpecApp(_) => impls.next().unwrap(),
+        _ => imp.replace_children(
+            imp.children()
+                .iter()
+                .map(|child| replace_subspecs(child, impls))
+                .collect::<Vec<_>>()
+                .into_iter(),
+        ),
+    }
+}
+
 impl<Tgt: Target> SpecTask<Tgt> {
     /// Begin computing the optimal implementation of a Spec.
     ///
     /// Internally, this will expand partial [Impl]s for all actions.
-    fn start(
+    fn start<D>(
         goal: Spec<Tgt>,
         preferences: Option<Vec<ActionIdx>>,
-        search: &TopDownSearch<'_>,
+        search: &TopDownSearch<'_, D>,
     ) -> Self
     where
+        D: Database,
         Tgt: Target,
         Tgt::Level: CanonicalBimap,
         <Tgt::Level as CanonicalBimap>::Bimap: BiMap<Codomain = u8>,
@@ -544,10 +1178,32 @@ impl<Tgt: Target> SpecTask<Tgt> {
         let tiling_depth = search.db.tiling_depth();
         let all_actions = goal.0.actions(tiling_depth).into_iter().collect::<Vec<_>>();
         let initial_skip = search.thread_idx * all_actions.len() / search.thread_count;
+        let mut trace = search.trace_recorder().and_then(|recorder| {
+            let spec = goal.to_string();
+            recorder.records(&spec).then(|| SpecTrace {
+                spec,
+                candidates: Vec::with_capacity(all_actions.len()),
+                decisions: vec![],
+            })
+        });
 
         for action_idx in (initial_skip..all_actions.len()).chain(0..initial_skip) {
             let action = &all_actions[action_idx];
-            match action.apply(&goal) {
+            let applied = action.apply(&goal);
+            if let Some(trace) = &mut trace {
+                let outcome = match &applied {
+                    Ok(_) => CandidateOutcome::Pending,
+                    Err(e) => CandidateOutcome::NotApplied {
+                        error: e.to_string(),
+                    },
+                };
+                trace.candidates.push(CandidateTrace {
+                    action_idx: action_idx.try_into().unwrap(),
+                    action: format!("{action:?}"),
+                    outcome,
+                });
+            }
+            match applied {
                 Ok(partial_impl) => {
                     let mut partial_impl_subspecs = Vec::new();
                     partial_impl.visit_subspecs(|s| {
@@ -562,10 +1218,15 @@ impl<Tgt: Target> SpecTask<Tgt> {
                     // are nested sub-Specs, then store the partial Impl for resolution by the
                     // caller.
                     if partial_impl_subspecs.is_empty() {
-                        reducer.insert(
-                            u16::try_from(action_idx).unwrap(),
-                            Cost::from_impl(&partial_impl),
-                        );
+                        let cost = Cost::from_impl(&partial_impl);
+                        if let Some(trace) = &mut trace {
+                            trace.candidates.last_mut().unwrap().outcome =
+                                CandidateOutcome::Evaluated {
+                                    child_costs: vec![],
+                                    cost: cost.clone(),
+                                };
+                        }
+                        reducer.insert(u16::try_from(action_idx).unwrap(), cost);
                     } else {
                         partial_impls.push(WorkingPartialImpl::Constructing {
                             partial_impl,
@@ -582,7 +1243,12 @@ impl<Tgt: Target> SpecTask<Tgt> {
         }
 
         if partial_impls_incomplete == 0 {
-            SpecTask::Complete(ActionCostVec(reducer.finalize()), false)
+            let decisions = reducer.finalize();
+            if let (Some(recorder), Some(mut trace)) = (search.trace_recorder(), trace) {
+                trace.decisions = decisions.clone();
+                recorder.record(trace);
+            }
+            SpecTask::Complete(ActionCostVec(decisions), false)
         } else {
             SpecTask::Running {
                 reducer,
@@ -590,6 +1256,7 @@ impl<Tgt: Target> SpecTask<Tgt> {
                 partial_impls,
                 partial_impls_incomplete,
                 request_batches_returned: 0,
+                trace,
             }
         }
     }
@@ -630,10 +1297,13 @@ impl<Tgt: Target> SpecTask<Tgt> {
         }))
     }
 
+    /// Resolves a request with the cost of its Spec, recording the trace of this task's goal to
+    /// `recorder` if that completes the task.
     fn resolve_request(
         &mut self,
         id: RequestId,
         cost: Option<Cost>, // `None` means that the Spec was unsat
+        recorder: Option<&TraceRecorder>,
     ) where
         Tgt: Target,
         Tgt::Level: CanonicalBimap,
@@ -645,6 +1315,7 @@ impl<Tgt: Target> SpecTask<Tgt> {
             partial_impls_incomplete,
             request_batches_returned: _,
             max_children: _,
+            trace,
         } = self
         else {
             panic!("Task is not running");
@@ -674,18 +1345,30 @@ impl<Tgt: Target> SpecTask<Tgt> {
                     // for the parent and transition this partial to a Sat state.
                     if subspec_costs.iter().all(|c| c.is_some()) {
                         finished = true;
-                        reducer.insert(
-                            *producing_action_idx,
-                            compute_impl_cost(
-                                partial_impl,
-                                // TODO: Move rather than clone the child_costs.
-                                &mut subspec_costs.iter().map(|c| c.as_ref().unwrap().clone()),
-                            ),
+                        let cost = compute_impl_cost(
+                            partial_impl,
+                            // TODO: Move rather than clone the child_costs.
+                            &mut subspec_costs.iter().map(|c| c.as_ref().unwrap().clone()),
                         );
+                        if let Some(trace) = trace.as_mut() {
+                            trace.candidate_mut(*producing_action_idx).outcome =
+                                CandidateOutcome::Evaluated {
+                                    child_costs: subspec_costs
+                                        .iter()
+                                        .map(|c| c.as_ref().unwrap().clone())
+                                        .collect(),
+                                    cost: cost.clone(),
+                                };
+                        }
+                        reducer.insert(*producing_action_idx, cost);
                     }
                 } else {
                     finished = true;
                     became_unsat = true;
+                    if let Some(trace) = trace.as_mut() {
+                        trace.candidate_mut(*producing_action_idx).outcome =
+                            CandidateOutcome::Unsat;
+                    }
                 }
             }
             WorkingPartialImpl::Unsat => {}
@@ -709,6 +1392,10 @@ impl<Tgt: Target> SpecTask<Tgt> {
                 let tmp_replacement = ImplReducer::new(0, Vec::new());
                 let removed_reducer: ImplReducer = replace(reducer, tmp_replacement);
                 let final_result = removed_reducer.finalize();
+                if let (Some(recorder), Some(mut trace)) = (recorder, take(trace)) {
+                    trace.decisions = final_result.clone();
+                    recorder.record(trace);
+                }
                 *self = SpecTask::Complete(ActionCostVec(final_result), false);
             }
         }
@@ -817,7 +1504,7 @@ where
 mod tests {
     use super::*;
     use crate::common::DimSize;
-    use crate::db::FilesDatabase;
+    use crate::db::InMemoryDatabase;
     use crate::layout::row_major;
     use crate::lspec;
     use crate::memorylimits::{MemVec, MemoryLimits};
@@ -845,7 +1532,7 @@ mod tests {
         fn test_can_synthesize_any_canonical_spec(
             spec in arb_canonical_spec::<X86Target>(Some(TEST_SMALL_SIZE), Some(TEST_SMALL_MEM))
         ) {
-            let db = FilesDatabase::new(None, false, 1, 128, 1, None);
+            let db = InMemoryDatabase::new(false, 1, None);
             top_down(&db, &spec, 1, Some(nz!(1usize)));
         }
 
@@ -855,7 +1542,7 @@ mod tests {
             spec_pair in lower_and_higher_canonical_specs::<X86Target>()
         ) {
             let (spec, raised_spec) = spec_pair;
-            let db = FilesDatabase::new(None, false, 1, 128, 1, None);
+            let db = InMemoryDatabase::new(false, 1, None);
 
             // Solve the first, lower Spec.
             let (lower_result_vec, _, _) = top_down(&db, &spec, 1, Some(nz!(1usize)));
@@ -878,7 +1565,7 @@ mod tests {
         fn test_synthesis_at_peak_memory_yields_same_decision(
             spec in arb_canonical_spec::<X86Target>(Some(TEST_SMALL_SIZE), Some(TEST_SMALL_MEM))
         ) {
-            let db = FilesDatabase::new(None, false, 1, 128, 1, None);
+            let db = InMemoryDatabase::new(false, 1, None);
             let (first_solutions, _, _) = top_down(&db, &spec, 1, Some(nz!(1usize)));
             let first_peak = if let Some(first_sol) = first_solutions.first() {
                 first_sol.1.peaks.clone()
@@ -1126,7 +1813,7 @@ mod tests {
             logical_spec,
             MemoryLimits::Standard(MemVec::new_from_binary_scaled([1, 1, 1, 0])),
         );
-        let db = FilesDatabase::new(None, false, 1, 128, 1, None);
+        let db = InMemoryDatabase::new(false, 1, None);
 
         let (action_costs, _, _) = top_down(&db, &spec, 1, Some(nz!(1usize)));
 
@@ -1138,6 +1825,199 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_budget_past_deadline_returns_greedy_impl_without_putting_goal() {
+        let spec = matmul_spec(2);
+        let result = top_down_with_budget(
+            &InMemoryDatabase::new(false, 1, None),
+            &spec,
+            Instant::now(),
+            Some(nz!(1usize)),
+        );
+        assert!(!result.optimal);
+        let (imp, cost) = result.best.expect("greedy search should find an Impl");
+        assert_eq!(Cost::from_impl(&imp), cost);
+
+        let db = InMemoryDatabase::new(false, 1, None);
+        let (optimal, _, _) = top_down(&db, &spec, 1, Some(nz!(1usize)));
+        assert!(optimal[0].1 <= cost);
+    }
+
+    #[test]
+    fn test_budget_past_deadline_puts_only_finished_specs() {
+        let spec = matmul_spec(2);
+        let db = InMemoryDatabase::new(false, 1, None);
+        top_down_with_budget(&db, &spec, Instant::now(), Some(nz!(1usize)));
+        assert!(db.get(&spec).is_none());
+
+        // Finishing the search afterwards gives the same decision as a fresh search.
+        let (resumed, _, _) = top_down(&db, &spec, 1, Some(nz!(1usize)));
+        let fresh_db = InMemoryDatabase::new(false, 1, None);
+        let (fresh, _, _) = top_down(&fresh_db, &spec, 1, Some(nz!(1usize)));
+        assert_eq!(resumed, fresh);
+    }
+
+    #[test]
+    fn test_budget_before_deadline_is_optimal() {
+        let spec = matmul_spec(2);
+        let db = InMemoryDatabase::new(false, 1, None);
+        let deadline = Instant::now() + std::time::Duration::from_secs(3600);
+        let result = top_down_with_budget(&db, &spec, deadline, Some(nz!(1usize)));
+        assert!(result.optimal);
+
+        let fresh_db = InMemoryDatabase::new(false, 1, None);
+        let (optimal, _, _) = top_down(&fresh_db, &spec, 1, Some(nz!(1usize)));
+        assert_eq!(
+            result.best.map(|(_, cost)| cost),
+            Some(optimal[0].1.clone())
+        );
+    }
+
+    #[test]
+    fn test_greedy_and_beam_search_costs_are_no_better_than_exact() {
+        let spec = matmul_spec(2);
+        let db = InMemoryDatabase::new(false, 1, None);
+        let (optimal, _, _) = top_down(&db, &spec, 1, Some(nz!(1usize)));
+
+        let empty_db = InMemoryDatabase::new(false, 1, None);
+        let (greedy_imp, greedy_cost) =
+            greedy_search(&empty_db, &spec).expect("greedy search should find an Impl");
+        let (_, beam_cost) =
+            beam_search(&empty_db, &spec, nz!(4usize)).expect("beam search should find an Impl");
+        assert_eq!(Cost::from_impl(&greedy_imp), greedy_cost);
+        assert!(optimal[0].1 <= greedy_cost);
+        assert!(optimal[0].1 <= beam_cost);
+    }
+
+    #[test]
+    fn test_node_budget_stops_greedy_and_beam_search() {
+        let spec = matmul_spec(2);
+        let db = InMemoryDatabase::new(false, 1, None);
+        // Every action on the goal leaves sub-Specs, which a budget of one can't expand.
+        assert!(greedy_search_with_budget(&db, &spec, 1).is_none());
+        assert!(beam_search_with_budget(&db, &spec, nz!(4usize), 1).is_none());
+        assert!(greedy_search_with_budget(&db, &spec, u64::MAX).is_some());
+    }
+
+    #[test]
+    fn test_unbounded_beam_search_is_exact() {
+        let spec = matmul_spec(2);
+        let db = InMemoryDatabase::new(false, 1, None);
+        let (optimal, _, _) = top_down(&db, &spec, 1, Some(nz!(1usize)));
+
+        let empty_db = InMemoryDatabase::new(false, 1, None);
+        let (imp, cost) = beam_search(&empty_db, &spec, NonZeroUsize::MAX)
+            .expect("beam search should find an Impl");
+        assert_eq!(Cost::from_impl(&imp), cost);
+        assert_eq!(cost, optimal[0].1);
+    }
+
+    #[test]
+    fn test_cancelled_search_puts_only_finished_specs() {
+        let spec = matmul_spec(2);
+        let db = InMemoryDatabase::new(false, 1, None);
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        let control = SearchControl {
+            cancellation: Some(&cancellation),
+            ..SearchControl::default()
+        };
+        let result = top_down_with_control(&db, &spec, Some(nz!(1usize)), &control);
+        assert!(!result.optimal);
+        assert!(db.get(&spec).is_none());
+    }
+
+    #[test]
+    fn test_observer_is_sent_final_progress() {
+        let spec = matmul_spec(2);
+        let db = InMemoryDatabase::new(false, 1, None);
+        let reports = std::sync::Mutex::new(Vec::new());
+        let observer = |progress: &SearchProgress| reports.lock().unwrap().push(progress.clone());
+        let control = SearchControl {
+            observer: Some(&observer),
+            ..SearchControl::default()
+        };
+        let result = top_down_with_control(&db, &spec, Some(nz!(1usize)), &control);
+        assert!(result.optimal);
+
+        let reports = reports.into_inner().unwrap();
+        let last = reports.last().expect("observer should be sent progress");
+        assert_eq!((last.stage, last.stage_count), (1, 1));
+        assert_eq!((last.hits, last.misses), (result.hits, result.misses));
+        assert!(last.specs_completed > 0);
+        assert_eq!(last.eta, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_trace_records_every_action_of_goal() {
+        let spec = matmul_spec(2);
+        let db = InMemoryDatabase::new(false, 1, None);
+        let recorder = TraceRecorder::new();
+        let control = SearchControl {
+            trace: Some(&recorder),
+            ..SearchControl::default()
+        };
+        let result = top_down_with_control(&db, &spec, Some(nz!(1usize)), &control);
+        let (_, best_cost) = result.best.expect("search should find an Impl");
+
+        let traces = recorder.into_traces();
+        let goal_trace = traces
+            .iter()
+            .find(|t| t.spec == spec.to_string())
+            .expect("goal should be traced");
+        assert_eq!(
+            goal_trace.candidates.len(),
+            spec.0.actions(db.tiling_depth()).into_iter().count()
+        );
+        assert!(traces
+            .iter()
+            .flat_map(|t| &t.candidates)
+            .all(|c| c.outcome != CandidateOutcome::Pending));
+
+        let [(decided_idx, decided_cost)] = &goal_trace.decisions[..] else {
+            panic!("goal should have one decision");
+        };
+        assert_eq!(*decided_cost, best_cost);
+        let decided = goal_trace
+            .candidates
+            .iter()
+            .find(|c| c.action_idx == *decided_idx)
+            .unwrap();
+        assert!(matches!(
+            &decided.outcome,
+            CandidateOutcome::Evaluated { cost, .. } if *cost == best_cost
+        ));
+    }
+
+    #[test]
+    fn test_trace_recorder_records_only_its_spec() {
+        let spec = matmul_spec(2);
+        let db = InMemoryDatabase::new(false, 1, None);
+        let recorder = TraceRecorder::only(&spec);
+        let control = SearchControl {
+            trace: Some(&recorder),
+            ..SearchControl::default()
+        };
+        top_down_with_control(&db, &spec, Some(nz!(1usize)), &control);
+
+        let traces = recorder.into_traces();
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].spec, spec.to_string());
+    }
+
+    fn matmul_spec(size: u32) -> Spec<X86Target> {
+        Spec(
+            lspec!(Matmul(
+                [size, size, size],
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2)),
+                (u32, GL, row_major(2)),
+                serial
+            )),
+            X86Target::max_mem(),
+        )
+    }
+
     #[test]
     fn test_synthesis_at_peak_memory_yields_same_decision_1() {
         let spec = Spec::<X86Target>(
@@ -1145,7 +2025,7 @@ mod tests {
             MemoryLimits::Standard(MemVec::new_from_binary_scaled([0, 5, 7, 6])),
         );
 
-        let db = FilesDatabase::new(None, false, 1, 128, 1, None);
+        let db = InMemoryDatabase::new(false, 1, None);
         let (first_solutions, _, _) = top_down(&db, &spec, 1, Some(nz!(1usize)));
         let first_peak = if let Some(first_sol) = first_solutions.first() {
             first_sol.1.peaks.clone()
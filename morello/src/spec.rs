@@ -5,11 +5,11 @@ use crate::datadeps::SpecKey;
 use crate::grid::canon::CanonicalBimap;
 use crate::grid::general::{BiMap, SurMap};
 use crate::grid::linear::BimapInt;
-use crate::layout::row_major;
-use crate::memorylimits::{MemoryLimits, MemoryLimitsBimap};
+use crate::layout::{nhwc, row_major, Layout, PhysDim};
+use crate::memorylimits::{MemVec, MemoryLimits, MemoryLimitsBimap};
 use crate::scheduling::{Action, TileOut};
 use crate::target::MemoryLevel;
-use crate::target::Target;
+use crate::target::{Target, LEVEL_COUNT};
 use crate::tensorspec::{self, TensorSpec, TensorSpecAux};
 use crate::tiling::Tiling;
 use crate::utils::{
@@ -30,6 +30,7 @@ use std::marker::PhantomData;
 use std::mem;
 use std::num::NonZeroU32;
 use std::panic;
+use std::str::FromStr;
 use std::{assert_eq, debug_assert_eq};
 
 /// Whether `tile_out` actions should tile in all dimensions per Spec.
@@ -38,7 +39,7 @@ const MULTI_DIM_TILING: bool = false;
 /// An empirically chosen initial capacity for the [LogicalSpec::move_actions] results buffer.
 const MOVE_RESULTS_CAPACITY: usize = 16;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 const ARBITRARY_SPEC_MAX_SIZE: DimSize = nonzero::nonzero!(8u32);
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
@@ -68,7 +69,7 @@ pub struct PrimitiveBasics {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
-#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[cfg_attr(any(test, feature = "proptest"), derive(proptest_derive::Arbitrary))]
 pub enum PrimitiveSpecType {
     Zero,
     Move,
@@ -111,18 +112,36 @@ pub struct PrimitiveBasicsBimap {
 
 pub struct ShapeBimap(pub bool);
 
+/// An error returned when parsing the textual form of a [Spec] or [LogicalSpec].
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("{message} at position {position}")]
+pub struct SpecParseError {
+    pub message: String,
+    pub position: usize,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum CanonicalizeError {
-    #[error("Failed to canonicalize the TensorSpecAux: {0}")]
-    TensorSpecAuxCanonicalizeError(tensorspec::CanonicalizeError),
+    #[error("Failed to canonicalize the TensorSpecAux of operand {0}: {1}")]
+    TensorSpecAuxCanonicalizeError(usize, tensorspec::CanonicalizeError),
+    #[error("Expected spec_shape to have {expected} dimensions, but it has {found}")]
+    SpecShapeRankError { expected: usize, found: usize },
+    #[error("Expected {expected} {field} (one per operand), but found {found}")]
+    OperandCountError {
+        field: &'static str,
+        expected: usize,
+        found: usize,
+    },
 }
 
 impl<Tgt: Target> Spec<Tgt> {
     pub fn canonicalize(&mut self) -> Result<(), CanonicalizeError> {
+        // Canonicalize the LogicalSpec first, which also checks that it is well-formed.
+        self.0.canonicalize()?;
         let parameters = self.0.parameters();
         let levels = parameters.iter().map(|p| p.level()).collect::<Vec<_>>();
         self.1.zero_levels_slower_than_all::<Tgt>(&levels);
-        self.0.canonicalize()
+        Ok(())
     }
 
     pub fn is_canonical(&self) -> bool {
@@ -158,7 +177,20 @@ impl<Tgt: Target> Display for Spec<Tgt> {
     }
 }
 
-#[cfg(test)]
+/// Parses the format written by [Spec]'s [Display] implementation, such as
+/// `(Move((4×4, f32), (4×4, f32, L1)), [64, 1024, 0, 0])`.
+impl<Tgt: Target> FromStr for Spec<Tgt> {
+    type Err = SpecParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = SpecParser::new(s);
+        let spec = parser.spec()?;
+        parser.expect_end()?;
+        Ok(spec)
+    }
+}
+
+#[cfg(any(test, feature = "proptest"))]
 impl<Tgt: Target> proptest::arbitrary::Arbitrary for Spec<Tgt> {
     type Parameters = (Option<DimSize>, Option<u64>);
     type Strategy = proptest::strategy::BoxedStrategy<Spec<Tgt>>;
@@ -182,7 +214,7 @@ impl<Tgt: Target> proptest::arbitrary::Arbitrary for Spec<Tgt> {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 pub fn arb_canonical_spec<Tgt: Target>(
     max_size: Option<DimSize>,
     max_memory: Option<u64>,
@@ -421,7 +453,7 @@ impl Display for PrimitiveBasics {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 impl proptest::arbitrary::Arbitrary for PrimitiveBasics {
     type Parameters = Option<DimSize>;
     type Strategy = proptest::strategy::BoxedStrategy<PrimitiveBasics>;
@@ -663,15 +695,22 @@ impl<Tgt: Target> LogicalSpec<Tgt> {
         match self {
             LogicalSpec::Primitive(basics, primitive_aux, _) => match &basics.typ {
                 PrimitiveSpecType::Matmul { accum: _ } | PrimitiveSpecType::Conv { accum: _ } => {
-                    for (shp, aux) in basics.parameter_shapes().iter().zip(primitive_aux) {
+                    check_primitive_counts(basics, primitive_aux)?;
+                    for (i, (shp, aux)) in basics
+                        .parameter_shapes()
+                        .iter()
+                        .zip(primitive_aux)
+                        .enumerate()
+                    {
                         aux.canonicalize(shp)
-                            .map_err(CanonicalizeError::TensorSpecAuxCanonicalizeError)?;
+                            .map_err(|e| CanonicalizeError::TensorSpecAuxCanonicalizeError(i, e))?;
                     }
                 }
                 PrimitiveSpecType::Move => {
-                    for aux in primitive_aux.iter_mut() {
+                    check_primitive_counts(basics, primitive_aux)?;
+                    for (i, aux) in primitive_aux.iter_mut().enumerate() {
                         aux.canonicalize(&basics.spec_shape)
-                            .map_err(CanonicalizeError::TensorSpecAuxCanonicalizeError)?;
+                            .map_err(|e| CanonicalizeError::TensorSpecAuxCanonicalizeError(i, e))?;
                     }
 
                     // It source and destination are fully contiguous and the dtypes and layouts
@@ -694,9 +733,10 @@ impl<Tgt: Target> LogicalSpec<Tgt> {
                     }
                 }
                 PrimitiveSpecType::Zero => {
+                    check_primitive_counts(basics, primitive_aux)?;
                     primitive_aux[0]
                         .canonicalize(&basics.spec_shape)
-                        .map_err(CanonicalizeError::TensorSpecAuxCanonicalizeError)?;
+                        .map_err(|e| CanonicalizeError::TensorSpecAuxCanonicalizeError(0, e))?;
                 }
             },
             LogicalSpec::Compose { .. } => todo!(),
@@ -1241,6 +1281,19 @@ impl<Tgt: Target> Display for LogicalSpec<Tgt> {
     }
 }
 
+/// Parses the format written by [LogicalSpec]'s [Display] implementation. Compose Specs are not
+/// supported.
+impl<Tgt: Target> FromStr for LogicalSpec<Tgt> {
+    type Err = SpecParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = SpecParser::new(s);
+        let logical_spec = parser.logical_spec()?;
+        parser.expect_end()?;
+        Ok(logical_spec)
+    }
+}
+
 impl<Tgt, F, A, Aa, const N: usize> SurMap for SpecSurMap<Tgt, F, A, Aa>
 where
     Tgt: Target,
@@ -1504,7 +1557,7 @@ impl BiMap for ShapeBimap {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 impl<Tgt: Target> proptest::arbitrary::Arbitrary for LogicalSpec<Tgt> {
     type Parameters = Option<DimSize>;
     type Strategy = proptest::strategy::BoxedStrategy<LogicalSpec<Tgt>>;
@@ -1534,7 +1587,7 @@ impl<Tgt: Target> proptest::arbitrary::Arbitrary for LogicalSpec<Tgt> {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 pub fn arb_canonical_logical_spec<Tgt: Target>(
     max_size: Option<DimSize>,
 ) -> impl proptest::strategy::Strategy<Value = LogicalSpec<Tgt>> {
@@ -1690,6 +1743,394 @@ pub fn conv_infer_output_shape(image_shape: &[DimSize], filters_shape: &[DimSize
         .collect()
 }
 
+/// Checks that a primitive's shape, dtypes, and [TensorSpecAux]s agree with its type, which
+/// deserialized Specs do not guarantee.
+fn check_primitive_counts<Tgt: Target>(
+    basics: &PrimitiveBasics,
+    auxes: &[TensorSpecAux<Tgt>],
+) -> Result<(), CanonicalizeError> {
+    let expected_rank = match basics.typ {
+        PrimitiveSpecType::Matmul { .. } => Some(3),
+        PrimitiveSpecType::Conv { .. } => Some(7),
+        PrimitiveSpecType::Move | PrimitiveSpecType::Zero => None,
+    };
+    if let Some(expected) = expected_rank {
+        if basics.spec_shape.len() != expected {
+            return Err(CanonicalizeError::SpecShapeRankError {
+                expected,
+                found: basics.spec_shape.len(),
+            });
+        }
+    }
+    let expected = basics.typ.operand_count();
+    for (field, found) in [("dtypes", basics.dtypes.len()), ("auxes", auxes.len())] {
+        if found != expected {
+            return Err(CanonicalizeError::OperandCountError {
+                field,
+                expected,
+                found,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Parses a [Layout] of the given rank as written by its [Display] implementation.
+#[cfg(feature = "db-stats")]
+pub(crate) fn parse_layout<Tgt: Target>(text: &str, rank: u8) -> Result<Layout, SpecParseError> {
+    match text {
+        "RM" => Ok(row_major(rank)),
+        "NHWC" => Ok(nhwc()),
+        _ => {
+            let mut parser = SpecParser::<Tgt>::new(text);
+            let layout = parser.layout()?;
+            parser.expect_end()?;
+            Ok(layout)
+        }
+    }
+}
+
+/// A recursive-descent parser for the textual form of [Spec]s and [LogicalSpec]s produced by their
+/// [Display] implementations.
+struct SpecParser<'s, Tgt: Target> {
+    input: &'s str,
+    pos: usize,
+    phantom: PhantomData<Tgt>,
+}
+
+impl<'s, Tgt: Target> SpecParser<'s, Tgt> {
+    fn new(input: &'s str) -> Self {
+        SpecParser {
+            input,
+            pos: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, SpecParseError> {
+        Err(SpecParseError {
+            message: message.into(),
+            position: self.pos,
+        })
+    }
+
+    fn rest(&self) -> &'s str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.rest().chars().next()
+    }
+
+    /// Consume `c` if it is the next non-whitespace character.
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), SpecParseError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(format!("expected '{c}'"))
+        }
+    }
+
+    fn expect_end(&mut self) -> Result<(), SpecParseError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => self.error("unexpected trailing input"),
+        }
+    }
+
+    /// Consume an alphanumeric word.
+    fn word(&mut self) -> Result<&'s str, SpecParseError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return self.error("expected a word");
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, SpecParseError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        match rest[..len].parse() {
+            Ok(n) => {
+                self.pos += len;
+                Ok(n)
+            }
+            Err(_) => self.error("expected a number"),
+        }
+    }
+
+    fn dim_size(&mut self) -> Result<DimSize, SpecParseError> {
+        let start = self.pos;
+        let n = self.number::<u32>()?;
+        DimSize::new(n).ok_or(SpecParseError {
+            message: String::from("dimensions must be non-zero"),
+            position: start,
+        })
+    }
+
+    fn spec(&mut self) -> Result<Spec<Tgt>, SpecParseError> {
+        self.expect('(')?;
+        let logical_spec = self.logical_spec()?;
+        self.expect(',')?;
+        let memory_limits = self.memory_limits()?;
+        self.expect(')')?;
+        Ok(Spec(logical_spec, memory_limits))
+    }
+
+    fn memory_limits(&mut self) -> Result<MemoryLimits, SpecParseError> {
+        self.expect('[')?;
+        let mut limits = Vec::with_capacity(LEVEL_COUNT);
+        loop {
+            self.skip_whitespace();
+            let limit_start = self.pos;
+            let limit = self.number::<u64>()?;
+            if limit != 0 && !limit.is_power_of_two() {
+                self.pos = limit_start;
+                return self.error("memory limits must be zero or a power of two");
+            }
+            limits.push(limit);
+            if !self.eat(',') {
+                break;
+            }
+        }
+        self.expect(']')?;
+        match limits.try_into() {
+            Ok(limits) => Ok(MemoryLimits::Standard(MemVec::new(limits))),
+            Err(_) => self.error(format!("expected {LEVEL_COUNT} memory limits")),
+        }
+    }
+
+    fn logical_spec(&mut self) -> Result<LogicalSpec<Tgt>, SpecParseError> {
+        let start = self.pos;
+        let typ = match self.word()? {
+            "Zero" => PrimitiveSpecType::Zero,
+            "Move" => PrimitiveSpecType::Move,
+            "Matmul" => PrimitiveSpecType::Matmul { accum: false },
+            "MatmulAccum" => PrimitiveSpecType::Matmul { accum: true },
+            "Conv" => PrimitiveSpecType::Conv { accum: false },
+            "ConvAccum" => PrimitiveSpecType::Conv { accum: true },
+            w => {
+                self.pos = start;
+                return self.error(format!("unsupported Spec type: {w}"));
+            }
+        };
+
+        self.expect('(')?;
+        let mut operands = vec![];
+        let mut serial_only = false;
+        loop {
+            if self.peek() == Some('(') {
+                operands.push(self.tensor_spec()?);
+            } else if self.word()? == "serial" {
+                serial_only = true;
+            } else {
+                return self.error("expected an operand or 'serial'");
+            }
+            if serial_only || !self.eat(',') {
+                break;
+            }
+        }
+        self.expect(')')?;
+
+        if operands.len() != typ.operand_count() {
+            self.pos = start;
+            return self.error(format!(
+                "{typ} takes {} operands, but {} were given",
+                typ.operand_count(),
+                operands.len()
+            ));
+        }
+        let shapes = operands.iter().map(|o| o.shape()).collect::<Vec<_>>();
+        let spec_shape = match (typ, &shapes[..]) {
+            (PrimitiveSpecType::Matmul { .. }, [[m, k], [_, n], _]) => vec![*m, *k, *n],
+            (PrimitiveSpecType::Conv { .. }, [[b, c, h, w], [f, _, fh, fw], _])
+                if h >= fh && w >= fw =>
+            {
+                vec![*b, *f, *c, *h, *w, *fh, *fw]
+            }
+            (PrimitiveSpecType::Move | PrimitiveSpecType::Zero, [s, ..]) => s.to_vec(),
+            _ => {
+                self.pos = start;
+                return self.error(format!("invalid operand shapes for {typ}"));
+            }
+        };
+        let basics = PrimitiveBasics {
+            typ,
+            spec_shape,
+            dtypes: operands.iter().map(|o| o.dtype()).collect(),
+        };
+        if basics.parameter_shapes().iter().ne(shapes) {
+            self.pos = start;
+            return self.error(format!("inconsistent operand shapes for {typ}"));
+        }
+        Ok(LogicalSpec::Primitive(
+            basics,
+            operands.into_iter().map(|o| o.aux).collect(),
+            serial_only,
+        ))
+    }
+
+    fn tensor_spec(&mut self) -> Result<TensorSpec<Tgt>, SpecParseError> {
+        self.expect('(')?;
+        let mut shape = vec![self.dim_size()?];
+        while self.eat('×') || self.eat('x') {
+            shape.push(self.dim_size()?);
+        }
+        self.expect(',')?;
+        let dtype_start = self.pos;
+        let dtype = self.word()?.parse::<Dtype>().map_err(|_| SpecParseError {
+            message: String::from("unknown dtype"),
+            position: dtype_start,
+        })?;
+
+        let mut level = Tgt::default_level();
+        let mut layout = None;
+        let mut contig = None;
+        let mut aligned = true;
+        let mut vector_size = None;
+        while self.eat(',') {
+            if matches!(self.peek(), Some('[' | '<')) {
+                layout = Some(self.layout()?);
+                continue;
+            }
+            let part_start = self.pos;
+            let part = self.word()?;
+            if part == "RM" {
+                layout = Some(row_major(u8::try_from(shape.len()).unwrap()));
+            } else if part == "NHWC" {
+                layout = Some(nhwc());
+            } else if part == "ua" {
+                aligned = false;
+            } else if let Some(c) = part.strip_prefix('c').and_then(|c| c.parse().ok()) {
+                contig = Some(c);
+            } else if let Ok(v) = part.parse::<u32>() {
+                vector_size = DimSize::new(v);
+            } else if let Some(l) = Tgt::levels().into_iter().find(|l| l.to_string() == part) {
+                level = l;
+            } else {
+                self.pos = part_start;
+                return self.error(format!("unrecognized tensor property: {part}"));
+            }
+        }
+        self.expect(')')?;
+
+        let layout = layout.unwrap_or_else(|| row_major(u8::try_from(shape.len()).unwrap()));
+        if !layout.applies_to_shape(&shape) {
+            return self.error(format!("layout {layout} does not apply to shape"));
+        }
+        let contig = contig.unwrap_or_else(|| layout.contiguous_full());
+        Ok(TensorSpec::new_noncanon_with_aux(
+            shape,
+            dtype,
+            TensorSpecAux {
+                contig,
+                aligned,
+                level,
+                layout,
+                vector_size,
+            },
+        ))
+    }
+
+    fn layout(&mut self) -> Result<Layout, SpecParseError> {
+        let start = self.pos;
+        let dims = if self.eat('<') {
+            let logical_dims = self.layout_logical_dims()?;
+            self.expect(',')?;
+            self.expect('[')?;
+            let mut physical_dims = vec![];
+            loop {
+                physical_dims.push(match self.word()? {
+                    "Dynamic" => PhysDim::Dynamic,
+                    "Packed" => {
+                        self.expect('(')?;
+                        let size = self.dim_size()?;
+                        self.expect(')')?;
+                        PhysDim::Packed(size)
+                    }
+                    "OddEven" => {
+                        self.expect('(')?;
+                        let size = self.dim_size()?;
+                        self.expect(')')?;
+                        PhysDim::OddEven(size)
+                    }
+                    w => return self.error(format!("unknown physical dimension: {w}")),
+                });
+                if !self.eat(',') {
+                    break;
+                }
+            }
+            self.expect(']')?;
+            self.expect('>')?;
+            if logical_dims.len() != physical_dims.len() {
+                self.pos = start;
+                return self.error("layout has mismatched logical and physical dimensions");
+            }
+            logical_dims.into_iter().zip(physical_dims).collect()
+        } else {
+            self.layout_logical_dims()?
+                .into_iter()
+                .map(|d| (d, PhysDim::Dynamic))
+                .collect::<Vec<_>>()
+        };
+
+        // Reject layouts which [Layout::new] would panic on.
+        let mut seen = vec![];
+        for (d, phys) in &dims {
+            let d = usize::from(*d);
+            if seen.len() <= d {
+                seen.resize(d + 1, false);
+            }
+            if seen[d] && matches!(phys, PhysDim::Dynamic) {
+                self.pos = start;
+                return self.error("only the first occurrence of a dimension may be dynamic");
+            }
+            seen[d] = true;
+        }
+        if !seen.iter().all(|&s| s) {
+            self.pos = start;
+            return self.error("layout must mention every logical dimension");
+        }
+        Ok(Layout::new(dims))
+    }
+
+    fn layout_logical_dims(&mut self) -> Result<Vec<u8>, SpecParseError> {
+        self.expect('[')?;
+        let mut dims = vec![];
+        loop {
+            dims.push(self.number()?);
+            if !self.eat(',') {
+                break;
+            }
+        }
+        self.expect(']')?;
+        Ok(dims)
+    }
+}
+
 pub mod macros {
     pub mod internal {
         use crate::common::DimSize;
@@ -1902,6 +2343,73 @@ mod tests {
         assert_eq!(spec, expected);
     }
 
+    #[test]
+    fn test_parse_spec() {
+        let text = "(MatmulAccum((2×3, u8), (3×3, i8, c0), (2×3, u16, L1, ua), serial), \
+                    [64, 1024, 32768, 0])";
+        let expected = Spec::<X86Target>(
+            lspec!(MatmulAccum(
+                [2, 3, 3],
+                (u8, GL, row_major(2)),
+                (i8, GL, row_major(2), c0),
+                (u16, crate::target::CpuMemoryLevel::L1, row_major(2), ua),
+                serial
+            )),
+            MemoryLimits::Standard(MemVec::new([64, 1024, 32768, 0])),
+        );
+        assert_eq!(text.parse::<Spec<X86Target>>().unwrap(), expected);
+        assert_eq!(expected.to_string(), text);
+    }
+
+    #[test]
+    fn test_parse_spec_rejects_non_power_of_two_limits() {
+        let text = "(Zero((4x4, u32)), [64, 1000, 0, 0])";
+        let err = text.parse::<Spec<X86Target>>().unwrap_err();
+        assert_eq!(err.position, 24);
+    }
+
+    #[test]
+    fn test_canonicalize_rejects_malformed_specs() {
+        let LogicalSpec::Primitive(basics, auxes, serial) =
+            lspec!(Zero([4, 4], (u32, GL, row_major(2))))
+        else {
+            unreachable!();
+        };
+        let mut extra_dtype = PrimitiveBasics {
+            dtypes: vec![Dtype::Uint32; 2],
+            ..basics.clone()
+        };
+        assert!(matches!(
+            LogicalSpec::<X86Target>::Primitive(extra_dtype.clone(), auxes.clone(), serial)
+                .canonicalize(),
+            Err(CanonicalizeError::OperandCountError {
+                field: "dtypes",
+                expected: 1,
+                found: 2
+            })
+        ));
+        extra_dtype.typ = PrimitiveSpecType::Matmul { accum: false };
+        assert!(matches!(
+            LogicalSpec::Primitive(extra_dtype, auxes.clone(), serial).canonicalize(),
+            Err(CanonicalizeError::SpecShapeRankError {
+                expected: 3,
+                found: 2
+            })
+        ));
+        let mut bad_layout = auxes;
+        bad_layout[0].layout = row_major(3);
+        assert!(matches!(
+            LogicalSpec::Primitive(basics, bad_layout, serial).canonicalize(),
+            Err(CanonicalizeError::TensorSpecAuxCanonicalizeError(0, _))
+        ));
+    }
+
+    #[test]
+    fn test_parse_spec_rejects_inconsistent_shapes() {
+        let text = "(Matmul((2×3, u8), (4×3, i8), (2×3, u16)), [0, 0, 0, 0])";
+        assert!(text.parse::<Spec<X86Target>>().is_err());
+    }
+
     #[test]
     fn test_gen_tile_sizes_empty() {
         assert_eq!(
@@ -2025,6 +2533,25 @@ mod tests {
             shared_test_actions_are_valid_through_consumed_memory(logical_spec)
         }
 
+        #[test]
+        fn test_parse_display_roundtrip_x86(
+            spec in arb_canonical_spec::<X86Target>(None, None)
+        ) {
+            shared_test_parse_display_roundtrip(spec)?;
+        }
+
+        #[test]
+        fn test_parse_display_roundtrip_arm(
+            spec in arb_canonical_spec::<ArmTarget>(None, None)
+        ) {
+            shared_test_parse_display_roundtrip(spec)?;
+        }
+
+        #[test]
+        fn test_parse_display_roundtrip_noncanonical(spec in any::<Spec<X86Target>>()) {
+            shared_test_parse_display_roundtrip(spec)?;
+        }
+
         #[test]
         fn test_canonicalize_is_noop_if_already_canonical(
             logical_spec in any::<LogicalSpec<X86Target>>()
@@ -2194,6 +2721,17 @@ mod tests {
         }
     }
 
+    fn shared_test_parse_display_roundtrip<Tgt: Target>(
+        spec: Spec<Tgt>,
+    ) -> Result<(), TestCaseError> {
+        let text = spec.to_string();
+        let parsed = text
+            .parse::<Spec<Tgt>>()
+            .map_err(|e| TestCaseError::fail(format!("failed to parse {text}: {e}")))?;
+        prop_assert_eq!(parsed, spec);
+        Ok(())
+    }
+
     fn shared_test_no_action_panics<Tgt: Target>(spec: Spec<Tgt>) {
         for action in spec.0.actions(None) {
             let _ = action.apply(&spec);
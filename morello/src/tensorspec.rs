@@ -44,7 +44,7 @@ pub struct TensorSpecAuxNonDepBimap<Tgt: Target> {
     phantom: std::marker::PhantomData<Tgt>,
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 #[derive(Debug, Clone)]
 pub struct TensorSpecArbMaxShape(pub Shape);
 
@@ -297,7 +297,7 @@ impl<Tgt: Target> Display for TensorSpec<Tgt> {
 ///
 /// The maximum shape and rank of the [TensorSpec] can be controlled by providing a
 /// [TensorSpecArbMaxShape].
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 impl<Tgt: Target> proptest::arbitrary::Arbitrary for TensorSpec<Tgt> {
     type Parameters = TensorSpecArbMaxShape;
     type Strategy = proptest::strategy::BoxedStrategy<TensorSpec<Tgt>>;
@@ -400,7 +400,7 @@ impl<Tgt: Target> Display for TensorSpecAux<Tgt> {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 impl<Tgt: Target> proptest::arbitrary::Arbitrary for TensorSpecAux<Tgt> {
     type Parameters = TensorSpecArbMaxShape;
     type Strategy = proptest::strategy::BoxedStrategy<TensorSpecAux<Tgt>>;
@@ -415,7 +415,7 @@ impl<Tgt: Target> proptest::arbitrary::Arbitrary for TensorSpecAux<Tgt> {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 impl Default for TensorSpecArbMaxShape {
     fn default() -> Self {
         use crate::shape;
@@ -542,7 +542,7 @@ fn tensorspec_aux_str<Tgt: Target>(aux: &TensorSpecAux<Tgt>) -> String {
     join_into_string(&parts, ", ")
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "proptest"))]
 fn arb_tensorspecaux<Tgt: Target>(
     max_shape: &[DimSize],
     dtype: Dtype,
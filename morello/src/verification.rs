@@ -1,23 +1,29 @@
 //! Extends [crate::codegen::BuiltArtifact] with methods to check correctness of lowered code.
 
 use crate::{
-    codegen::BuiltArtifact,
+    codegen::{tensor_file, BuiltArtifact, LoadedKernel},
     common::{DimSize, Dtype},
+    imp::ImplNode,
+    interpreter::interpret,
     spec::{LogicalSpec, PrimitiveBasics, PrimitiveSpecType, Spec},
-    target::Target,
+    target::{CpuTarget, Target},
     tensorspec::TensorSpec,
 };
 use ndarray::prelude::*;
 use ndarray_conv::{ConvExt, ConvMode, PaddingMode};
 use num_traits::AsPrimitive;
-use std::process::Command;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::process::{self, Command};
 use std::{
+    fmt, fs,
     io::{self, BufWriter, Write},
+    num::Wrapping,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 use tempfile::NamedTempFile;
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum DynArray<D> {
     Uint8(Array<u8, D>),
     Sint8(Array<i8, D>),
@@ -44,6 +50,178 @@ pub enum DynArrayViewMut<'a, D> {
 pub enum RunError {
     #[error("IO error: {0}")]
     IoError(#[from] io::Error),
+    #[error("Generated program exited with status code: {0}")]
+    BadExitStatus(process::ExitStatus),
+    #[error("Parameter {0} isn't row-major, so can't be given row-major input data")]
+    NonRowMajorParameter(usize),
+}
+
+/// How tensors are passed to and from a generated program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TensorFormat {
+    /// Inputs are files of little-endian values and the output is printed as text.
+    Text,
+    /// Inputs and the output are files in the [tensor_file] format.
+    Binary,
+}
+
+/// [BuiltArtifact::run_with_input_data] uses [TensorFormat::Binary] if any tensor has at least
+/// this many values.
+pub const BINARY_FORMAT_MIN_VALUES: usize = 4096;
+
+/// How [BuiltArtifact::check_correctness_with_options] chooses input values.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum InputStrategy {
+    /// Every value of the dtype in ascending order, repeated. Floats cycle through 0 to 255.
+    #[default]
+    Sequential,
+    /// Uniformly random integers and random multiples of 1/8 in [-1, 1] for floats.
+    Random,
+    /// [InputStrategy::Random] values mixed with edge cases: integer minimums and maximums,
+    /// negative zero, infinities, NaN, and subnormals.
+    Adversarial,
+}
+
+/// Options for [BuiltArtifact::check_correctness_with_options].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckOptions {
+    pub strategy: InputStrategy,
+    /// The number of sets of inputs to check. [InputStrategy::Sequential] always generates the
+    /// same inputs, so is checked once.
+    pub trials: u32,
+    /// The seed from which the first trial's inputs are generated. Trial `i` uses `seed + i`.
+    pub seed: u64,
+}
+
+/// How far floating-point values may be from expected values and still be considered correct.
+///
+/// A value is within tolerance if it is at most `max_ulps` units in the last place from the
+/// expected value, or if their difference is at most `abs_eps + rel_eps * magnitude`. The
+/// magnitude is `|expected|` or, for reductions, the sum of the absolute values of the terms
+/// (see [DynArray::compare_with_magnitudes]). Integer values must always be equal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    pub max_ulps: u32,
+    pub rel_eps: f32,
+    pub abs_eps: f32,
+}
+
+/// Why an array didn't match its expected value. See [DynArray::compare].
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    #[error("expected dtype {expected} but got {actual}")]
+    Dtype { expected: Dtype, actual: Dtype },
+    #[error("expected shape {expected:?} but got {actual:?}")]
+    Shape {
+        expected: Vec<usize>,
+        actual: Vec<usize>,
+    },
+    #[error("{count} values differ; worst at {worst}")]
+    Values {
+        /// The value furthest from its expected value.
+        worst: Difference,
+        /// Up to [REPORTED_DIFFERENCES] of the differing values, in row-major order.
+        first: Vec<Difference>,
+        /// The number of values outside the tolerance.
+        count: usize,
+    },
+    /// The implementation produced no output, e.g., because it crashed.
+    #[error("failed to run: {0}")]
+    RunFailed(String),
+}
+
+/// A value outside the tolerance of its expected value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    pub index: Vec<usize>,
+    pub expected: f64,
+    pub actual: f64,
+}
+
+/// The number of differing values listed in a [Mismatch::Values].
+pub const REPORTED_DIFFERENCES: usize = 8;
+
+/// A report of an implementation's output differing from its [Spec]'s.
+#[derive(Debug, Clone)]
+pub struct CorrectnessFailure {
+    /// The Spec checked, in its [Display](fmt::Display) syntax.
+    pub spec: String,
+    pub strategy: InputStrategy,
+    /// The seed from which the inputs were generated, if not [InputStrategy::Sequential].
+    pub seed: Option<u64>,
+    /// The arguments given to the implementation, including the output's initial value.
+    pub inputs: Vec<DynArray<IxDyn>>,
+    pub expected: DynArray<IxDyn>,
+    /// The implementation's output, or `None` if it failed to run.
+    pub actual: Option<DynArray<IxDyn>>,
+    pub mismatch: Mismatch,
+    /// The emitted source, if it was kept (see [crate::codegen::BuildOptions::keep_sources]).
+    pub source_path: Option<PathBuf>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}: expected {} but got {}",
+            self.index, self.expected, self.actual
+        )
+    }
+}
+
+impl fmt::Display for CorrectnessFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Spec: {}", self.spec)?;
+        match self.seed {
+            Some(seed) => writeln!(f, "Inputs: {:?} with seed {seed}", self.strategy)?,
+            None => writeln!(f, "Inputs: {:?}", self.strategy)?,
+        }
+        writeln!(f, "Mismatch: {}", self.mismatch)?;
+        if let Mismatch::Values { first, .. } = &self.mismatch {
+            for difference in first {
+                writeln!(f, "  {difference}")?;
+            }
+        }
+        match &self.source_path {
+            Some(source_path) => write!(f, "Source: {}", source_path.display()),
+            None => write!(f, "Source: not kept"),
+        }
+    }
+}
+
+impl std::error::Error for CorrectnessFailure {}
+
+impl CorrectnessFailure {
+    /// Writes the report and its tensors to `dir`, creating it if needed.
+    ///
+    /// The report is written to `report.txt` and the Spec alone to `spec.txt`. The inputs are
+    /// written to `input_0.bin`, `input_1.bin`, etc., and the expected and actual outputs to
+    /// `expected.bin` and `actual.bin`, all in the [tensor_file] format. `actual.bin` is omitted
+    /// if the implementation failed to run.
+    pub fn dump(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join("spec.txt"), format!("{}\n", self.spec))?;
+        fs::write(dir.join("report.txt"), format!("{self}\n"))?;
+        for (i, input) in self.inputs.iter().enumerate() {
+            write_tensor_file(input, dir.join(format!("input_{i}.bin")))?;
+        }
+        write_tensor_file(&self.expected, dir.join("expected.bin"))?;
+        if let Some(actual) = &self.actual {
+            write_tensor_file(actual, dir.join("actual.bin"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        CheckOptions {
+            strategy: InputStrategy::default(),
+            trials: 1,
+            seed: 0,
+        }
+    }
 }
 
 impl BuiltArtifact {
@@ -51,27 +229,53 @@ impl BuiltArtifact {
     ///
     /// This method can be used for a little extra defense against bugs in Morello or the underlying
     /// C compiler.
-    pub fn check_correctness<Tgt: Target>(&self, spec: &Spec<Tgt>) -> bool {
-        let test_result = match &spec.0 {
-            LogicalSpec::Primitive(PrimitiveBasics { .. }, _, _) => {
-                test_artifact_correct_inner(spec, self)
-            }
-            LogicalSpec::Compose { .. } => todo!(),
-        };
-        if test_result {
-            log::debug!("Artifact passed correctness check");
-        } else {
-            log::debug!("Artifact failed correctness check");
-        }
-        test_result
+    pub fn check_correctness<Tgt: Target>(
+        &self,
+        spec: &Spec<Tgt>,
+    ) -> Result<(), CorrectnessFailure> {
+        self.check_correctness_with_options(spec, &CheckOptions::default())
+    }
+
+    /// Like [BuiltArtifact::check_correctness], but checks inputs chosen according to `options`.
+    pub fn check_correctness_with_options<Tgt: Target>(
+        &self,
+        spec: &Spec<Tgt>,
+        options: &CheckOptions,
+    ) -> Result<(), CorrectnessFailure> {
+        check_correctness_with(spec, options, |arguments| {
+            self.run_with_input_data(arguments)
+        })
+        .map_err(|mut failure| {
+            failure.source_path = self.source_path().map(Path::to_path_buf);
+            failure
+        })
     }
 
     /// Run the binary with provided input data and return the output.
     ///
-    /// Input data should be in row-major layout. Output data will also be row-major.
+    /// Input data should be in row-major layout. Output data will also be row-major. Tensors are
+    /// passed in [TensorFormat::Binary] if any has at least [BINARY_FORMAT_MIN_VALUES] values,
+    /// and otherwise in [TensorFormat::Text].
     pub fn run_with_input_data(
         &self,
         arguments: &[DynArray<IxDyn>],
+    ) -> Result<DynArray<IxDyn>, RunError> {
+        let format = if arguments
+            .iter()
+            .any(|arg| arg.len() >= BINARY_FORMAT_MIN_VALUES)
+        {
+            TensorFormat::Binary
+        } else {
+            TensorFormat::Text
+        };
+        self.run_with_input_data_as(arguments, format)
+    }
+
+    /// Like [BuiltArtifact::run_with_input_data], but passes tensors in the given format.
+    pub fn run_with_input_data_as(
+        &self,
+        arguments: &[DynArray<IxDyn>],
+        format: TensorFormat,
     ) -> Result<DynArray<IxDyn>, RunError> {
         assert_eq!(arguments.len(), self.parameter_dtypes().len());
 
@@ -80,6 +284,9 @@ impl BuiltArtifact {
         let mut files = Vec::with_capacity(arguments.len());
         for arg in arguments {
             let mut buffered_writer = BufWriter::new(tempfile::NamedTempFile::new()?);
+            if format == TensorFormat::Binary {
+                write_tensor_file_header(arg, &mut buffered_writer)?;
+            }
             write_inputs(arg, &mut buffered_writer)?;
             buffered_writer.flush()?;
             files.push(
@@ -89,16 +296,106 @@ impl BuiltArtifact {
             );
         }
 
-        // Pass the filename as an argument.
+        // Pass the filenames as arguments, preceded by `--binary` and the output path if
+        // reading the output from a file.
+        let output_file = match format {
+            TensorFormat::Text => None,
+            TensorFormat::Binary => Some(NamedTempFile::new()?),
+        };
         let mut cmd = Command::new(&self.binary_path);
+        if let Some(output_file) = &output_file {
+            cmd.arg("--binary").arg(output_file.path());
+        }
         cmd.args(files.iter().map(|f| f.path()));
         log::debug!("Running {:?}", cmd);
         let output = cmd.output()?;
+        if !output.status.success() {
+            return Err(RunError::BadExitStatus(output.status));
+        }
+
+        let dtype = *self.parameter_dtypes().last().unwrap();
+        Ok(match output_file {
+            None => read_output(dtype, &output.stdout)?,
+            Some(output_file) => read_binary_output(dtype, &std::fs::read(output_file.path())?)?,
+        })
+    }
+}
+
+impl LoadedKernel {
+    /// Check whether the kernel correctly implements a [Spec].
+    ///
+    /// Like [BuiltArtifact::check_correctness], but calls the kernel in-process.
+    pub fn check_correctness<Tgt: Target>(
+        &self,
+        spec: &Spec<Tgt>,
+    ) -> Result<(), CorrectnessFailure> {
+        self.check_correctness_with_options(spec, &CheckOptions::default())
+    }
 
-        Ok(read_output(
-            *self.parameter_dtypes().last().unwrap(),
-            &output.stdout,
-        )?)
+    /// Like [LoadedKernel::check_correctness], but checks inputs chosen according to `options`.
+    pub fn check_correctness_with_options<Tgt: Target>(
+        &self,
+        spec: &Spec<Tgt>,
+        options: &CheckOptions,
+    ) -> Result<(), CorrectnessFailure> {
+        check_correctness_with(spec, options, |arguments| {
+            self.run_with_input_data(arguments)
+        })
+    }
+
+    /// Call the kernel on copies of the provided input data and return the output.
+    ///
+    /// Input data should be in row-major layout. Output data will also be row-major. Inputs
+    /// aren't converted to other layouts, so fails if any parameter isn't row-major.
+    pub fn run_with_input_data(
+        &self,
+        arguments: &[DynArray<IxDyn>],
+    ) -> Result<DynArray<IxDyn>, RunError> {
+        if let Some(parameter) = self.first_non_row_major_parameter() {
+            return Err(RunError::NonRowMajorParameter(parameter));
+        }
+        assert_eq!(arguments.len(), self.parameter_dtypes().len());
+        let mut buffers = self.make_buffers();
+        for ((arg, dtype), buffer) in arguments
+            .iter()
+            .zip(self.parameter_dtypes())
+            .zip(&mut buffers)
+        {
+            assert_eq!(arg.dtype(), *dtype);
+            write_inputs(arg, &mut buffer.as_bytes_mut())
+                .expect("buffer should be large enough for argument");
+        }
+        self.call(&mut buffers);
+        let output = arguments.last().unwrap();
+        Ok(dyn_array_from_le_bytes(
+            output.dtype(),
+            output.shape(),
+            buffers.last().unwrap().as_bytes(),
+        ))
+    }
+}
+
+impl<Tgt: CpuTarget> ImplNode<Tgt> {
+    /// Check whether the Impl correctly implements a [Spec] by interpreting it.
+    ///
+    /// Unlike [BuiltArtifact::check_correctness], this needs no C compiler and doesn't exercise
+    /// code generation. See [crate::interpreter]. An Impl which can't be interpreted, e.g.,
+    /// because it indexes out of bounds, fails with a [Mismatch::RunFailed].
+    pub fn check_correctness_interpreted(
+        &self,
+        spec: &Spec<Tgt>,
+    ) -> Result<(), CorrectnessFailure> {
+        self.check_correctness_interpreted_with_options(spec, &CheckOptions::default())
+    }
+
+    /// Like [ImplNode::check_correctness_interpreted], but checks inputs chosen according to
+    /// `options`.
+    pub fn check_correctness_interpreted_with_options(
+        &self,
+        spec: &Spec<Tgt>,
+        options: &CheckOptions,
+    ) -> Result<(), CorrectnessFailure> {
+        check_correctness_with(spec, options, |arguments| interpret(self, arguments))
     }
 }
 
@@ -191,6 +488,28 @@ impl<D: ndarray::Dimension> DynArray<D> {
         }
     }
 
+    pub fn dtype(&self) -> Dtype {
+        match self {
+            DynArray::Uint8(_) => Dtype::Uint8,
+            DynArray::Sint8(_) => Dtype::Sint8,
+            DynArray::Uint16(_) => Dtype::Uint16,
+            DynArray::Sint16(_) => Dtype::Sint16,
+            DynArray::Uint32(_) => Dtype::Uint32,
+            DynArray::Sint32(_) => Dtype::Sint32,
+            DynArray::Float32(_) => Dtype::Float32,
+            DynArray::Bfloat16(_) => Dtype::Bfloat16,
+        }
+    }
+
+    /// Returns the number of values in the array.
+    pub fn len(&self) -> usize {
+        self.shape().iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn slice_copy<I>(&self, info: I) -> DynArray<I::OutDim>
     where
         I: ndarray::SliceArg<D>,
@@ -243,6 +562,22 @@ impl<D: ndarray::Dimension> DynArray<D> {
         self.into_dimensionality::<IxDyn>().unwrap()
     }
 
+    /// Returns the absolute values of the elements. Signed integers wrap, as `i32::MIN` does.
+    pub fn abs(&self) -> Self {
+        match self {
+            DynArray::Uint8(a) => DynArray::Uint8(a.clone()),
+            DynArray::Sint8(a) => DynArray::Sint8(a.mapv(i8::wrapping_abs)),
+            DynArray::Uint16(a) => DynArray::Uint16(a.clone()),
+            DynArray::Sint16(a) => DynArray::Sint16(a.mapv(i16::wrapping_abs)),
+            DynArray::Uint32(a) => DynArray::Uint32(a.clone()),
+            DynArray::Sint32(a) => DynArray::Sint32(a.mapv(i32::wrapping_abs)),
+            DynArray::Float32(a) => DynArray::Float32(a.mapv(f32::abs)),
+            DynArray::Bfloat16(a) => {
+                DynArray::Bfloat16(a.mapv(|v| half::bf16::from_bits(v.to_bits() & 0x7fff)))
+            }
+        }
+    }
+
     pub fn zero(&mut self) {
         match self {
             DynArray::Uint8(a) => a.fill(0),
@@ -296,47 +631,50 @@ impl<D: ndarray::Dimension> DynArray<D> {
 }
 
 impl DynArray<Ix2> {
+    /// Adds the product of `self` and `rhs` to `out`, computed in `out`'s dtype.
+    ///
+    /// Integer arithmetic wraps on overflow, as in emitted code.
     pub fn dot_inplace(&self, rhs: &DynArray<Ix2>, out: &mut DynArray<Ix2>) {
         match out {
             DynArray::Uint8(o) => {
                 let l = self.saturating_cast::<u8>();
                 let r = rhs.saturating_cast::<u8>();
-                o.assign(&l.dot(&r));
+                wrapping_dot_add(&l, &r, o);
             }
             DynArray::Sint8(o) => {
                 let l = self.saturating_cast::<i8>();
                 let r = rhs.saturating_cast::<i8>();
-                o.assign(&l.dot(&r));
+                wrapping_dot_add(&l, &r, o);
             }
             DynArray::Uint16(o) => {
                 let l = self.saturating_cast::<u16>();
                 let r = rhs.saturating_cast::<u16>();
-                o.assign(&l.dot(&r));
+                wrapping_dot_add(&l, &r, o);
             }
             DynArray::Sint16(o) => {
                 let l = self.saturating_cast::<i16>();
                 let r = rhs.saturating_cast::<i16>();
-                o.assign(&l.dot(&r));
+                wrapping_dot_add(&l, &r, o);
             }
             DynArray::Uint32(o) => {
                 let l = self.saturating_cast::<u32>();
                 let r = rhs.saturating_cast::<u32>();
-                o.assign(&l.dot(&r));
+                wrapping_dot_add(&l, &r, o);
             }
             DynArray::Sint32(o) => {
                 let l = self.saturating_cast::<i32>();
                 let r = rhs.saturating_cast::<i32>();
-                o.assign(&l.dot(&r));
+                wrapping_dot_add(&l, &r, o);
             }
             DynArray::Float32(o) => {
                 let l = self.saturating_cast::<f32>();
                 let r = rhs.saturating_cast::<f32>();
-                o.assign(&l.dot(&r));
+                *o += &l.dot(&r);
             }
             DynArray::Bfloat16(o) => {
                 let l = self.saturating_cast::<half::bf16>();
                 let r = rhs.saturating_cast::<half::bf16>();
-                o.assign(&l.dot(&r));
+                *o += &l.dot(&r);
             }
         }
     }
@@ -396,6 +734,133 @@ impl<D: ndarray::Dimension> PartialEq for DynArray<D> {
     }
 }
 
+impl Tolerance {
+    pub const EXACT: Tolerance = Tolerance {
+        max_ulps: 0,
+        rel_eps: 0.0,
+        abs_eps: 0.0,
+    };
+
+    /// Returns a tolerance for values of `dtype`, each a reduction of `reduction_len` terms.
+    ///
+    /// Rounding error grows with the number of terms summed, and summing in a different order
+    /// (e.g., after a split, or with multiple accumulators) changes it, so the tolerance scales
+    /// with `reduction_len`. It grows with the square root, as typical rounding error does, so
+    /// that long reductions, especially in low precision, still reject clearly wrong results.
+    /// Integer dtypes are always [Tolerance::EXACT].
+    pub fn for_reduction(dtype: Dtype, reduction_len: u32) -> Self {
+        let scale = (reduction_len.max(1) as f32).sqrt();
+        let epsilon = match dtype {
+            Dtype::Float32 => f32::EPSILON,
+            Dtype::Bfloat16 => half::bf16::EPSILON.to_f32(),
+            Dtype::Uint8
+            | Dtype::Sint8
+            | Dtype::Uint16
+            | Dtype::Sint16
+            | Dtype::Uint32
+            | Dtype::Sint32 => return Tolerance::EXACT,
+        };
+        Tolerance {
+            max_ulps: (2.0 * scale).ceil() as u32,
+            rel_eps: epsilon * scale,
+            abs_eps: epsilon * scale,
+        }
+    }
+
+    fn accepts_f32(&self, actual: f32, expected: f32, magnitude: f32, ulps: u64) -> bool {
+        if actual == expected || (actual.is_nan() && expected.is_nan()) {
+            return true;
+        }
+        if actual.is_nan() || expected.is_nan() {
+            return false;
+        }
+        ulps <= u64::from(self.max_ulps)
+            || (actual - expected).abs() <= self.abs_eps + self.rel_eps * magnitude.abs()
+    }
+}
+
+impl<D: ndarray::Dimension> DynArray<D> {
+    /// Compares this array to `expected`, allowing floating-point values to differ by `tolerance`.
+    ///
+    /// Unlike `==`, which compares floating-point values exactly, this accepts outputs of
+    /// reordered reductions. On failure, reports the value furthest from its expected value.
+    pub fn compare(&self, expected: &Self, tolerance: Tolerance) -> Result<(), Mismatch> {
+        self.compare_with_magnitudes(expected, expected, tolerance)
+    }
+
+    /// Like [DynArray::compare], but scales the tolerance of each value by the absolute value at
+    /// the same index in `magnitudes` instead of by the expected value.
+    ///
+    /// The rounding error of a reduction grows with its partial sums, which can be far larger
+    /// than a result near zero after cancellation, so pass the sum of the absolute values of
+    /// each value's terms (see [reduction_magnitudes]). `magnitudes` must have `expected`'s
+    /// dtype and shape.
+    pub fn compare_with_magnitudes(
+        &self,
+        expected: &Self,
+        magnitudes: &Self,
+        tolerance: Tolerance,
+    ) -> Result<(), Mismatch> {
+        assert_eq!(magnitudes.dtype(), expected.dtype());
+        assert_eq!(magnitudes.shape(), expected.shape());
+        if self.dtype() != expected.dtype() {
+            return Err(Mismatch::Dtype {
+                expected: expected.dtype(),
+                actual: self.dtype(),
+            });
+        }
+        if self.shape() != expected.shape() {
+            return Err(Mismatch::Shape {
+                expected: expected.shape().to_vec(),
+                actual: self.shape().to_vec(),
+            });
+        }
+        match (self, expected, magnitudes) {
+            (Self::Uint8(a), Self::Uint8(e), Self::Uint8(m)) => {
+                compare_values(a, e, m, |a, e, _| a == e, f64::from)
+            }
+            (Self::Sint8(a), Self::Sint8(e), Self::Sint8(m)) => {
+                compare_values(a, e, m, |a, e, _| a == e, f64::from)
+            }
+            (Self::Uint16(a), Self::Uint16(e), Self::Uint16(m)) => {
+                compare_values(a, e, m, |a, e, _| a == e, f64::from)
+            }
+            (Self::Sint16(a), Self::Sint16(e), Self::Sint16(m)) => {
+                compare_values(a, e, m, |a, e, _| a == e, f64::from)
+            }
+            (Self::Uint32(a), Self::Uint32(e), Self::Uint32(m)) => {
+                compare_values(a, e, m, |a, e, _| a == e, f64::from)
+            }
+            (Self::Sint32(a), Self::Sint32(e), Self::Sint32(m)) => {
+                compare_values(a, e, m, |a, e, _| a == e, f64::from)
+            }
+            (Self::Float32(a), Self::Float32(e), Self::Float32(m)) => compare_values(
+                a,
+                e,
+                m,
+                |a, e, m| {
+                    let ulps = ordered_float_bits(a.to_bits().into(), 32)
+                        .abs_diff(ordered_float_bits(e.to_bits().into(), 32));
+                    tolerance.accepts_f32(a, e, m, ulps)
+                },
+                f64::from,
+            ),
+            (Self::Bfloat16(a), Self::Bfloat16(e), Self::Bfloat16(m)) => compare_values(
+                a,
+                e,
+                m,
+                |a, e, m| {
+                    let ulps = ordered_float_bits(a.to_bits().into(), 16)
+                        .abs_diff(ordered_float_bits(e.to_bits().into(), 16));
+                    tolerance.accepts_f32(a.to_f32(), e.to_f32(), m.to_f32(), ulps)
+                },
+                f64::from,
+            ),
+            _ => unreachable!("dtypes were checked to match"),
+        }
+    }
+}
+
 impl<D> From<Array<u8, D>> for DynArray<D> {
     fn from(value: Array<u8, D>) -> Self {
         DynArray::Uint8(value)
@@ -463,30 +928,198 @@ where
     }
 }
 
-fn test_artifact_correct_inner<Tgt>(spec: &Spec<Tgt>, built_artifact: &BuiltArtifact) -> bool
+/// Checks `run`, given inputs for `spec`, returns the output `spec` would.
+///
+/// An error from `run` is reported as a [Mismatch::RunFailed].
+fn check_correctness_with<Tgt, F, E>(
+    spec: &Spec<Tgt>,
+    options: &CheckOptions,
+    mut run: F,
+) -> Result<(), CorrectnessFailure>
 where
     Tgt: Target,
+    F: FnMut(&[DynArray<IxDyn>]) -> Result<DynArray<IxDyn>, E>,
+    E: fmt::Display,
+{
+    let seeds = match options.strategy {
+        InputStrategy::Sequential => vec![None],
+        InputStrategy::Random | InputStrategy::Adversarial => (0..options.trials)
+            .map(|trial| Some(options.seed.wrapping_add(trial.into())))
+            .collect(),
+    };
+    let test_result = match &spec.0 {
+        LogicalSpec::Primitive(PrimitiveBasics { .. }, _, _) => seeds
+            .into_iter()
+            .try_for_each(|seed| test_correct_inner(spec, options.strategy, seed, &mut run)),
+        LogicalSpec::Compose { .. } => todo!(),
+    };
+    match &test_result {
+        Ok(()) => log::debug!("Artifact passed correctness check"),
+        Err(failure) => log::debug!("Artifact failed correctness check: {}", failure.mismatch),
+    }
+    test_result
+}
+
+fn test_correct_inner<Tgt, F, E>(
+    spec: &Spec<Tgt>,
+    strategy: InputStrategy,
+    seed: Option<u64>,
+    run: &mut F,
+) -> Result<(), CorrectnessFailure>
+where
+    Tgt: Target,
+    F: FnMut(&[DynArray<IxDyn>]) -> Result<DynArray<IxDyn>, E>,
+    E: fmt::Display,
 {
     // Generate some test inputs (and output).
     let parameters = spec.0.parameters();
-    let mut concrete_tensors = parameters
-        .iter()
-        .map(make_array_input_dyn::<Tgt>)
-        .collect::<Vec<_>>();
+    let mut concrete_tensors = match seed {
+        None => parameters
+            .iter()
+            .map(make_array_input_dyn::<Tgt>)
+            .collect::<Vec<_>>(),
+        Some(seed) => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let adversarial = strategy == InputStrategy::Adversarial;
+            parameters
+                .iter()
+                .map(|p| make_array_input_random_dyn(p, adversarial, &mut rng))
+                .collect()
+        }
+    };
 
     // Gather output from program. Do this before execute so that the expected output
     // isn't given to the generated program.
-    let lowered_output = built_artifact
-        .run_with_input_data(&concrete_tensors)
-        .unwrap();
+    let lowered_output = run(&concrete_tensors);
 
-    // Compute expected output
+    // Compute expected output. Keep the inputs for the failure report.
+    let inputs = concrete_tensors.clone();
     concrete_tensors = spec.0.execute(concrete_tensors);
 
-    lowered_output == concrete_tensors[spec.0.output_idx()]
+    let expected_output = concrete_tensors.swap_remove(spec.0.output_idx());
+    let tolerance = Tolerance::for_reduction(expected_output.dtype(), reduction_len(&spec.0));
+    let (actual, result) = match lowered_output {
+        Ok(actual) => {
+            let result = if tolerance == Tolerance::EXACT {
+                actual.compare(&expected_output, tolerance)
+            } else {
+                let magnitudes = reduction_magnitudes(&spec.0, &inputs);
+                actual.compare_with_magnitudes(&expected_output, &magnitudes, tolerance)
+            };
+            (Some(actual), result)
+        }
+        Err(e) => (None, Err(Mismatch::RunFailed(e.to_string()))),
+    };
+    result.map_err(|mismatch| CorrectnessFailure {
+        spec: spec.to_string(),
+        strategy,
+        seed,
+        inputs,
+        expected: expected_output,
+        actual,
+        mismatch,
+        source_path: None,
+    })
+}
+
+/// Returns the number of terms summed into each output value of `spec`.
+pub(crate) fn reduction_len<Tgt: Target>(spec: &LogicalSpec<Tgt>) -> u32 {
+    match spec {
+        LogicalSpec::Primitive(basics, _, _) => match basics.typ {
+            PrimitiveSpecType::Matmul { .. } => basics.spec_shape[1].get(),
+            // Each output value sums over channels and the filters' height and width.
+            PrimitiveSpecType::Conv { .. } => [2, 5, 6]
+                .into_iter()
+                .map(|i| basics.spec_shape[i].get())
+                .product(),
+            PrimitiveSpecType::Zero | PrimitiveSpecType::Move => 1,
+        },
+        LogicalSpec::Compose { .. } => todo!(),
+    }
 }
 
-fn make_array_input_dyn<Tgt: Target>(input: &TensorSpec<Tgt>) -> DynArray<IxDyn> {
+/// Returns, for each output value of `spec` given `inputs`, the sum of the absolute values of
+/// the terms reduced into it, including the output's initial value when accumulating.
+///
+/// This bounds the magnitude of every partial sum, however the reduction is ordered.
+pub(crate) fn reduction_magnitudes<Tgt: Target>(
+    spec: &LogicalSpec<Tgt>,
+    inputs: &[DynArray<IxDyn>],
+) -> DynArray<IxDyn> {
+    let mut outputs = spec.execute(inputs.iter().map(DynArray::abs).collect());
+    outputs.swap_remove(spec.output_idx())
+}
+
+/// Compares `actual` to `expected` element-wise with `within`, which is also given the value at
+/// the same index in `magnitudes`, returning the worst mismatch along with the first
+/// [REPORTED_DIFFERENCES] mismatches.
+///
+/// Mismatches are ranked by absolute difference, with NaNs ranked worst.
+fn compare_values<T, D>(
+    actual: &Array<T, D>,
+    expected: &Array<T, D>,
+    magnitudes: &Array<T, D>,
+    within: impl Fn(T, T, T) -> bool,
+    to_f64: impl Fn(T) -> f64,
+) -> Result<(), Mismatch>
+where
+    T: Copy,
+    D: ndarray::Dimension,
+{
+    let mut worst: Option<(f64, Difference)> = None;
+    let mut first = vec![];
+    let mut count = 0;
+    for (((index, &a), &e), &m) in actual
+        .view()
+        .into_dyn()
+        .indexed_iter()
+        .zip(expected.iter())
+        .zip(magnitudes.iter())
+    {
+        if within(a, e, m) {
+            continue;
+        }
+        count += 1;
+        let difference = Difference {
+            index: index.slice().to_vec(),
+            expected: to_f64(e),
+            actual: to_f64(a),
+        };
+        let distance = match (difference.actual - difference.expected).abs() {
+            d if d.is_nan() => f64::INFINITY,
+            d => d,
+        };
+        if first.len() < REPORTED_DIFFERENCES {
+            first.push(difference.clone());
+        }
+        if !matches!(&worst, Some((w, _)) if *w >= distance) {
+            worst = Some((distance, difference));
+        }
+    }
+    match worst {
+        None => Ok(()),
+        Some((_, worst)) => Err(Mismatch::Values {
+            worst,
+            first,
+            count,
+        }),
+    }
+}
+
+/// Maps the bits of a `width`-bit IEEE float so that adjacent floats map to adjacent integers.
+///
+/// The difference between two mapped values is the floats' distance in units in the last place.
+fn ordered_float_bits(bits: u64, width: u32) -> i64 {
+    let sign_bit = 1i64 << (width - 1);
+    let bits = bits as i64;
+    if bits & sign_bit == 0 {
+        bits
+    } else {
+        sign_bit - bits
+    }
+}
+
+pub(crate) fn make_array_input_dyn<Tgt: Target>(input: &TensorSpec<Tgt>) -> DynArray<IxDyn> {
     match input.dtype() {
         Dtype::Uint8 => DynArray::Uint8(make_array_input_static(input.shape())),
         Dtype::Sint8 => DynArray::Sint8(make_array_input_static(input.shape())),
@@ -523,6 +1156,128 @@ fn make_array_input_static_bf16(shape: &[DimSize]) -> ArrayD<half::bf16> {
     make_array_input_static::<u8>(shape).map(|x| x.as_())
 }
 
+/// Returns a tensor of random values for [InputStrategy::Random], or for
+/// [InputStrategy::Adversarial] if `adversarial` is set.
+fn make_array_input_random_dyn<Tgt: Target>(
+    input: &TensorSpec<Tgt>,
+    adversarial: bool,
+    rng: &mut StdRng,
+) -> DynArray<IxDyn> {
+    let shape = input.shape();
+    match input.dtype() {
+        Dtype::Uint8 => DynArray::Uint8(make_array_input_random(shape, adversarial, rng)),
+        Dtype::Sint8 => DynArray::Sint8(make_array_input_random(shape, adversarial, rng)),
+        Dtype::Uint16 => DynArray::Uint16(make_array_input_random(shape, adversarial, rng)),
+        Dtype::Sint16 => DynArray::Sint16(make_array_input_random(shape, adversarial, rng)),
+        Dtype::Uint32 => DynArray::Uint32(make_array_input_random(shape, adversarial, rng)),
+        Dtype::Sint32 => DynArray::Sint32(make_array_input_random(shape, adversarial, rng)),
+        Dtype::Float32 => DynArray::Float32(make_array_input_random(shape, adversarial, rng)),
+        Dtype::Bfloat16 => DynArray::Bfloat16(make_array_input_random(shape, adversarial, rng)),
+    }
+}
+
+fn make_array_input_random<T: InputValue>(
+    shape: &[DimSize],
+    adversarial: bool,
+    rng: &mut StdRng,
+) -> ArrayD<T> {
+    let shp_usize = shape
+        .iter()
+        .map(|v| usize::try_from(v.get()).unwrap())
+        .collect::<Vec<_>>();
+    ArrayD::from_shape_simple_fn(IxDyn(&shp_usize), || {
+        if adversarial && rng.gen_ratio(1, 4) {
+            T::EDGE_CASES[rng.gen_range(0..T::EDGE_CASES.len())]
+        } else {
+            T::random(rng)
+        }
+    })
+}
+
+/// A value which can be generated for [InputStrategy::Random] and [InputStrategy::Adversarial].
+trait InputValue: Copy {
+    /// Values likely to expose overflow, saturation, and special floating-point value bugs.
+    const EDGE_CASES: &'static [Self];
+
+    fn random(rng: &mut StdRng) -> Self;
+}
+
+macro_rules! int_input_value {
+    ($t:ty) => {
+        impl InputValue for $t {
+            const EDGE_CASES: &'static [Self] = &[
+                <$t>::MIN,
+                <$t>::MIN.wrapping_add(1),
+                // -1 for signed types.
+                <$t>::MIN.wrapping_add(<$t>::MAX),
+                0,
+                1,
+                <$t>::MAX - 1,
+                <$t>::MAX,
+            ];
+
+            fn random(rng: &mut StdRng) -> Self {
+                rng.gen()
+            }
+        }
+    };
+}
+
+int_input_value!(u8);
+int_input_value!(i8);
+int_input_value!(u16);
+int_input_value!(i16);
+int_input_value!(u32);
+int_input_value!(i32);
+
+// Random floats are small multiples of a power of two so that products and short sums are exact,
+// and reordered reductions stay well within [Tolerance::for_reduction]. Edge cases exclude the
+// largest finite values, whose sums overflow to infinity or not depending on order.
+impl InputValue for f32 {
+    const EDGE_CASES: &'static [Self] = &[
+        0.0,
+        -0.0,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NAN,
+        f32::MIN_POSITIVE,
+        -f32::MIN_POSITIVE,
+        f32::MIN_POSITIVE / 4.0,
+        -f32::MIN_POSITIVE / 4.0,
+    ];
+
+    fn random(rng: &mut StdRng) -> Self {
+        f32::from(rng.gen_range(-8i8..=8)) / 8.0
+    }
+}
+
+impl InputValue for half::bf16 {
+    const EDGE_CASES: &'static [Self] = &[
+        half::bf16::ZERO,
+        half::bf16::NEG_ZERO,
+        half::bf16::INFINITY,
+        half::bf16::NEG_INFINITY,
+        half::bf16::NAN,
+        half::bf16::MIN_POSITIVE,
+        half::bf16::MIN_POSITIVE_SUBNORMAL,
+        half::bf16::MAX_SUBNORMAL,
+    ];
+
+    fn random(rng: &mut StdRng) -> Self {
+        half::bf16::from_f32(f32::random(rng))
+    }
+}
+
+/// Adds the product of `lhs` and `rhs` to `out`, wrapping on overflow.
+fn wrapping_dot_add<T>(lhs: &Array2<T>, rhs: &Array2<T>, out: &mut Array2<T>)
+where
+    T: Copy + 'static,
+    Wrapping<T>: ndarray::LinalgScalar,
+{
+    let product = lhs.mapv(Wrapping).dot(&rhs.mapv(Wrapping));
+    out.zip_mut_with(&product, |o, &p| *o = (Wrapping(*o) + p).0);
+}
+
 /// Returns an iterator that yields infinitely all values of a numeric type in ascending order.
 fn cycle_int_values<T>() -> impl Iterator<Item = T>
 where
@@ -540,7 +1295,7 @@ where
 /// Writes an input tensor for consumption by emitted code.
 ///
 /// Written values have little endian byte ordering.
-fn write_inputs(input: &DynArray<IxDyn>, writer: &mut BufWriter<NamedTempFile>) -> io::Result<()> {
+fn write_inputs<W: Write>(input: &DynArray<IxDyn>, writer: &mut W) -> io::Result<()> {
     match input {
         DynArray::Uint8(a) => {
             for value in a.iter() {
@@ -586,6 +1341,25 @@ fn write_inputs(input: &DynArray<IxDyn>, writer: &mut BufWriter<NamedTempFile>)
     Ok(())
 }
 
+/// Writes `tensor` to a new file at `path` in the [tensor_file] format.
+fn write_tensor_file(tensor: &DynArray<IxDyn>, path: impl AsRef<Path>) -> io::Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    write_tensor_file_header(tensor, &mut writer)?;
+    write_inputs(tensor, &mut writer)?;
+    writer.flush()
+}
+
+fn write_tensor_file_header<W: Write>(tensor: &DynArray<IxDyn>, writer: &mut W) -> io::Result<()> {
+    writer.write_all(&tensor_file::encode_header(
+        tensor.dtype(),
+        &tensor
+            .shape()
+            .iter()
+            .map(|&d| u32::try_from(d).unwrap())
+            .collect::<Vec<_>>(),
+    ))
+}
+
 fn read_output(dtype: Dtype, source: &[u8]) -> io::Result<DynArray<IxDyn>> {
     // Read the shape from the first line. The remainder of the lines are the
     // flattened values; read until we have the reported number of values.
@@ -617,6 +1391,56 @@ fn read_output(dtype: Dtype, source: &[u8]) -> io::Result<DynArray<IxDyn>> {
     })
 }
 
+/// Reads a tensor file written by a generated program given `--binary`.
+fn read_binary_output(dtype: Dtype, source: &[u8]) -> io::Result<DynArray<IxDyn>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let (code, shape) =
+        tensor_file::decode_header(source).ok_or_else(|| invalid("malformed tensor header"))?;
+    if code != tensor_file::dtype_code(dtype) {
+        return Err(invalid("unexpected dtype in tensor header"));
+    }
+    let shape = shape
+        .into_iter()
+        .map(|d| usize::try_from(d).unwrap())
+        .collect::<Vec<_>>();
+    let data = &source[tensor_file::header_len(shape.len())..];
+    if data.len() != shape.iter().product::<usize>() * usize::from(dtype.size()) {
+        return Err(invalid("tensor data doesn't match header's shape"));
+    }
+    Ok(dyn_array_from_le_bytes(dtype, &shape, data))
+}
+
+/// Returns an array of the little-endian values in `data`.
+fn dyn_array_from_le_bytes(dtype: Dtype, shape: &[usize], data: &[u8]) -> DynArray<IxDyn> {
+    match dtype {
+        Dtype::Uint8 => ndarray_from_le_bytes(shape, data, u8::from_le_bytes),
+        Dtype::Sint8 => ndarray_from_le_bytes(shape, data, i8::from_le_bytes),
+        Dtype::Uint16 => ndarray_from_le_bytes(shape, data, u16::from_le_bytes),
+        Dtype::Sint16 => ndarray_from_le_bytes(shape, data, i16::from_le_bytes),
+        Dtype::Uint32 => ndarray_from_le_bytes(shape, data, u32::from_le_bytes),
+        Dtype::Sint32 => ndarray_from_le_bytes(shape, data, i32::from_le_bytes),
+        Dtype::Float32 => ndarray_from_le_bytes(shape, data, f32::from_le_bytes),
+        Dtype::Bfloat16 => ndarray_from_le_bytes(shape, data, half::bf16::from_le_bytes),
+    }
+}
+
+fn ndarray_from_le_bytes<T, const N: usize>(
+    shape: &[usize],
+    data: &[u8],
+    from_le_bytes: fn([u8; N]) -> T,
+) -> DynArray<IxDyn>
+where
+    Array<T, IxDyn>: Into<DynArray<IxDyn>>,
+{
+    let values = data
+        .chunks_exact(N)
+        .map(|chunk| from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    ArrayD::from_shape_vec(ndarray::IxDyn(shape), values)
+        .unwrap()
+        .into()
+}
+
 fn ndarray_from_lines<T, I, V>(shape: &[usize], lines: &mut I) -> DynArray<IxDyn>
 where
     T: FromStr,
@@ -635,3 +1459,252 @@ where
         .unwrap()
         .into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f32_array(values: &[f32]) -> DynArray<IxDyn> {
+        DynArray::Float32(ArrayD::from_shape_vec(IxDyn(&[values.len()]), values.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn test_compare_accepts_reordered_float_sum() {
+        let terms = [0.1f32, 0.2, 0.3, 1e-3, 7.0, 1e-4, 0.25, 3.3];
+        let forward = terms.iter().sum::<f32>();
+        let backward = terms.iter().rev().sum::<f32>();
+        let tolerance = Tolerance::for_reduction(Dtype::Float32, terms.len() as u32);
+        assert!(f32_array(&[forward])
+            .compare(&f32_array(&[backward]), tolerance)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_compare_with_magnitudes_accepts_reordered_cancelling_sum() {
+        // Summed in this order, the 1.0 is lost to rounding and the result is 0.0, not 1.0.
+        let terms = [1e8f32, 1.0, -1e8];
+        let forward = terms.iter().sum::<f32>();
+        let tolerance = Tolerance::for_reduction(Dtype::Float32, terms.len() as u32);
+        let actual = f32_array(&[forward]);
+        let expected = f32_array(&[1.0]);
+        let magnitudes = f32_array(&[terms.iter().map(|t| t.abs()).sum()]);
+        assert!(actual.compare(&expected, tolerance).is_err());
+        assert!(actual
+            .compare_with_magnitudes(&expected, &magnitudes, tolerance)
+            .is_ok());
+        assert!(f32_array(&[1e3])
+            .compare_with_magnitudes(&expected, &magnitudes, tolerance)
+            .is_err());
+    }
+
+    #[test]
+    fn test_compare_rejects_wrong_long_bf16_reduction() {
+        let bf16_array = |value: f32| {
+            DynArray::Bfloat16(ArrayD::from_elem(IxDyn(&[1]), half::bf16::from_f32(value)))
+        };
+        let tolerance = Tolerance::for_reduction(Dtype::Bfloat16, 128);
+        // The sum of 128 ones, off by rounding, and missing an eighth or half of its terms.
+        let expected = bf16_array(128.0);
+        assert!(bf16_array(127.0).compare(&expected, tolerance).is_ok());
+        assert!(bf16_array(112.0).compare(&expected, tolerance).is_err());
+        assert!(bf16_array(64.0).compare(&expected, tolerance).is_err());
+    }
+
+    #[test]
+    fn test_compare_reports_worst_mismatch() {
+        let expected = f32_array(&[1.0, 2.0, 3.0, 4.0]);
+        let actual = f32_array(&[1.5, 2.0, 5.0, 4.0]);
+        let tolerance = Tolerance::for_reduction(Dtype::Float32, 4);
+        assert_eq!(
+            actual.compare(&expected, tolerance),
+            Err(Mismatch::Values {
+                worst: Difference {
+                    index: vec![2],
+                    expected: 3.0,
+                    actual: 5.0,
+                },
+                first: vec![
+                    Difference {
+                        index: vec![0],
+                        expected: 1.0,
+                        actual: 1.5,
+                    },
+                    Difference {
+                        index: vec![2],
+                        expected: 3.0,
+                        actual: 5.0,
+                    },
+                ],
+                count: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_compare_is_exact_for_integers() {
+        let expected = DynArray::Uint32(ArrayD::from_elem(IxDyn(&[2, 2]), 7u32));
+        let mut actual = expected.clone();
+        if let DynArray::Uint32(a) = &mut actual {
+            a[[1, 0]] = 8;
+        }
+        let tolerance = Tolerance::for_reduction(Dtype::Uint32, 1024);
+        assert_eq!(tolerance, Tolerance::EXACT);
+        assert_eq!(
+            actual.compare(&expected, tolerance),
+            Err(Mismatch::Values {
+                worst: Difference {
+                    index: vec![1, 0],
+                    expected: 7.0,
+                    actual: 8.0,
+                },
+                first: vec![Difference {
+                    index: vec![1, 0],
+                    expected: 7.0,
+                    actual: 8.0,
+                }],
+                count: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_compare_reports_at_most_reported_differences() {
+        let expected = f32_array(&[0.0; 20]);
+        let actual = f32_array(&[1.0; 20]);
+        match actual.compare(&expected, Tolerance::EXACT) {
+            Err(Mismatch::Values { first, count, .. }) => {
+                assert_eq!(count, 20);
+                assert_eq!(
+                    first.iter().map(|d| d.index[0]).collect::<Vec<_>>(),
+                    (0..REPORTED_DIFFERENCES).collect::<Vec<_>>()
+                );
+            }
+            r => panic!("expected a value mismatch, got {r:?}"),
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_check_correctness_reports_failed_run() {
+        use crate::layout::row_major;
+        use crate::lspec;
+        use crate::target::{CpuMemoryLevel::GL, X86Target};
+
+        let spec = Spec::<X86Target>(
+            lspec!(Zero([2, 2], (u32, GL, row_major(2)))),
+            X86Target::max_mem(),
+        );
+        // `false` ignores its arguments and exits unsuccessfully, like a crashing kernel.
+        let artifact = BuiltArtifact::new(
+            "false".into(),
+            None,
+            None,
+            vec![Dtype::Uint32],
+            String::new(),
+        );
+        let failure = artifact.check_correctness(&spec).unwrap_err();
+        assert!(
+            matches!(failure.mismatch, Mismatch::RunFailed(_)),
+            "{failure}"
+        );
+        assert!(failure.actual.is_none());
+    }
+
+    #[test]
+    fn test_dump_writes_report_and_tensor_files() {
+        let expected = f32_array(&[1.0, 2.0]);
+        let actual = f32_array(&[1.0, 3.0]);
+        let failure = CorrectnessFailure {
+            spec: "Zero((2, f32, GL, row_major), serial)".to_string(),
+            strategy: InputStrategy::Random,
+            seed: Some(3),
+            inputs: vec![f32_array(&[0.0, 0.0])],
+            mismatch: actual.compare(&expected, Tolerance::EXACT).unwrap_err(),
+            expected,
+            actual: Some(actual),
+            source_path: None,
+        };
+        let dir = tempfile::tempdir().unwrap();
+        failure.dump(dir.path()).unwrap();
+
+        let report = fs::read_to_string(dir.path().join("report.txt")).unwrap();
+        assert!(report.contains("Random with seed 3"), "{report}");
+        assert!(report.contains("[1]: expected 2 but got 3"), "{report}");
+        for name in ["input_0.bin", "expected.bin", "actual.bin"] {
+            let bytes = fs::read(dir.path().join(name)).unwrap();
+            assert_eq!(
+                tensor_file::decode_header(&bytes),
+                Some((tensor_file::dtype_code(Dtype::Float32), vec![2])),
+                "{name}"
+            );
+        }
+        let actual_bytes = fs::read(dir.path().join("actual.bin")).unwrap();
+        assert_eq!(
+            Some(read_binary_output(Dtype::Float32, &actual_bytes).unwrap()),
+            failure.actual
+        );
+    }
+
+    #[test]
+    fn test_random_inputs_are_reproducible_from_seed() {
+        let shape = [DimSize::new(4).unwrap(), DimSize::new(8).unwrap()];
+        for adversarial in [false, true] {
+            let make = |seed| {
+                make_array_input_random::<f32>(
+                    &shape,
+                    adversarial,
+                    &mut StdRng::seed_from_u64(seed),
+                )
+            };
+            let a = make(1);
+            // NaNs compare unequal, so compare bits.
+            assert_eq!(a.mapv(f32::to_bits), make(1).mapv(f32::to_bits));
+            assert_ne!(a.mapv(f32::to_bits), make(2).mapv(f32::to_bits));
+        }
+    }
+
+    #[test]
+    fn test_adversarial_inputs_include_edge_cases() {
+        let shape = [DimSize::new(64).unwrap(), DimSize::new(64).unwrap()];
+        let mut rng = StdRng::seed_from_u64(0);
+        let ints = make_array_input_random::<i16>(&shape, true, &mut rng);
+        assert!(ints.iter().any(|&x| x == i16::MIN));
+        assert!(ints.iter().any(|&x| x == i16::MAX));
+        let floats = make_array_input_random::<half::bf16>(&shape, true, &mut rng);
+        assert!(floats.iter().any(|x| x.is_nan()));
+        assert!(floats.iter().any(|x| x.is_infinite()));
+        assert!(floats
+            .iter()
+            .any(|&x| x == half::bf16::ZERO && x.is_sign_negative()));
+    }
+
+    #[test]
+    fn test_matmul_reference_wraps_on_overflow() {
+        let lhs = DynArray::Uint8(array![[200u8, 100]]);
+        let rhs = DynArray::Uint8(array![[2u8], [1]]);
+        let mut out = DynArray::Uint8(array![[10u8]]);
+        lhs.dot_inplace(&rhs, &mut out);
+        assert_eq!(
+            out,
+            DynArray::Uint8(array![[10u8.wrapping_add(144).wrapping_add(100)]])
+        );
+    }
+
+    #[test]
+    fn test_ulps_between_adjacent_floats_is_one() {
+        for x in [-1.5f32, 0.0, 1e-30, 2.0] {
+            let next = if x.is_sign_negative() {
+                f32::from_bits(x.to_bits() - 1)
+            } else {
+                f32::from_bits(x.to_bits() + 1)
+            };
+            let ulps = ordered_float_bits(x.to_bits().into(), 32)
+                .abs_diff(ordered_float_bits(next.to_bits().into(), 32));
+            assert_eq!(ulps, 1, "{x}");
+        }
+        assert_eq!(
+            ordered_float_bits((-0.0f32).to_bits().into(), 32),
+            ordered_float_bits(0.0f32.to_bits().into(), 32)
+        );
+    }
+}
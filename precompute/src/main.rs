@@ -23,7 +23,7 @@ use {
 };
 
 use morello::common::{DimSize, Dtype};
-use morello::db::FilesDatabase;
+use morello::db::{Database, FilesDatabase, InMemoryDatabase};
 use morello::grid::compose::Compose;
 use morello::grid::downscale::DownscaleSurMap;
 use morello::grid::general::SurMap;
@@ -140,24 +140,34 @@ fn main() -> Result<()> {
     #[cfg(feature = "db-stats")]
     log::info!("DB statistic collection enabled");
 
-    let db = FilesDatabase::new(
-        args.db.as_deref(),
-        true,
-        K,
-        args.cache_size,
-        threads,
-        args.tiling_depth,
-    );
-    main_per_db(&args, db, args.db.as_deref());
+    match args.db.as_deref() {
+        Some(db_path) => {
+            let db = FilesDatabase::new(
+                Some(db_path),
+                true,
+                K,
+                args.cache_size,
+                threads,
+                args.tiling_depth,
+            );
+            main_per_db(&args, db, Some(db_path));
+        }
+        None => {
+            let db = InMemoryDatabase::new(true, K, args.tiling_depth);
+            main_per_db(&args, db, None);
+        }
+    }
 
     Ok(())
 }
 
-fn main_per_db(
+fn main_per_db<D>(
     args: &Args,
-    #[allow(unused_mut)] mut db: FilesDatabase, // mut when db-stats enabled
+    #[allow(unused_mut)] mut db: D, // mut when db-stats enabled
     db_path: Option<&path::Path>,
-) {
+) where
+    D: Database + Sync,
+{
     let MemoryLimits::Standard(top) = X86Target::max_mem();
 
     // TODO: Most of the following details aren't used in computing the bound.